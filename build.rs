@@ -0,0 +1,9 @@
+fn main() {
+    // the gRPC service is generated from its protocol, which needs protoc
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/analyzer.proto").expect("the protocol compiles");
+
+    // the Node.js addon links against symbols node provides when it loads it
+    #[cfg(feature = "node")]
+    napi_build::setup();
+}
@@ -0,0 +1,81 @@
+#ifndef STACK_DISTANCE_H
+#define STACK_DISTANCE_H
+
+/* Generated by cbindgen from src/ffi.rs; don't edit by hand. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+// Computes stack distances one access at a time, for traces too long to hold in memory.
+typedef struct StackDistanceProcessor StackDistanceProcessor;
+
+// A stack distance histogram, owned by this library until it's passed to
+// `stack_distance_histogram_free`.
+typedef struct StackDistanceHistogram {
+  // The ith entry is the number of accesses with distance i.
+  uint64_t *counts;
+  // The number of entries of `counts`.
+  size_t len;
+  // The number of first accesses, whose distance is infinite.
+  uint64_t infinities;
+} StackDistanceHistogram;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Compute the stack distance histogram of `len` addresses into `out`.
+//
+// # Safety
+//
+// `trace` must point to `len` addresses, or may be null if `len` is zero, and `out` must be
+// valid for writes.
+int stack_distance_compute(const uint64_t *trace, size_t len, StackDistanceHistogram *out);
+
+// Free the counts of a histogram this library filled in, leaving it empty.
+//
+// # Safety
+//
+// `histogram` must be null, or have been filled in by this library and not freed since.
+void stack_distance_histogram_free(StackDistanceHistogram *histogram);
+
+// Create a processor, to be freed with `stack_distance_processor_free`.
+StackDistanceProcessor *stack_distance_processor_new(void);
+
+// Process the next `len` addresses of the trace.
+//
+// # Safety
+//
+// `processor` must come from `stack_distance_processor_new`, and `addresses` must point to `len`
+// addresses, or may be null if `len` is zero.
+int stack_distance_processor_access(StackDistanceProcessor *processor,
+                                    const uint64_t *addresses,
+                                    size_t len);
+
+// Write the histogram of every address processed since the processor was created or reset.
+//
+// # Safety
+//
+// `processor` must come from `stack_distance_processor_new`, and `out` must be valid for writes.
+int stack_distance_processor_histogram(const StackDistanceProcessor *processor,
+                                       StackDistanceHistogram *out);
+
+// Forget every address processed.
+//
+// # Safety
+//
+// `processor` must come from `stack_distance_processor_new`.
+int stack_distance_processor_reset(StackDistanceProcessor *processor);
+
+// Free a processor.
+//
+// # Safety
+//
+// `processor` must be null, or come from `stack_distance_processor_new` and not have been freed.
+void stack_distance_processor_free(StackDistanceProcessor *processor);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* STACK_DISTANCE_H */
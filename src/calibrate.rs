@@ -0,0 +1,158 @@
+//! Contains calibration of real cache implementations against ideal LRU.
+//!
+//! A third-party cache, like one from the `lru` or `moka` crates, implements [`CacheAdapter`]
+//! with a lookup that inserts on a miss. [`calibrate`] then drives a cache of each size with a
+//! trace and compares its hit ratio with the one the stack distances predict.
+
+use std::fmt::Display;
+
+use crate::mrc::MissRatioCurve;
+use crate::trace::Trace;
+
+/// A cache which can be driven by a trace.
+pub trait CacheAdapter {
+    /// Access a key, returning whether it hit. A miss should insert the key.
+    fn access(&mut self, key: u32) -> bool;
+}
+
+impl<F: FnMut(u32) -> bool> CacheAdapter for F {
+    fn access(&mut self, key: u32) -> bool {
+        self(key)
+    }
+}
+
+/// A cache's hit ratio at one size, next to the one ideal LRU gets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Calibration {
+    pub size: usize,
+    /// The hit ratio of the cache.
+    pub observed: f64,
+    /// The hit ratio of an LRU cache of the same size.
+    pub predicted: f64,
+}
+
+impl Calibration {
+    /// How much higher the observed hit ratio is than the prediction.
+    pub fn error(&self) -> f64 {
+        self.observed - self.predicted
+    }
+}
+
+/// A cache's calibrations at each size.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CalibrationReport {
+    pub calibrations: Vec<Calibration>,
+}
+
+impl CalibrationReport {
+    /// The largest error by magnitude, or zero if there are no calibrations.
+    pub fn max_abs_error(&self) -> f64 {
+        self.calibrations
+            .iter()
+            .map(|calibration| calibration.error().abs())
+            .fold(0.0, f64::max)
+    }
+
+    /// Whether the cache is within `tolerance` of LRU's hit ratio at every size.
+    pub fn is_within(&self, tolerance: f64) -> bool {
+        self.max_abs_error() <= tolerance
+    }
+}
+
+impl Display for CalibrationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{:>10}  {:>8}  {:>9}  {:>8}",
+            "size", "observed", "predicted", "error"
+        )?;
+        for calibration in &self.calibrations {
+            writeln!(
+                f,
+                "{:>10}  {:>8.4}  {:>9.4}  {:>+8.4}",
+                calibration.size,
+                calibration.observed,
+                calibration.predicted,
+                calibration.error()
+            )?;
+        }
+        writeln!(f, "max error {:.4}", self.max_abs_error())
+    }
+}
+
+/// Drive a new cache of each size with the trace, comparing its hit ratio with LRU's.
+pub fn calibrate<C: CacheAdapter>(
+    trace: &Trace,
+    sizes: &[usize],
+    mut new_cache: impl FnMut(usize) -> C,
+) -> CalibrationReport {
+    let mrc = MissRatioCurve::from_histogram(&trace.stack_distance_histogram());
+
+    let calibrations = sizes
+        .iter()
+        .map(|&size| {
+            let mut cache = new_cache(size);
+            let hits = trace
+                .as_slice()
+                .iter()
+                .filter(|&&key| cache.access(key))
+                .count();
+
+            #[allow(clippy::cast_precision_loss)]
+            let observed = if trace.is_empty() {
+                0.0
+            } else {
+                hits as f64 / trace.len() as f64
+            };
+            Calibration {
+                size,
+                observed,
+                predicted: mrc.hit_ratio(size),
+            }
+        })
+        .collect();
+
+    CalibrationReport { calibrations }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A cache of the given size with the given policy, keeping its keys oldest first.
+    fn cache(size: usize, lru: bool) -> impl FnMut(u32) -> bool {
+        let mut keys = Vec::new();
+        move |key| {
+            let position = keys.iter().position(|&k| k == key);
+            if let Some(position) = position {
+                if lru {
+                    keys.remove(position);
+                    keys.push(key);
+                }
+                return true;
+            }
+            if size > 0 {
+                if keys.len() == size {
+                    keys.remove(0);
+                }
+                keys.push(key);
+            }
+            false
+        }
+    }
+
+    #[test]
+    fn calibrate_works() {
+        let trace = Trace::from(vec![0, 1, 0, 2, 0, 3, 0, 1, 2, 0]);
+        let sizes = [0, 1, 2, 3, 4];
+
+        let lru = calibrate(&trace, &sizes, |size| cache(size, true));
+        assert_eq!(lru.calibrations.len(), sizes.len());
+        assert!(lru.is_within(1e-12));
+
+        // FIFO evicts the hot symbol at size 2, which LRU keeps
+        let fifo = calibrate(&trace, &sizes, |size| cache(size, false));
+        assert!(!fifo.is_within(1e-12));
+        assert!(fifo.calibrations[2].error() < 0.0);
+    }
+}
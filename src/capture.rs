@@ -0,0 +1,128 @@
+//! Contains capture of the data addresses `perf mem` samples, for the locality of running
+//! processes.
+//!
+//! `perf` records in pipe mode and `perf script` decodes its samples, so nothing touches the
+//! disk between the process and the analysis.
+
+use std::ffi::{OsStr, OsString};
+use std::io::{self, BufRead, BufReader};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+
+use tracing::{debug, warn};
+
+use crate::online::OnlineAnalyzer;
+
+/// Where the samples come from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// Run a command under `perf mem record`.
+    Record(Vec<OsString>),
+    /// Decode a `perf.data` file recorded earlier.
+    File(PathBuf),
+}
+
+/// Parse a data address as `perf script -F addr` prints it, in hex with or without `0x`.
+pub fn parse_address(line: &str) -> Option<u64> {
+    let line = line.trim();
+    let digits = line.strip_prefix("0x").unwrap_or(line);
+    u64::from_str_radix(digits, 16).ok()
+}
+
+/// Analyze every address read, one per line, coarsened by `shift` bits, returning how many were
+/// analyzed. Lines without an address, like samples without one, are skipped.
+pub fn analyze<R: BufRead>(
+    reader: R,
+    shift: u32,
+    analyzer: &mut OnlineAnalyzer,
+) -> io::Result<usize> {
+    let mut samples = 0;
+    for line in reader.lines() {
+        match parse_address(&line?) {
+            Some(0) | None => {}
+            Some(address) => {
+                analyzer.access(address.checked_shr(shift).unwrap_or(0));
+                samples += 1;
+            }
+        }
+    }
+    Ok(samples)
+}
+
+// Spawn a child, saying which on failure, since a missing `perf` is the likeliest one.
+fn spawn(command: &mut Command) -> io::Result<Child> {
+    command.spawn().map_err(|e| {
+        let program = command.get_program().to_string_lossy().into_owned();
+        io::Error::new(e.kind(), format!("couldn't run {}: {}", program, e))
+    })
+}
+
+// Wait for a child, failing unless it succeeds.
+fn wait(mut child: Child, name: &str) -> io::Result<()> {
+    let status = child.wait()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("{} failed: {}", name, status)))
+    }
+}
+
+/// Capture samples with the `perf` binary at `perf`, returning the analysis of their addresses.
+pub fn capture(perf: &OsStr, source: &Source, shift: u32) -> io::Result<OnlineAnalyzer> {
+    let mut script = Command::new(perf);
+    script.args(["script", "-F", "addr", "-i"]);
+
+    let record = match source {
+        Source::Record(command) => {
+            let mut record = Command::new(perf);
+            record
+                .args(["mem", "record", "-o", "-", "--"])
+                .args(command)
+                .stdout(Stdio::piped());
+            let mut record = spawn(&mut record)?;
+            script
+                .arg("-")
+                .stdin(record.stdout.take().expect("stdout is piped"));
+            Some(record)
+        }
+        Source::File(path) => {
+            script.arg(path);
+            None
+        }
+    };
+
+    debug!(?script, "decoding samples");
+    let mut script = spawn(script.stdout(Stdio::piped()))?;
+    let mut analyzer = OnlineAnalyzer::new();
+    let samples = analyze(
+        BufReader::new(script.stdout.take().expect("stdout is piped")),
+        shift,
+        &mut analyzer,
+    )?;
+
+    if let Some(record) = record {
+        wait(record, "perf mem record")?;
+    }
+    wait(script, "perf script")?;
+    if samples == 0 {
+        warn!("perf sampled no data addresses; its events may be unsupported on this machine");
+    }
+    Ok(analyzer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn analyze_works() {
+        assert_eq!(parse_address("  7ffd1000"), Some(0x7ffd_1000));
+        assert_eq!(parse_address("0x10"), Some(0x10));
+        assert_eq!(parse_address("addr"), None);
+
+        let mut analyzer = OnlineAnalyzer::new();
+        let input = "7ffd1000\n7ffd2000\n\n0\n7ffd1008\n";
+        assert_eq!(analyze(input.as_bytes(), 6, &mut analyzer).unwrap(), 3);
+        assert_eq!(analyzer.histogram().frequencies(), [0, 1]);
+    }
+}
@@ -0,0 +1,154 @@
+//! Contains SVG charts of histograms and miss ratio curves.
+//!
+//! With the `evcxr` feature, [`Histogram`] and [`MissRatioCurve`] also get an `evcxr_display`
+//! method, so a Rust Jupyter kernel renders them as these charts inline.
+
+use std::fmt::Write;
+
+use crate::histogram::Histogram;
+use crate::mrc::MissRatioCurve;
+
+const WIDTH: f64 = 480.0;
+const HEIGHT: f64 = 240.0;
+// room for the axis labels
+const MARGIN: f64 = 32.0;
+
+// Open a chart with its axes, labelling the x axis from `x_label` and the y axis up to `y_max`.
+fn open(x_label: &str, y_label: &str, y_max: &str) -> String {
+    let (left, bottom) = (MARGIN, HEIGHT - MARGIN);
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+         font-family=\"sans-serif\" font-size=\"11\">",
+        WIDTH, HEIGHT
+    );
+    write!(
+        svg,
+        "<path d=\"M{left} {top} V{bottom} H{right}\" fill=\"none\" stroke=\"black\"/>\
+         <text x=\"{center}\" y=\"{label_y}\" text-anchor=\"middle\">{}</text>\
+         <text x=\"4\" y=\"{top}\">{}</text>\
+         <text x=\"4\" y=\"{bottom}\">0</text>\
+         <text x=\"4\" y=\"{y_label_y}\">{}</text>",
+        x_label,
+        y_max,
+        y_label,
+        top = MARGIN / 2.0,
+        right = WIDTH - MARGIN / 2.0,
+        center = (left + WIDTH) / 2.0,
+        label_y = HEIGHT - 8.0,
+        y_label_y = HEIGHT / 2.0,
+    )
+    .expect("writing to a string succeeds");
+    svg
+}
+
+/// A bar chart of the count at each distance, with the infinities as a last, lighter bar.
+pub fn histogram_svg(histogram: &Histogram) -> String {
+    let counts: Vec<usize> = histogram
+        .frequencies()
+        .iter()
+        .copied()
+        .chain([histogram.infinities()])
+        .collect();
+    let max = counts.iter().copied().max().unwrap_or(0).max(1);
+    let mut svg = open("distance", "count", &max.to_string());
+
+    #[allow(clippy::cast_precision_loss)]
+    let (bar_width, max) = ((WIDTH - 1.5 * MARGIN) / counts.len() as f64, max as f64);
+    for (i, &count) in counts.iter().enumerate() {
+        #[allow(clippy::cast_precision_loss)]
+        let (x, height) = (
+            MARGIN + i as f64 * bar_width,
+            (HEIGHT - 1.5 * MARGIN) * count as f64 / max,
+        );
+        let (fill, label) = if i + 1 == counts.len() {
+            ("#9ecae1", "inf".to_string())
+        } else {
+            ("#3182bd", i.to_string())
+        };
+        write!(
+            svg,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\">\
+             <title>{}: {}</title></rect>",
+            x,
+            HEIGHT - MARGIN - height,
+            bar_width * 0.9,
+            height,
+            fill,
+            label,
+            count
+        )
+        .expect("writing to a string succeeds");
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// A line chart of the miss ratio at each cache size.
+pub fn mrc_svg(mrc: &MissRatioCurve) -> String {
+    let mut svg = open("cache size", "miss ratio", "1");
+    let ratios = mrc.ratios();
+
+    #[allow(clippy::cast_precision_loss)]
+    let step = (WIDTH - 1.5 * MARGIN) / ratios.len().saturating_sub(1).max(1) as f64;
+    let points: Vec<String> = ratios
+        .iter()
+        .enumerate()
+        .map(|(size, ratio)| {
+            #[allow(clippy::cast_precision_loss)]
+            let x = MARGIN + size as f64 * step;
+            let y = HEIGHT - MARGIN - (HEIGHT - 1.5 * MARGIN) * ratio;
+            format!("{:.1},{:.1}", x, y)
+        })
+        .collect();
+    write!(
+        svg,
+        "<polyline points=\"{}\" fill=\"none\" stroke=\"#3182bd\" stroke-width=\"2\"/></svg>",
+        points.join(" ")
+    )
+    .expect("writing to a string succeeds");
+    svg
+}
+
+// Hand a chart to evcxr, which renders whatever a value prints between these markers.
+#[cfg(feature = "evcxr")]
+fn evcxr_display(svg: &str) {
+    println!(
+        "EVCXR_BEGIN_CONTENT image/svg+xml\n{}\nEVCXR_END_CONTENT",
+        svg
+    );
+}
+
+#[cfg(feature = "evcxr")]
+impl Histogram {
+    /// Render the histogram as a chart in a Rust Jupyter kernel.
+    pub fn evcxr_display(&self) {
+        evcxr_display(&histogram_svg(self));
+    }
+}
+
+#[cfg(feature = "evcxr")]
+impl MissRatioCurve {
+    /// Render the curve as a chart in a Rust Jupyter kernel.
+    pub fn evcxr_display(&self) {
+        evcxr_display(&mrc_svg(self));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn charts() {
+        let histogram = Histogram::new(vec![0, 1, 2], 3);
+        let svg = histogram_svg(&histogram);
+        assert!(svg.starts_with("<svg") && svg.ends_with("</svg>"));
+        assert_eq!(svg.matches("<rect").count(), 4);
+        assert!(svg.contains("<title>inf: 3</title>"));
+
+        let svg = mrc_svg(&MissRatioCurve::from_histogram(&histogram));
+        assert_eq!(svg.matches("<polyline").count(), 1);
+        assert!(!svg.contains("NaN"));
+        assert!(!mrc_svg(&MissRatioCurve::from_histogram(&Histogram::default())).contains("NaN"));
+    }
+}
@@ -0,0 +1,155 @@
+//! Contains Che's approximation of LRU hit ratios.
+//!
+//! Under the independent reference model, an LRU cache of size `C` behaves as if each object
+//! stays cached for a fixed characteristic time `T` after its last access, where `T` is chosen
+//! so that the expected number of cached objects is `C`. This only needs the frequency
+//! histogram, so it's much cheaper than computing stack distances.
+
+use crate::mrc::MissRatioCurve;
+
+/// Che's approximation for a given access distribution.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Che {
+    // the access probability of each referenced symbol
+    popularity: Vec<f64>,
+}
+
+impl Che {
+    /// Build the approximation from a frequency histogram.
+    pub fn new(frequencies: &[usize]) -> Self {
+        let total: usize = frequencies.iter().sum();
+
+        #[allow(clippy::cast_precision_loss)]
+        let popularity = frequencies
+            .iter()
+            .filter(|&&n| n != 0)
+            .map(|&n| n as f64 / total as f64)
+            .collect();
+
+        Self { popularity }
+    }
+
+    // The expected number of cached objects with characteristic time `t`.
+    fn occupancy(&self, t: f64) -> f64 {
+        self.popularity.iter().map(|q| 1.0 - (-q * t).exp()).sum()
+    }
+
+    /// Calculate the characteristic time of a cache of the given size, in accesses.
+    ///
+    /// Returns infinity if every object fits in the cache.
+    pub fn characteristic_time(&self, size: usize) -> f64 {
+        #[allow(clippy::cast_precision_loss)]
+        let size = size as f64;
+
+        #[allow(clippy::cast_precision_loss)]
+        if size >= self.popularity.len() as f64 {
+            return f64::INFINITY;
+        }
+
+        // occupancy is increasing in t, so bisect once we've bracketed the size
+        let mut high = 1.0;
+        while self.occupancy(high) < size {
+            high *= 2.0;
+        }
+        let mut low = 0.0;
+        for _ in 0..100 {
+            let mid = (low + high) / 2.0;
+            if self.occupancy(mid) < size {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        (low + high) / 2.0
+    }
+
+    /// Calculate the hit probability of each referenced symbol, in symbol order.
+    pub fn hit_probabilities(&self, size: usize) -> Vec<f64> {
+        let t = self.characteristic_time(size);
+        self.popularity
+            .iter()
+            .map(|q| 1.0 - (-q * t).exp())
+            .collect()
+    }
+
+    /// Calculate the aggregate hit ratio of a cache of the given size.
+    pub fn hit_ratio(&self, size: usize) -> f64 {
+        self.popularity
+            .iter()
+            .zip(self.hit_probabilities(size))
+            .map(|(q, h)| q * h)
+            .sum()
+    }
+
+    /// Calculate the aggregate miss ratio of a cache of the given size.
+    pub fn miss_ratio(&self, size: usize) -> f64 {
+        if self.popularity.is_empty() {
+            return 0.0;
+        }
+        1.0 - self.hit_ratio(size)
+    }
+
+    /// Compare the approximation against an exact miss-ratio curve.
+    ///
+    /// Returns the approximate and exact miss ratios at each size. Note the approximation is for
+    /// the steady state, so it doesn't count the compulsory misses the exact curve includes.
+    pub fn compare(&self, exact: &MissRatioCurve, sizes: &[usize]) -> Vec<(f64, f64)> {
+        sizes
+            .iter()
+            .map(|&size| (self.miss_ratio(size), exact.miss_ratio(size)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::Trace;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn uniform() {
+        // with n equally popular objects, a cache of size c hits c/n of the time
+        let che = Che::new(&[0, 5, 5, 5, 5]);
+        assert!(close(che.hit_ratio(1), 0.25));
+        assert!(close(che.hit_ratio(3), 0.75));
+        assert!(che.hit_probabilities(2).iter().all(|&h| close(h, 0.5)));
+    }
+
+    #[test]
+    fn edges() {
+        let che = Che::new(&[3, 1]);
+        assert!(close(che.characteristic_time(0), 0.0));
+        assert!(close(che.miss_ratio(0), 1.0));
+        assert_eq!(che.characteristic_time(2), f64::INFINITY);
+        assert!(close(che.miss_ratio(2), 0.0));
+        assert_eq!(Che::new(&[]).miss_ratio(1), 0.0);
+    }
+
+    #[test]
+    fn skewed_favors_popular() {
+        let che = Che::new(&[8, 1, 1]);
+        let probabilities = che.hit_probabilities(1);
+        assert!(probabilities[0] > probabilities[1]);
+        assert!(close(
+            probabilities.iter().sum::<f64>(),
+            1.0 // the expected occupancy is the cache size
+        ));
+    }
+
+    #[test]
+    fn compare_cyclic() {
+        // a long cyclic trace is uniform, and stack distances are all 3, so the exact curve has
+        // no hits below size 4 while Che predicts some
+        let trace = Trace::from((0..400).map(|i| i % 4).collect::<Vec<_>>());
+        let comparison =
+            Che::new(&trace.frequency_histogram()).compare(&MissRatioCurve::from(&trace), &[2, 4]);
+        assert!(close(comparison[0].0, 0.5));
+        assert!(close(comparison[0].1, 1.0));
+        assert!(close(comparison[1].0, 0.0));
+        assert!(close(comparison[1].1, 0.01));
+    }
+}
@@ -0,0 +1,304 @@
+//! Contains classes of equivalent canonical traces.
+//!
+//! Traces which share a stack distance histogram are equivalent for anything which only depends
+//! on the histogram, like miss ratios.
+//! A canonical trace is determined by its sequence of stack distances, and a sequence is valid
+//! exactly when each finite distance `d` comes after at least `d + 1` infinities, since the stack
+//! must be that deep. So classes can be counted without enumerating their traces.
+
+use std::collections::HashMap;
+use std::thread;
+
+use num_bigint::BigUint;
+
+use crate::counting;
+use crate::histogram::Histogram;
+use crate::trace::{Trace, TraceIter};
+
+/// The canonical traces of one length which share a stack distance histogram.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HistogramClass {
+    /// The shared histogram.
+    pub histogram: Histogram,
+    /// A trace in the class.
+    pub representative: Trace,
+    /// The number of canonical traces in the class.
+    pub size: BigUint,
+}
+
+/// An iterator over the stack distance histograms of canonical traces of a given length, with a
+/// representative and the size of each class.
+///
+/// Histograms are ordered by number of infinities, then lexicographically by frequencies.
+#[derive(Debug, Clone)]
+pub struct HistogramClasses {
+    len: usize,
+    // the number of infinities, and the frequency of each distance less than it
+    next: Option<(usize, Vec<usize>)>,
+    binomials: Vec<Vec<BigUint>>,
+}
+
+impl HistogramClasses {
+    /// Enumerate the classes of canonical traces of length `len`.
+    pub fn new(len: usize) -> Self {
+        // the only trace of length zero has no infinities; otherwise there's at least one
+        let next = if len == 0 {
+            Some((0, Vec::new()))
+        } else {
+            Some((1, vec![len - 1]))
+        };
+        Self {
+            len,
+            next,
+            binomials: counting::binomials(len),
+        }
+    }
+
+    // The number of distance sequences with the given infinities and frequencies.
+    fn size(&self, infinities: usize, freqs: &[usize]) -> BigUint {
+        // Insert distances into the sequence of infinities from the deepest down. Distance d goes
+        // anywhere after the (d + 1)th infinity, among the later infinities and the deeper
+        // distances already inserted, and identical distances can be arranged in any order.
+        let mut size = BigUint::from(1_u32);
+        let mut tail = 0;
+        for (d, &count) in freqs.iter().enumerate().rev() {
+            let later = infinities - d - 1 + tail;
+            size *= &self.binomials[later + count][count];
+            tail += count;
+        }
+        size
+    }
+}
+
+// Build the canonical trace with the given stack distances.
+fn from_distances(distances: impl IntoIterator<Item = Option<usize>>) -> Trace {
+    // the LRU stack, with the most recent symbol at the front
+    let mut stack: Vec<u32> = Vec::new();
+    let mut trace = Vec::new();
+    for distance in distances {
+        #[allow(clippy::cast_possible_truncation)]
+        let symbol = distance.map_or(stack.len() as u32, |d| stack.remove(d));
+        stack.insert(0, symbol);
+        trace.push(symbol);
+    }
+    Trace::from(trace)
+}
+
+impl Iterator for HistogramClasses {
+    type Item = HistogramClass;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (infinities, mut freqs) = self.next.take()?;
+
+        // the histogram's frequencies don't keep trailing zeros
+        let trimmed = freqs.len() - freqs.iter().rev().take_while(|&&n| n == 0).count();
+        let histogram = Histogram::new(freqs[..trimmed].to_vec(), infinities);
+        let class = HistogramClass {
+            representative: witness(&histogram).expect("every enumerated histogram is achievable"),
+            histogram,
+            size: self.size(infinities, &freqs),
+        };
+
+        // step to the next way to split the finite accesses among the distances, moving one
+        // from the last non-empty frequency (other than the final one) to the next distance
+        let last = freqs.len().saturating_sub(1);
+        self.next = match freqs[..last].iter().rposition(|&n| n > 0) {
+            Some(i) => {
+                let carried = freqs[last];
+                freqs[last] = 0;
+                freqs[i] -= 1;
+                freqs[i + 1] += carried + 1;
+                Some((infinities, freqs))
+            }
+            None if infinities < self.len => {
+                let mut freqs = vec![0; infinities + 1];
+                freqs[0] = self.len - infinities - 1;
+                Some((infinities + 1, freqs))
+            }
+            None => None,
+        };
+
+        Some(class)
+    }
+}
+
+/// Find a trace with the given stack distance histogram, if there is one.
+///
+/// A histogram is achievable exactly when every finite distance is less than the number of
+/// infinities, so that the stack can be that deep, and there's an infinity if there's anything.
+pub fn witness(histogram: &Histogram) -> Option<Trace> {
+    let infinities = histogram.infinities();
+    let freqs = histogram.frequencies();
+    let achievable = freqs.iter().skip(infinities).all(|&n| n == 0)
+        && (infinities > 0 || histogram.total() == 0);
+
+    // all the infinities first makes the stack deep enough for every distance
+    achievable.then(|| {
+        from_distances(
+            std::iter::repeat_n(None, infinities).chain(
+                freqs
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(d, &count)| std::iter::repeat_n(Some(d), count)),
+            ),
+        )
+    })
+}
+
+/// Enumerate the stack distance histograms achievable by traces of length `len`, with a trace
+/// achieving each.
+pub fn achievable_histograms(len: usize) -> impl Iterator<Item = (Histogram, Trace)> {
+    HistogramClasses::new(len).map(|class| (class.histogram, class.representative))
+}
+
+/// A canonical trace and its reversal, made canonical.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ReversalClass {
+    /// The first trace of the class in enumeration order.
+    pub representative: Trace,
+    /// The number of distinct canonical traces in the class, one for palindromes up to renaming
+    /// and otherwise two.
+    pub size: usize,
+}
+
+/// Enumerate the canonical traces of length `len` up to reversal, yielding each class once.
+///
+/// This still visits every canonical trace, and reverses each, so it's slower than
+/// [`TraceIter`] itself. What it halves is the work after it: anything invariant under reversal,
+/// like the footprint, only needs checking on the representatives, which are about half the
+/// traces.
+pub fn reversal_classes(len: usize) -> impl Iterator<Item = ReversalClass> {
+    TraceIter::new(len).filter_map(|trace| {
+        let reversed = trace.reversed().canonical();
+        // the smaller trace is enumerated first
+        (trace.as_slice() <= reversed.as_slice()).then(|| ReversalClass {
+            size: if trace == reversed { 1 } else { 2 },
+            representative: trace,
+        })
+    })
+}
+
+/// Count the canonical traces of length `len` with each stack distance histogram, by analyzing
+/// every one of them on `threads` threads.
+///
+/// [`HistogramClasses`] gives the same counts without enumerating traces, so this is mostly useful
+/// as a template for aggregating other properties over the enumeration.
+pub fn histogram_distribution(len: usize, threads: usize) -> HashMap<Histogram, usize> {
+    let threads = threads.max(1);
+    // a few parts per thread evens out their sizes
+    let prefix_len = (0..len)
+        .find(|&n| counting::bell(n) >= BigUint::from(threads * 4))
+        .unwrap_or(len);
+    let parts = TraceIter::partition(len, u32::MAX, prefix_len);
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|t| {
+                let parts: Vec<_> = parts.iter().skip(t).step_by(threads).cloned().collect();
+                scope.spawn(move || {
+                    let mut counts = HashMap::new();
+                    for trace in parts.into_iter().flatten() {
+                        *counts.entry(trace.stack_distance_histogram()).or_insert(0) += 1;
+                    }
+                    counts
+                })
+            })
+            .collect();
+
+        let mut counts = HashMap::new();
+        for handle in handles {
+            for (histogram, n) in handle.join().expect("the thread doesn't panic") {
+                *counts.entry(histogram).or_insert(0) += n;
+            }
+        }
+        counts
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_brute_force() {
+        for len in 0..=8 {
+            let mut expected: HashMap<Histogram, usize> = HashMap::new();
+            for trace in TraceIter::new(len) {
+                *expected
+                    .entry(trace.stack_distance_histogram())
+                    .or_insert(0) += 1;
+            }
+
+            let classes: Vec<_> = HistogramClasses::new(len).collect();
+            assert_eq!(classes.len(), expected.len());
+            for class in classes {
+                assert_eq!(class.size, BigUint::from(expected[&class.histogram]));
+                assert_eq!(
+                    class.representative.stack_distance_histogram(),
+                    class.histogram
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn distribution() {
+        let expected: HashMap<_, _> = HistogramClasses::new(7)
+            .map(|class| (class.histogram, usize::try_from(class.size).unwrap()))
+            .collect();
+        assert_eq!(histogram_distribution(7, 1), expected);
+        assert_eq!(histogram_distribution(7, 3), expected);
+        assert_eq!(histogram_distribution(0, 2).len(), 1);
+    }
+
+    #[test]
+    fn witnesses() {
+        let histogram = Histogram::new(vec![2, 0, 1], 3);
+        let trace = witness(&histogram).unwrap();
+        assert_eq!(trace.stack_distance_histogram(), histogram);
+
+        assert_eq!(
+            witness(&Histogram::new(vec![], 0)),
+            Some(Trace::from(vec![]))
+        );
+        assert_eq!(witness(&Histogram::new(vec![0, 1], 1)), None);
+        assert_eq!(witness(&Histogram::new(vec![1], 0)), None);
+
+        let achievable: Vec<_> = achievable_histograms(4).collect();
+        assert_eq!(achievable.len(), 8);
+        assert!(achievable
+            .iter()
+            .all(|(histogram, trace)| trace.stack_distance_histogram() == *histogram));
+    }
+
+    #[test]
+    fn three() {
+        let classes: Vec<_> = HistogramClasses::new(3)
+            .map(|c| (c.representative, c.size))
+            .collect();
+        let one = BigUint::from(1_u32);
+        assert_eq!(
+            classes,
+            vec![
+                (Trace::from(vec![0, 0, 0]), one.clone()),
+                (Trace::from(vec![0, 1, 1]), BigUint::from(2_u32)),
+                (Trace::from(vec![0, 1, 0]), one.clone()),
+                (Trace::from(vec![0, 1, 2]), one),
+            ]
+        );
+    }
+
+    #[test]
+    fn reversal() {
+        let classes: Vec<_> = reversal_classes(3).collect();
+        assert_eq!(classes.len(), 4);
+        assert_eq!(classes[1].representative, Trace::from(vec![0, 0, 1]));
+        assert_eq!(classes[1].size, 2);
+        assert_eq!(classes[2].size, 1);
+
+        for len in 0..=7 {
+            let total: usize = reversal_classes(len).map(|class| class.size).sum();
+            assert_eq!(BigUint::from(total), counting::bell(len));
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! Contains the classification of cache misses as compulsory, capacity, or conflict misses.
+//!
+//! This is Hill's three Cs model: a miss is compulsory if the symbol was never seen before,
+//! capacity if a fully-associative LRU cache of the same size would also miss (i.e. the stack
+//! distance is at least the size), and conflict otherwise.
+
+use std::fmt::Display;
+
+use crate::simulate::{CacheConfig, Policy, SetAssociative};
+use crate::trace::Trace;
+
+/// The outcome of a single access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Outcome {
+    Hit,
+    Compulsory,
+    Capacity,
+    Conflict,
+}
+
+/// Classify every access of a trace to a set-associative LRU cache.
+pub fn classify(trace: &Trace, config: CacheConfig) -> Vec<Outcome> {
+    let mut cache = SetAssociative::new(Policy::Lru, config);
+
+    trace
+        .as_slice()
+        .iter()
+        .zip(trace.stack_distance())
+        .map(|(&curr, distance)| {
+            if cache.access(curr) {
+                Outcome::Hit
+            } else {
+                match distance {
+                    None => Outcome::Compulsory,
+                    Some(d) if d >= config.size() => Outcome::Capacity,
+                    Some(_) => Outcome::Conflict,
+                }
+            }
+        })
+        .collect()
+}
+
+/// The number of accesses with each outcome.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MissBreakdown {
+    pub hits: usize,
+    pub compulsory: usize,
+    pub capacity: usize,
+    pub conflict: usize,
+}
+
+impl MissBreakdown {
+    /// Classify the accesses of a trace and count each outcome.
+    pub fn new(trace: &Trace, config: CacheConfig) -> Self {
+        classify(trace, config).into_iter().collect()
+    }
+
+    /// The total number of misses.
+    pub const fn misses(&self) -> usize {
+        self.compulsory + self.capacity + self.conflict
+    }
+}
+
+impl FromIterator<Outcome> for MissBreakdown {
+    fn from_iter<T: IntoIterator<Item = Outcome>>(iter: T) -> Self {
+        let mut breakdown = Self::default();
+        for outcome in iter {
+            match outcome {
+                Outcome::Hit => breakdown.hits += 1,
+                Outcome::Compulsory => breakdown.compulsory += 1,
+                Outcome::Capacity => breakdown.capacity += 1,
+                Outcome::Conflict => breakdown.conflict += 1,
+            }
+        }
+        breakdown
+    }
+}
+
+impl Display for MissBreakdown {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let accesses = self.hits + self.misses();
+        let rows = [
+            ("hits", self.hits),
+            ("compulsory", self.compulsory),
+            ("capacity", self.capacity),
+            ("conflict", self.conflict),
+        ];
+        for (name, count) in rows {
+            #[allow(clippy::cast_precision_loss)]
+            let percent = if accesses == 0 {
+                0.0
+            } else {
+                100.0 * count as f64 / accesses as f64
+            };
+            writeln!(f, "{:<12}{:>10}{:>8.2}%", name, count, percent)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn three_cs() {
+        // two sets of one way: A and C share set 0, while B and D share set 1
+        let config = CacheConfig::new(2, 1).unwrap();
+        let trace = Trace::from(vec![0, 1, 0, 2, 0, 1, 3, 1, 2]);
+        assert_eq!(
+            classify(&trace, config),
+            vec![
+                Outcome::Compulsory,
+                Outcome::Compulsory,
+                Outcome::Hit,
+                Outcome::Compulsory,
+                Outcome::Conflict,
+                Outcome::Hit,
+                Outcome::Compulsory,
+                Outcome::Conflict,
+                Outcome::Capacity,
+            ]
+        );
+    }
+
+    #[test]
+    fn fully_associative_has_no_conflicts() {
+        let config = CacheConfig::fully_associative(2).unwrap();
+        let breakdown = MissBreakdown::new(&Trace::from(vec![0, 2, 4, 0, 2, 0, 4, 2]), config);
+        assert_eq!(breakdown.conflict, 0);
+        assert_eq!(breakdown.compulsory, 3);
+        assert_eq!(breakdown.misses() + breakdown.hits, 8);
+    }
+
+    #[test]
+    fn config() {
+        assert!(CacheConfig::new(8, 3).is_none());
+        assert!(CacheConfig::new(8, 0).is_none());
+        assert!(CacheConfig::new(0, 0).is_none());
+        assert_eq!(CacheConfig::new(32, 8).unwrap().sets(), 4);
+    }
+}
@@ -0,0 +1,158 @@
+//! Contains closed-form counts of canonical traces.
+//!
+//! A canonical trace starts with symbol 0, and each access is at most one more than the largest
+//! symbol before it, so it's the representative of its class under renaming. These are restricted
+//! growth strings: the number of length `n` with exactly `k` symbols is the Stirling number of the
+//! second kind `S(n, k)`, and the total is the Bell number `B(n)`.
+
+use num_bigint::BigUint;
+
+// The row S(len, 0..=len) of the Stirling triangle, by S(n, k) = k S(n - 1, k) + S(n - 1, k - 1).
+fn stirling_row(len: usize) -> Vec<BigUint> {
+    let mut row = vec![BigUint::from(1_u32)];
+    for n in 1..=len {
+        let mut next = vec![BigUint::ZERO; n + 1];
+        for k in 1..=n {
+            if k < n {
+                next[k] = &row[k] * k;
+            }
+            next[k] += &row[k - 1];
+        }
+        row = next;
+    }
+    row
+}
+
+/// Calculate the number of canonical traces of length `len` with exactly `symbols` distinct
+/// symbols, the Stirling number of the second kind.
+pub fn stirling(len: usize, symbols: usize) -> BigUint {
+    stirling_row(len)
+        .into_iter()
+        .nth(symbols)
+        .unwrap_or(BigUint::ZERO)
+}
+
+/// Calculate the number of canonical traces of length `len`, the Bell number.
+pub fn bell(len: usize) -> BigUint {
+    stirling_row(len).into_iter().sum()
+}
+
+// Pascal's triangle up to row n, so that binomials[a][b] is a choose b.
+pub(crate) fn binomials(n: usize) -> Vec<Vec<BigUint>> {
+    let mut rows: Vec<Vec<BigUint>> = Vec::with_capacity(n + 1);
+    for a in 0..=n {
+        let row = (0..=a)
+            .map(|b| {
+                if b == 0 || b == a {
+                    BigUint::from(1_u32)
+                } else {
+                    &rows[a - 1][b - 1] + &rows[a - 1][b]
+                }
+            })
+            .collect();
+        rows.push(row);
+    }
+    rows
+}
+
+/// The number of ways to finish a canonical trace of a given length, with a bounded alphabet.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct Completions {
+    // table[r][m] counts the ways to fill the last r accesses when m symbols are already in use
+    table: Vec<Vec<BigUint>>,
+}
+
+impl Completions {
+    /// Tabulate completions of traces of length `len` with at most `max_symbols` symbols.
+    pub(crate) fn new(len: usize, max_symbols: u32) -> Self {
+        Self::with_bounds(len, max_symbols, &vec![0; len + 1])
+    }
+
+    /// Tabulate completions of traces of length `len` with at most `max_symbols` symbols, where
+    /// the first `i` accesses must use at least `min_used[i]` symbols.
+    pub(crate) fn with_bounds(len: usize, max_symbols: u32, min_used: &[usize]) -> Self {
+        let max_symbols = usize::try_from(max_symbols).map_or(len, |max| max.min(len));
+        let mut table = vec![(0..=max_symbols)
+            .map(|m| BigUint::from(u32::from(m >= min_used[len])))
+            .collect::<Vec<_>>()];
+        for r in 1..=len {
+            let previous = &table[r - 1];
+            let row = (0..=max_symbols)
+                .map(|m| {
+                    if m < min_used[len - r] {
+                        return BigUint::ZERO;
+                    }
+                    // reuse one of the m symbols, or introduce the next one if there's room
+                    let mut count = &previous[m] * m;
+                    if m < max_symbols {
+                        count += &previous[m + 1];
+                    }
+                    count
+                })
+                .collect();
+            table.push(row);
+        }
+        Self { table }
+    }
+
+    /// Whether there's any way to fill the last `remaining` accesses with `used` symbols in use.
+    pub(crate) fn is_possible(&self, remaining: usize, used: usize) -> bool {
+        self.table[remaining]
+            .get(used)
+            .is_some_and(|count| count > &BigUint::ZERO)
+    }
+
+    /// The number of ways to fill the last `remaining` accesses with `used` symbols in use.
+    pub(crate) fn get(&self, remaining: usize, used: usize) -> BigUint {
+        self.table[remaining]
+            .get(used)
+            .cloned()
+            .unwrap_or(BigUint::ZERO)
+    }
+
+    /// The number of traces in total.
+    pub(crate) fn total(&self) -> BigUint {
+        self.get(self.table.len() - 1, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stirling_works() {
+        let row: Vec<_> = (0..=5).map(|k| stirling(5, k)).collect();
+        let expected: Vec<BigUint> = [0_u32, 1, 15, 25, 10, 1].map(BigUint::from).into();
+        assert_eq!(row, expected);
+        assert_eq!(stirling(0, 0), BigUint::from(1_u32));
+        assert_eq!(stirling(3, 4), BigUint::ZERO);
+    }
+
+    #[test]
+    fn bell_works() {
+        let bells: Vec<_> = (0..=8).map(bell).collect();
+        let expected: Vec<BigUint> = [1_u32, 1, 2, 5, 15, 52, 203, 877, 4140]
+            .map(BigUint::from)
+            .into();
+        assert_eq!(bells, expected);
+        assert_eq!(bell(30).to_string(), "846749014511809332450147");
+    }
+
+    #[test]
+    fn completions() {
+        for len in 0..=8 {
+            assert_eq!(Completions::new(len, u32::MAX).total(), bell(len));
+            let bounded: BigUint = (0..=3).map(|k| stirling(len, k)).sum();
+            assert_eq!(Completions::new(len, 3).total(), bounded);
+        }
+        // with one symbol in use and room for one more, each of two accesses has two choices
+        assert_eq!(Completions::new(4, 2).get(2, 1), BigUint::from(4_u32));
+
+        // at least three symbols within the first four accesses
+        let bounded = Completions::with_bounds(5, u32::MAX, &[0, 0, 0, 0, 3, 3]);
+        assert_eq!(bounded.total(), BigUint::from(6_u32 * 4 + 5));
+        assert!(bounded.is_possible(2, 3));
+        assert!(!bounded.is_possible(1, 2));
+    }
+}
@@ -0,0 +1,84 @@
+//! Contains conversions of results into Polars data frames, for analytics in Rust without
+//! writing files in between.
+//!
+//! Infinite distances are nulls, so the distance columns stay numeric.
+
+use polars::prelude::*;
+
+use crate::histogram::Histogram;
+use crate::mrc::MissRatioCurve;
+use crate::trace::Trace;
+
+/// The position, symbol, and stack distance of each access.
+pub fn accesses_frame(trace: &Trace) -> PolarsResult<DataFrame> {
+    let distances: Vec<Option<u64>> = trace
+        .stack_distance()
+        .into_iter()
+        .map(|d| d.map(|d| d as u64))
+        .collect();
+    df!(
+        "position" => (0..trace.len() as u64).collect::<Vec<_>>(),
+        "symbol" => trace.as_slice(),
+        "distance" => distances,
+    )
+}
+
+impl TryFrom<&Histogram> for DataFrame {
+    type Error = PolarsError;
+
+    /// Tabulate the count at each distance, with the infinities last.
+    fn try_from(histogram: &Histogram) -> PolarsResult<Self> {
+        let distances: Vec<Option<u64>> = (0..histogram.frequencies().len() as u64)
+            .map(Some)
+            .chain([None])
+            .collect();
+        let counts: Vec<u64> = histogram
+            .frequencies()
+            .iter()
+            .map(|&count| count as u64)
+            .chain([histogram.infinities() as u64])
+            .collect();
+        df!("distance" => distances, "count" => counts)
+    }
+}
+
+impl TryFrom<&MissRatioCurve> for DataFrame {
+    type Error = PolarsError;
+
+    /// Tabulate the miss ratio at each size up to where the curve is flat.
+    fn try_from(mrc: &MissRatioCurve) -> PolarsResult<Self> {
+        df!(
+            "size" => (0..mrc.ratios().len() as u64).collect::<Vec<_>>(),
+            "miss_ratio" => mrc.ratios(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames() {
+        let trace: Trace = "1 2 1 3 2 1".parse().unwrap();
+        let accesses = accesses_frame(&trace).unwrap();
+        assert_eq!(accesses.shape(), (6, 3));
+        assert_eq!(accesses.column("distance").unwrap().null_count(), 3);
+
+        let histogram = DataFrame::try_from(&trace.stack_distance_histogram()).unwrap();
+        let counts: Vec<_> = histogram
+            .column("count")
+            .unwrap()
+            .u64()
+            .unwrap()
+            .into_no_null_iter()
+            .collect();
+        assert_eq!(counts, [0, 1, 2, 3]);
+
+        let mrc = DataFrame::try_from(&MissRatioCurve::from(&trace)).unwrap();
+        assert_eq!(
+            mrc.column("miss_ratio").unwrap().f64().unwrap().get(0),
+            Some(1.0)
+        );
+    }
+}
@@ -0,0 +1,229 @@
+//! Contains a C ABI over the stack distance analyses, for callers in C and C++.
+//!
+//! The header is `include/stack_distance.h`, which cbindgen regenerates from this module with
+//! `cbindgen --config cbindgen.toml --output include/stack_distance.h`.
+//!
+//! Every function returning `int` returns zero on success and a negative value if a pointer it
+//! needs is null.
+
+use std::os::raw::c_int;
+use std::ptr;
+
+use crate::histogram::Histogram;
+use crate::online::OnlineAnalyzer;
+
+/// A stack distance histogram, owned by this library until it's passed to
+/// `stack_distance_histogram_free`.
+#[repr(C)]
+pub struct StackDistanceHistogram {
+    /// The ith entry is the number of accesses with distance i.
+    pub counts: *mut u64,
+    /// The number of entries of `counts`.
+    pub len: usize,
+    /// The number of first accesses, whose distance is infinite.
+    pub infinities: u64,
+}
+
+/// Computes stack distances one access at a time, for traces too long to hold in memory.
+pub struct StackDistanceProcessor(OnlineAnalyzer);
+
+// Hand a histogram over to the caller.
+fn export(histogram: &Histogram, out: &mut StackDistanceHistogram) {
+    let counts: Box<[u64]> = histogram
+        .frequencies()
+        .iter()
+        .map(|&count| count as u64)
+        .collect();
+    out.len = counts.len();
+    out.counts = Box::into_raw(counts).cast();
+    out.infinities = histogram.infinities() as u64;
+}
+
+/// Compute the stack distance histogram of `len` addresses into `out`.
+///
+/// # Safety
+///
+/// `trace` must point to `len` addresses, or may be null if `len` is zero, and `out` must be
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn stack_distance_compute(
+    trace: *const u64,
+    len: usize,
+    out: *mut StackDistanceHistogram,
+) -> c_int {
+    let Some(out) = out.as_mut() else {
+        return -1;
+    };
+    let trace = match (trace.is_null(), len) {
+        (_, 0) => &[],
+        (true, _) => return -1,
+        (false, _) => std::slice::from_raw_parts(trace, len),
+    };
+
+    let mut analyzer = OnlineAnalyzer::new();
+    for &address in trace {
+        analyzer.access(address);
+    }
+    export(analyzer.histogram(), out);
+    0
+}
+
+/// Free the counts of a histogram this library filled in, leaving it empty.
+///
+/// # Safety
+///
+/// `histogram` must be null, or have been filled in by this library and not freed since.
+#[no_mangle]
+pub unsafe extern "C" fn stack_distance_histogram_free(histogram: *mut StackDistanceHistogram) {
+    let Some(histogram) = histogram.as_mut() else {
+        return;
+    };
+    if !histogram.counts.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            histogram.counts,
+            histogram.len,
+        )));
+    }
+    histogram.counts = ptr::null_mut();
+    histogram.len = 0;
+    histogram.infinities = 0;
+}
+
+/// Create a processor, to be freed with `stack_distance_processor_free`.
+#[no_mangle]
+pub extern "C" fn stack_distance_processor_new() -> *mut StackDistanceProcessor {
+    Box::into_raw(Box::new(StackDistanceProcessor(OnlineAnalyzer::new())))
+}
+
+/// Process the next `len` addresses of the trace.
+///
+/// # Safety
+///
+/// `processor` must come from `stack_distance_processor_new`, and `addresses` must point to `len`
+/// addresses, or may be null if `len` is zero.
+#[no_mangle]
+pub unsafe extern "C" fn stack_distance_processor_access(
+    processor: *mut StackDistanceProcessor,
+    addresses: *const u64,
+    len: usize,
+) -> c_int {
+    let Some(processor) = processor.as_mut() else {
+        return -1;
+    };
+    if len == 0 {
+        return 0;
+    }
+    if addresses.is_null() {
+        return -1;
+    }
+    for &address in std::slice::from_raw_parts(addresses, len) {
+        processor.0.access(address);
+    }
+    0
+}
+
+/// Write the histogram of every address processed since the processor was created or reset.
+///
+/// # Safety
+///
+/// `processor` must come from `stack_distance_processor_new`, and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn stack_distance_processor_histogram(
+    processor: *const StackDistanceProcessor,
+    out: *mut StackDistanceHistogram,
+) -> c_int {
+    match (processor.as_ref(), out.as_mut()) {
+        (Some(processor), Some(out)) => {
+            export(processor.0.histogram(), out);
+            0
+        }
+        _ => -1,
+    }
+}
+
+/// Forget every address processed.
+///
+/// # Safety
+///
+/// `processor` must come from `stack_distance_processor_new`.
+#[no_mangle]
+pub unsafe extern "C" fn stack_distance_processor_reset(
+    processor: *mut StackDistanceProcessor,
+) -> c_int {
+    match processor.as_mut() {
+        Some(processor) => {
+            processor.0.reset();
+            0
+        }
+        None => -1,
+    }
+}
+
+/// Free a processor.
+///
+/// # Safety
+///
+/// `processor` must be null, or come from `stack_distance_processor_new` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn stack_distance_processor_free(processor: *mut StackDistanceProcessor) {
+    if !processor.is_null() {
+        drop(Box::from_raw(processor));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty() -> StackDistanceHistogram {
+        StackDistanceHistogram {
+            counts: ptr::null_mut(),
+            len: 0,
+            infinities: 0,
+        }
+    }
+
+    #[test]
+    fn compute() {
+        let trace = [0x1000_u64, 0x2000, 0x1000, 0x3000, 0x2000, 0x1000];
+        let mut out = empty();
+        unsafe {
+            assert_eq!(
+                stack_distance_compute(trace.as_ptr(), trace.len(), &mut out),
+                0
+            );
+            assert_eq!(std::slice::from_raw_parts(out.counts, out.len), [0, 1, 2]);
+            assert_eq!(out.infinities, 3);
+            stack_distance_histogram_free(&mut out);
+            assert!(out.counts.is_null());
+
+            assert_eq!(stack_distance_compute(ptr::null(), 1, &mut out), -1);
+        }
+    }
+
+    #[test]
+    fn processor() {
+        let mut out = empty();
+        unsafe {
+            let processor = stack_distance_processor_new();
+            assert_eq!(
+                stack_distance_processor_access(processor, [7, 9].as_ptr(), 2),
+                0
+            );
+            assert_eq!(
+                stack_distance_processor_access(processor, [7].as_ptr(), 1),
+                0
+            );
+            assert_eq!(stack_distance_processor_histogram(processor, &mut out), 0);
+            assert_eq!(std::slice::from_raw_parts(out.counts, out.len), [0, 1]);
+            assert_eq!(out.infinities, 2);
+            stack_distance_histogram_free(&mut out);
+
+            assert_eq!(stack_distance_processor_reset(processor), 0);
+            assert_eq!(stack_distance_processor_histogram(processor, &mut out), 0);
+            assert_eq!((out.len, out.infinities), (0, 0));
+            stack_distance_histogram_free(&mut out);
+            stack_distance_processor_free(processor);
+        }
+    }
+}
@@ -0,0 +1,265 @@
+//! Contains an Arrow Flight service, for streaming results straight into a data warehouse.
+//!
+//! Each result is a flight whose ticket is its name:
+//!
+//! - `accesses` has the position, symbol, and stack distance of each access, with a null
+//!   distance for first accesses.
+//! - `histogram` has the count at each distance, with a null distance for the infinities.
+
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, UInt32Array, UInt64Array};
+use arrow::error::ArrowError;
+use arrow::record_batch::RecordBatch;
+use arrow_flight::encode::FlightDataEncoderBuilder;
+use arrow_flight::error::FlightError;
+use arrow_flight::flight_service_server::{FlightService, FlightServiceServer};
+use arrow_flight::{
+    Action, ActionType, Criteria, Empty, FlightData, FlightDescriptor, FlightEndpoint, FlightInfo,
+    HandshakeRequest, HandshakeResponse, PollInfo, PutResult, SchemaResult, Ticket,
+};
+use futures::stream::{self, BoxStream};
+use futures::{StreamExt, TryStreamExt};
+use tonic::transport::Server;
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::histogram::Histogram;
+use crate::trace::Trace;
+
+/// The names of the results, which are also their tickets.
+pub const RESULTS: [&str; 2] = ["accesses", "histogram"];
+
+/// The stack distance of each access, with null for first accesses.
+pub fn accesses_batch(trace: &Trace) -> Result<RecordBatch, ArrowError> {
+    let distances = trace.stack_distance();
+    let columns: [(&str, ArrayRef); 3] = [
+        (
+            "position",
+            Arc::new(UInt64Array::from_iter_values(0..trace.len() as u64)),
+        ),
+        (
+            "symbol",
+            Arc::new(UInt32Array::from_iter_values(
+                trace.as_slice().iter().copied(),
+            )),
+        ),
+        (
+            "distance",
+            Arc::new(UInt64Array::from_iter(
+                distances.iter().map(|d| d.map(|d| d as u64)),
+            )),
+        ),
+    ];
+    RecordBatch::try_from_iter(columns)
+}
+
+/// The count at each distance, with a null distance for the infinities.
+pub fn histogram_batch(histogram: &Histogram) -> Result<RecordBatch, ArrowError> {
+    let distances = (0..histogram.frequencies().len() as u64)
+        .map(Some)
+        .chain([None]);
+    let counts = histogram
+        .frequencies()
+        .iter()
+        .map(|&count| count as u64)
+        .chain([histogram.infinities() as u64]);
+    let columns: [(&str, ArrayRef); 2] = [
+        ("distance", Arc::new(UInt64Array::from_iter(distances))),
+        ("count", Arc::new(UInt64Array::from_iter_values(counts))),
+    ];
+    RecordBatch::try_from_iter(columns)
+}
+
+/// The service, with the results of one trace.
+#[derive(Debug, Clone)]
+pub struct Service {
+    accesses: RecordBatch,
+    histogram: RecordBatch,
+}
+
+impl Service {
+    /// Analyze a trace to serve its results.
+    pub fn new(trace: &Trace) -> Result<Self, ArrowError> {
+        Ok(Self {
+            accesses: accesses_batch(trace)?,
+            histogram: histogram_batch(&trace.stack_distance_histogram())?,
+        })
+    }
+
+    // The result a ticket or path names.
+    fn result(&self, name: &[u8]) -> Result<(&'static str, &RecordBatch), Status> {
+        match name {
+            b"accesses" => Ok((RESULTS[0], &self.accesses)),
+            b"histogram" => Ok((RESULTS[1], &self.histogram)),
+            _ => Err(Status::not_found(format!(
+                "no result `{}`, only {}",
+                String::from_utf8_lossy(name),
+                RESULTS.join(" and ")
+            ))),
+        }
+    }
+
+    // Describe a result, with the ticket to fetch it.
+    fn info(name: &'static str, batch: &RecordBatch) -> Result<FlightInfo, Status> {
+        Ok(FlightInfo::new()
+            .try_with_schema(&batch.schema())
+            .map_err(|e| Status::internal(e.to_string()))?
+            .with_descriptor(FlightDescriptor::new_path(vec![name.to_string()]))
+            .with_endpoint(FlightEndpoint::new().with_ticket(Ticket::new(name)))
+            .with_total_records(batch.num_rows() as i64))
+    }
+
+    // The result a descriptor names by its path.
+    fn described(
+        &self,
+        descriptor: &FlightDescriptor,
+    ) -> Result<(&'static str, &RecordBatch), Status> {
+        match descriptor.path.as_slice() {
+            [name] => self.result(name.as_bytes()),
+            _ => Err(Status::invalid_argument("name one result in the path")),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl FlightService for Service {
+    type HandshakeStream = BoxStream<'static, Result<HandshakeResponse, Status>>;
+    type ListFlightsStream = BoxStream<'static, Result<FlightInfo, Status>>;
+    type DoGetStream = BoxStream<'static, Result<FlightData, Status>>;
+    type DoPutStream = BoxStream<'static, Result<PutResult, Status>>;
+    type DoActionStream = BoxStream<'static, Result<arrow_flight::Result, Status>>;
+    type ListActionsStream = BoxStream<'static, Result<ActionType, Status>>;
+    type DoExchangeStream = BoxStream<'static, Result<FlightData, Status>>;
+
+    async fn handshake(
+        &self,
+        _request: Request<Streaming<HandshakeRequest>>,
+    ) -> Result<Response<Self::HandshakeStream>, Status> {
+        Err(Status::unimplemented("the service needs no handshake"))
+    }
+
+    async fn list_flights(
+        &self,
+        _request: Request<Criteria>,
+    ) -> Result<Response<Self::ListFlightsStream>, Status> {
+        let infos = [
+            Self::info(RESULTS[0], &self.accesses),
+            Self::info(RESULTS[1], &self.histogram),
+        ];
+        Ok(Response::new(stream::iter(infos).boxed()))
+    }
+
+    async fn get_flight_info(
+        &self,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        let (name, batch) = self.described(request.get_ref())?;
+        Ok(Response::new(Self::info(name, batch)?))
+    }
+
+    async fn poll_flight_info(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> Result<Response<PollInfo>, Status> {
+        Err(Status::unimplemented(
+            "the results are ready, so use GetFlightInfo",
+        ))
+    }
+
+    async fn get_schema(
+        &self,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<SchemaResult>, Status> {
+        let (name, batch) = self.described(request.get_ref())?;
+        // the info has the schema encoded the same way
+        Ok(Response::new(SchemaResult {
+            schema: Self::info(name, batch)?.schema,
+        }))
+    }
+
+    async fn do_get(
+        &self,
+        request: Request<Ticket>,
+    ) -> Result<Response<Self::DoGetStream>, Status> {
+        let (_, batch) = self.result(&request.get_ref().ticket)?;
+        let data = FlightDataEncoderBuilder::new()
+            .build(stream::iter([Ok::<_, FlightError>(batch.clone())]))
+            .map_err(Status::from);
+        Ok(Response::new(data.boxed()))
+    }
+
+    async fn do_put(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoPutStream>, Status> {
+        Err(Status::unimplemented("the results are read-only"))
+    }
+
+    async fn do_action(
+        &self,
+        _request: Request<Action>,
+    ) -> Result<Response<Self::DoActionStream>, Status> {
+        Err(Status::unimplemented("the service has no actions"))
+    }
+
+    async fn list_actions(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<Self::ListActionsStream>, Status> {
+        Ok(Response::new(stream::empty().boxed()))
+    }
+
+    async fn do_exchange(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoExchangeStream>, Status> {
+        Err(Status::unimplemented("the results are read-only"))
+    }
+}
+
+/// Serve the results of a trace on an address until the process ends.
+pub fn serve(addr: SocketAddr, trace: &Trace) -> Result<(), Box<dyn Error>> {
+    let service = Service::new(trace)?;
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(
+        Server::builder()
+            .add_service(FlightServiceServer::new(service))
+            .serve(addr),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::Array;
+
+    use super::*;
+
+    #[test]
+    fn batches() {
+        let trace: Trace = "1 2 1 3 2 1".parse().unwrap();
+
+        let accesses = accesses_batch(&trace).unwrap();
+        assert_eq!(accesses.num_rows(), 6);
+        let distances = accesses
+            .column(2)
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        assert_eq!(
+            distances.iter().collect::<Vec<_>>(),
+            [None, None, Some(1), None, Some(2), Some(2)]
+        );
+
+        let histogram = histogram_batch(&trace.stack_distance_histogram()).unwrap();
+        let counts = histogram
+            .column(1)
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        assert_eq!(counts.values().to_vec(), [0, 1, 2, 3]);
+        assert!(histogram.column(0).is_null(3));
+    }
+}
@@ -0,0 +1,145 @@
+//! Contains Xiang et al.'s footprint analysis.
+//!
+//! The footprint `fp(w)` is the average number of distinct symbols in a window of `w` accesses.
+//! By the higher-order theory of locality (HOTL), a cache of size `fp(w)` misses at rate
+//! `fp(w + 1) - fp(w)`, so the footprint also gives a miss-ratio curve.
+
+use std::collections::HashMap;
+
+use crate::mrc::MissRatioCurve;
+use crate::trace::Trace;
+
+/// The average footprint of a trace at every window length.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Footprint {
+    // the wth entry is the average footprint of windows of length w, from 0 to the trace length
+    fp: Vec<f64>,
+}
+
+impl Footprint {
+    /// Calculate the footprint of every window length, in linear time.
+    pub fn new(trace: &Trace) -> Self {
+        let n = trace.as_slice().len();
+
+        // a symbol is missing from exactly the windows which fit in the gaps around its accesses,
+        // so we tally gap lengths: a gap of g accesses misses max(0, g - w + 1) windows of length
+        // w. Storing g + 1 makes both ends of the trace look like reuses.
+        let mut gaps = vec![0_usize; n + 2];
+        let mut last = HashMap::new();
+        for (i, &curr) in trace.as_slice().iter().enumerate() {
+            let previous = last.insert(curr, i + 1).unwrap_or(0);
+            gaps[i + 1 - previous] += 1;
+        }
+        for &l in last.values() {
+            gaps[n + 1 - l] += 1;
+        }
+        let m = last.len();
+
+        // with suffix sums of counts and lengths, the number of (gap, window) pairs for each w is
+        // sum over g > w of (g - w) = lengths - w * counts
+        let mut fp = vec![0.0; n + 1];
+        let (mut counts, mut lengths) = (0, 0);
+        for w in (1..=n).rev() {
+            counts += gaps[w + 1];
+            lengths += (w + 1) * gaps[w + 1];
+
+            #[allow(clippy::cast_precision_loss)]
+            let missing = (lengths - w * counts) as f64 / (n - w + 1) as f64;
+            #[allow(clippy::cast_precision_loss)]
+            let average = m as f64 - missing;
+            fp[w] = average;
+        }
+
+        Self { fp }
+    }
+
+    /// The average footprint of windows of the given length, which must be at most the trace
+    /// length.
+    pub fn get(&self, window: usize) -> f64 {
+        self.fp[window]
+    }
+
+    /// The average footprints from window length zero up to the trace length.
+    pub fn footprints(&self) -> &[f64] {
+        &self.fp
+    }
+
+    /// Convert the footprint into a miss-ratio curve with the HOTL conversion.
+    ///
+    /// The miss ratio of a cache of size `c` is the slope of the footprint at the shortest window
+    /// with footprint at least `c`.
+    pub fn miss_ratio_curve(&self) -> MissRatioCurve {
+        let mut ratios = Vec::new();
+        let mut w = 0;
+
+        while w + 1 < self.fp.len() {
+            #[allow(clippy::cast_precision_loss)]
+            while w + 1 < self.fp.len() && self.fp[w] < ratios.len() as f64 {
+                w += 1;
+            }
+            ratios.push(self.fp.get(w + 1).map_or(0.0, |next| next - self.fp[w]));
+        }
+
+        if ratios.is_empty() {
+            ratios.push(0.0);
+        }
+        MissRatioCurve::from_ratios(ratios)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::trace::TraceIter;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    fn brute_force(trace: &[u32], w: usize) -> f64 {
+        let windows = trace.windows(w);
+        let count = windows.len();
+        #[allow(clippy::cast_precision_loss)]
+        let average = windows
+            .map(|w| w.iter().collect::<HashSet<_>>().len())
+            .sum::<usize>() as f64
+            / count as f64;
+        average
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        for trace in TraceIter::new(7) {
+            let fp = Footprint::new(&trace);
+            assert_eq!(fp.get(0), 0.0);
+            for w in 1..=7 {
+                assert!(
+                    close(fp.get(w), brute_force(trace.as_slice(), w)),
+                    "{} {}",
+                    trace,
+                    w
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn cyclic_hotl() {
+        // every window of a cyclic trace has min(w, 4) symbols, so the HOTL curve is a cliff at 4
+        let trace = Trace::from((0..40).map(|i| i % 4).collect::<Vec<_>>());
+        let mrc = Footprint::new(&trace).miss_ratio_curve();
+        for size in 0..4 {
+            assert!(close(mrc.miss_ratio(size), 1.0));
+        }
+        assert!(close(mrc.miss_ratio(4), 0.0));
+    }
+
+    #[test]
+    fn empty() {
+        let fp = Footprint::new(&Trace::from(vec![]));
+        assert_eq!(fp.footprints(), &[0.0]);
+        assert_eq!(fp.miss_ratio_curve().miss_ratio(1), 0.0);
+    }
+}
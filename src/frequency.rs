@@ -0,0 +1,175 @@
+//! Contains summaries of the skew of a frequency histogram.
+
+/// Calculate the Shannon entropy of the access distribution, in bits.
+///
+/// This is zero when every access is to one symbol, and `log2(n)` when `n` symbols are accessed
+/// equally often.
+pub fn entropy(frequencies: &[usize]) -> f64 {
+    let total: usize = frequencies.iter().sum();
+
+    #[allow(clippy::cast_precision_loss)]
+    frequencies
+        .iter()
+        .filter(|&&n| n != 0)
+        .map(|&n| {
+            let p = n as f64 / total as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// A Zipf law fitted to a frequency histogram.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ZipfFit {
+    /// The exponent `s`, where the kth most popular symbol is accessed in proportion to `k^-s`.
+    pub exponent: f64,
+    /// The coefficient of determination of the fit in log-log space, between zero and one.
+    pub r_squared: f64,
+}
+
+/// Fit a Zipf law to a frequency histogram by least squares on the log-log rank-frequency plot.
+///
+/// Returns `None` if fewer than two symbols were accessed.
+pub fn fit_zipf(frequencies: &[usize]) -> Option<ZipfFit> {
+    let mut counts: Vec<_> = frequencies.iter().filter(|&&n| n != 0).collect();
+    if counts.len() < 2 {
+        return None;
+    }
+    counts.sort_unstable_by(|a, b| b.cmp(a));
+
+    #[allow(clippy::cast_precision_loss)]
+    let points: Vec<(f64, f64)> = counts
+        .iter()
+        .enumerate()
+        .map(|(rank, &&n)| (((rank + 1) as f64).ln(), (n as f64).ln()))
+        .collect();
+
+    #[allow(clippy::cast_precision_loss)]
+    let len = points.len() as f64;
+    let mean_x = points.iter().map(|p| p.0).sum::<f64>() / len;
+    let mean_y = points.iter().map(|p| p.1).sum::<f64>() / len;
+
+    let sxx: f64 = points.iter().map(|p| (p.0 - mean_x).powi(2)).sum();
+    let syy: f64 = points.iter().map(|p| (p.1 - mean_y).powi(2)).sum();
+    let sxy: f64 = points.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum();
+
+    // with every count equal the fit is perfectly flat, rather than undefined
+    let r_squared = if syy == 0.0 {
+        1.0
+    } else {
+        sxy * sxy / (sxx * syy)
+    };
+
+    Some(ZipfFit {
+        exponent: -sxy / sxx,
+        r_squared,
+    })
+}
+
+/// How concentrated accesses are on the most popular symbols.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Concentration {
+    // cumulative counts of the most popular symbols, most popular first
+    cumulative: Vec<usize>,
+}
+
+impl Concentration {
+    /// Rank the symbols of a frequency histogram by popularity.
+    pub fn new(frequencies: &[usize]) -> Self {
+        let mut counts: Vec<_> = frequencies.iter().copied().filter(|&n| n != 0).collect();
+        counts.sort_unstable_by(|a, b| b.cmp(a));
+
+        let mut total = 0;
+        let cumulative = counts
+            .into_iter()
+            .map(|n| {
+                total += n;
+                total
+            })
+            .collect();
+
+        Self { cumulative }
+    }
+
+    /// The fraction of accesses to the `k` most popular symbols.
+    pub fn coverage(&self, k: usize) -> f64 {
+        let Some(&total) = self.cumulative.last() else {
+            return 0.0;
+        };
+        let covered = k
+            .checked_sub(1)
+            .map_or(0, |i| self.cumulative.get(i).copied().unwrap_or(total));
+
+        #[allow(clippy::cast_precision_loss)]
+        let coverage = covered as f64 / total as f64;
+        coverage
+    }
+
+    /// The smallest number of most popular symbols which together account for at least the given
+    /// fraction of accesses.
+    pub fn symbols_to_cover(&self, fraction: f64) -> usize {
+        let Some(&total) = self.cumulative.last() else {
+            return 0;
+        };
+
+        #[allow(clippy::cast_precision_loss)]
+        let covered = self
+            .cumulative
+            .iter()
+            .position(|&n| n as f64 >= fraction * total as f64)
+            .map_or(self.cumulative.len(), |i| i + 1);
+        covered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn entropy_works() {
+        assert!(close(entropy(&[0, 5, 5, 5, 5]), 2.0));
+        assert!(close(entropy(&[7]), 0.0));
+        assert!(close(entropy(&[]), 0.0));
+        assert!(entropy(&[9, 1]) < 1.0);
+    }
+
+    #[test]
+    fn zipf() {
+        // counts roughly proportional to 1/k^2, in a shuffled order
+        let fit = fit_zipf(&[100, 0, 4, 25, 11, 6]).unwrap();
+        assert!((fit.exponent - 2.0).abs() < 0.05);
+        assert!(fit.r_squared > 0.99);
+    }
+
+    #[test]
+    fn uniform() {
+        let fit = fit_zipf(&[3, 3, 3]).unwrap();
+        assert!(close(fit.exponent, 0.0));
+        assert!(close(fit.r_squared, 1.0));
+    }
+
+    #[test]
+    fn concentration() {
+        let concentration = Concentration::new(&[1, 0, 6, 2, 1]);
+        assert!(close(concentration.coverage(0), 0.0));
+        assert!(close(concentration.coverage(1), 0.6));
+        assert!(close(concentration.coverage(2), 0.8));
+        assert!(close(concentration.coverage(10), 1.0));
+        assert_eq!(concentration.symbols_to_cover(0.5), 1);
+        assert_eq!(concentration.symbols_to_cover(0.9), 3);
+        assert_eq!(concentration.symbols_to_cover(0.99), 4);
+        assert_eq!(Concentration::new(&[]).symbols_to_cover(0.5), 0);
+        assert_eq!(Concentration::new(&[]).coverage(3), 0.0);
+    }
+
+    #[test]
+    fn too_few() {
+        assert_eq!(fit_zipf(&[0, 8]), None);
+        assert_eq!(fit_zipf(&[]), None);
+    }
+}
@@ -0,0 +1,1163 @@
+//! Contains seeded generators of synthetic traces.
+
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
+
+use crate::histogram::Histogram;
+use crate::markov::MarkovModel;
+use crate::rng::{self, Rng};
+use crate::trace::Trace;
+
+/// Generate a trace of `len` accesses drawn uniformly at random from `alphabet_size` symbols.
+///
+/// The same seed always produces the same trace.
+pub fn uniform(len: usize, alphabet_size: u32, seed: u64) -> Trace {
+    assert!(alphabet_size > 0, "the alphabet must be non-empty");
+
+    let mut rng = Rng::new(seed);
+    #[allow(clippy::cast_possible_truncation)]
+    let trace: Vec<_> = (0..len)
+        .map(|_| rng.below(u64::from(alphabet_size)) as u32)
+        .collect();
+    Trace::from(trace)
+}
+
+// Samples indices in proportion to their weights, by binary search on the cumulative weights.
+struct Sampler {
+    cumulative: Vec<f64>,
+}
+
+impl Sampler {
+    fn new(weights: impl IntoIterator<Item = f64>) -> Self {
+        let mut total = 0.0;
+        let cumulative: Vec<f64> = weights
+            .into_iter()
+            .map(|w| {
+                assert!(w >= 0.0, "weights must be non-negative");
+                total += w;
+                total
+            })
+            .collect();
+        assert!(total > 0.0, "some weight must be positive");
+        Self { cumulative }
+    }
+
+    fn sample(&self, rng: &mut Rng) -> usize {
+        let total = self.cumulative[self.cumulative.len() - 1];
+        let target = rng.next_f64() * total;
+        // rounding can push the target onto the total, which belongs to the last positive weight
+        let i = self.cumulative.partition_point(|&c| c <= target);
+        i.min(self.cumulative.partition_point(|&c| c < total))
+    }
+}
+
+/// Generate a trace of `len` accesses drawn from a Zipf distribution over `alphabet_size` symbols.
+///
+/// Symbol `k` is accessed in proportion to `(k + 1)^-exponent`, so symbol 0 is the most popular.
+/// An exponent of zero is uniform, and real workloads are typically between 0.6 and 1.2.
+pub fn zipf(len: usize, alphabet_size: u32, exponent: f64, seed: u64) -> Trace {
+    assert!(alphabet_size > 0, "the alphabet must be non-empty");
+    irm(len, &zipf_weights(alphabet_size, exponent), seed)
+}
+
+// The weight of each of `n` symbols under a Zipf distribution.
+fn zipf_weights(n: u32, exponent: f64) -> Vec<f64> {
+    (1..=n).map(|k| f64::from(k).powf(-exponent)).collect()
+}
+
+/// Generate a trace under the independent reference model, where each access is drawn
+/// independently with symbol `i` chosen in proportion to `weights[i]`.
+///
+/// Passing a trace's frequency histogram as the weights gives a synthetic stand-in for it with the
+/// same popularity distribution, but none of its temporal structure.
+pub fn irm(len: usize, weights: &[f64], seed: u64) -> Trace {
+    let sampler = Sampler::new(weights.iter().copied());
+    let mut rng = Rng::new(seed);
+    #[allow(clippy::cast_possible_truncation)]
+    let trace: Vec<_> = (0..len).map(|_| sampler.sample(&mut rng) as u32).collect();
+    Trace::from(trace)
+}
+
+/// Generate a trace whose stack distance histogram is the given one.
+///
+/// Accesses are drawn at random from the distances not yet used which the stack is deep enough
+/// for, in proportion to how many remain. This is exact whenever every distance is less than the
+/// number of infinities, i.e. whenever some trace has the histogram. Otherwise, distances deeper
+/// than the final stack are clamped to the bottom of it.
+pub fn from_histogram(histogram: &Histogram, seed: u64) -> Trace {
+    let mut remaining = histogram.frequencies().to_vec();
+    let mut infinities = histogram.infinities();
+    let mut rng = Rng::new(seed);
+
+    // the LRU stack, with the most recent symbol at the front
+    let mut stack = VecDeque::new();
+    let mut trace = Vec::with_capacity(histogram.total());
+
+    for _ in 0..histogram.total() {
+        let feasible = &remaining[..remaining.len().min(stack.len())];
+        let choices = feasible.iter().sum::<usize>() + infinities;
+
+        let distance = if choices == 0 {
+            // nothing fits, so use the deepest remaining distance as deep as we can, which is a
+            // first access if the stack is empty
+            let deepest = remaining
+                .iter()
+                .rposition(|&n| n != 0)
+                .expect("some access remains");
+            remaining[deepest] -= 1;
+            stack.len().checked_sub(1)
+        } else {
+            #[allow(clippy::cast_possible_truncation)]
+            let mut target = rng.below(choices as u64) as usize;
+            if target < infinities {
+                infinities -= 1;
+                None
+            } else {
+                target -= infinities;
+                let distance = feasible
+                    .iter()
+                    .position(|&n| {
+                        if target < n {
+                            true
+                        } else {
+                            target -= n;
+                            false
+                        }
+                    })
+                    .expect("the target is within the feasible distances");
+                remaining[distance] -= 1;
+                Some(distance)
+            }
+        };
+
+        let symbol = match distance {
+            #[allow(clippy::cast_possible_truncation)]
+            None => stack.len() as u32,
+            Some(distance) => stack.remove(distance).expect("the stack is deep enough"),
+        };
+        stack.push_front(symbol);
+        trace.push(symbol);
+    }
+
+    Trace::from(trace)
+}
+
+/// Generate a trace of `len` accesses by walking a Markov chain, either fitted to a trace with
+/// [`MarkovModel::fit`] or built from a transition matrix with [`MarkovModel::new`].
+pub fn markov(model: &MarkovModel, len: usize, seed: u64) -> Trace {
+    model.generate(len, &mut Rng::new(seed))
+}
+
+/// Generate a cyclic sweep over one more symbol than fits in the cache, `0, 1, ..., size, 0, 1,
+/// ...`, on which an LRU cache of size `cache_size` misses every access.
+pub fn cyclic(len: usize, cache_size: u32) -> Trace {
+    let span = cache_size as usize + 1;
+    #[allow(clippy::cast_possible_truncation)]
+    let trace: Vec<_> = (0..len).map(|i| (i % span) as u32).collect();
+    Trace::from(trace)
+}
+
+/// Generate a sawtooth scan over `span` symbols, sweeping up and then back down, `0, 1, ..., span -
+/// 1, span - 1, ..., 0, 0, 1, ...`.
+///
+/// Unlike a cyclic sweep, each turnaround reuses the most recent symbols, so an LRU cache hits in
+/// proportion to its size.
+pub fn sawtooth(len: usize, span: u32) -> Trace {
+    assert!(span > 0, "the span must be positive");
+
+    let span = span as usize;
+    #[allow(clippy::cast_possible_truncation)]
+    let trace: Vec<_> = (0..len)
+        .map(|i| {
+            let offset = i % (2 * span);
+            (if offset < span {
+                offset
+            } else {
+                2 * span - 1 - offset
+            }) as u32
+        })
+        .collect();
+    Trace::from(trace)
+}
+
+/// Generate a thrashing pattern which alternates every `period` accesses between looping over
+/// two disjoint sets of `cache_size` symbols.
+///
+/// Each set fits in the cache on its own, but every switch evicts it, so an LRU cache of size
+/// `cache_size` misses the whole first pass of every period.
+pub fn thrash(len: usize, cache_size: u32, period: usize) -> Trace {
+    assert!(cache_size > 0, "the cache must be non-empty");
+    assert!(period > 0, "the period must be positive");
+
+    let size = cache_size as usize;
+    #[allow(clippy::cast_possible_truncation)]
+    let trace: Vec<_> = (0..len)
+        .map(|i| {
+            let set = (i / period) % 2;
+            (set * size + (i % period) % size) as u32
+        })
+        .collect();
+    Trace::from(trace)
+}
+
+/// A workload which repeatedly accesses a small hot set at random, interrupted by long sequential
+/// scans over symbols which are never reused.
+///
+/// The scans flush the hot set out of an LRU cache even though they gain nothing from caching,
+/// which is exactly what scan-resistant policies avoid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HotSetScan {
+    hot_set: u32,
+    hot_accesses: usize,
+    scan_len: usize,
+}
+
+impl HotSetScan {
+    /// Create a workload over a hot set of `hot_set` symbols, where every run of `hot_accesses`
+    /// random accesses to it is followed by a scan of `scan_len` fresh symbols.
+    pub const fn new(hot_set: u32, hot_accesses: usize, scan_len: usize) -> Self {
+        assert!(hot_set > 0, "the hot set must be non-empty");
+        Self {
+            hot_set,
+            hot_accesses,
+            scan_len,
+        }
+    }
+
+    /// The fraction of accesses which belong to scans.
+    pub fn scan_fraction(&self) -> f64 {
+        #[allow(clippy::cast_precision_loss)]
+        let fraction = self.scan_len as f64 / (self.hot_accesses + self.scan_len) as f64;
+        fraction
+    }
+
+    /// Generate a trace of `len` accesses, starting with the hot set.
+    ///
+    /// The hot set is symbols `0..hot_set`, and scans continue through the symbols after it.
+    pub fn generate(&self, len: usize, seed: u64) -> Trace {
+        let period = self.hot_accesses + self.scan_len;
+        assert!(period > 0, "the workload must access something");
+
+        let mut rng = Rng::new(seed);
+        let mut next_scanned = self.hot_set;
+        #[allow(clippy::cast_possible_truncation)]
+        let trace: Vec<_> = (0..len)
+            .map(|i| {
+                if i % period < self.hot_accesses {
+                    rng.below(u64::from(self.hot_set)) as u32
+                } else {
+                    next_scanned += 1;
+                    next_scanned - 1
+                }
+            })
+            .collect();
+        Trace::from(trace)
+    }
+}
+
+/// The access pattern within a phase of a multi-phase trace.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pattern {
+    /// Uniformly random accesses.
+    Uniform,
+    /// Zipfian accesses with the given exponent.
+    Zipf(f64),
+    /// A cyclic sweep over the alphabet.
+    Cyclic,
+    /// A sawtooth scan over the alphabet.
+    Sawtooth,
+}
+
+/// One phase of a multi-phase trace.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhaseSpec {
+    /// The number of accesses in the phase.
+    pub len: usize,
+    /// The number of symbols the phase accesses.
+    pub alphabet_size: u32,
+    /// The first symbol the phase accesses, so phases can share symbols or not.
+    pub offset: u32,
+    /// How the phase accesses its symbols.
+    pub pattern: Pattern,
+}
+
+impl PhaseSpec {
+    fn generate(&self, seed: u64) -> Trace {
+        assert!(self.alphabet_size > 0, "the alphabet must be non-empty");
+        assert!(
+            self.offset.checked_add(self.alphabet_size - 1).is_some(),
+            "the symbols must fit in a u32"
+        );
+        let trace = match self.pattern {
+            Pattern::Uniform => uniform(self.len, self.alphabet_size, seed),
+            Pattern::Zipf(exponent) => zipf(self.len, self.alphabet_size, exponent, seed),
+            Pattern::Cyclic => cyclic(self.len, self.alphabet_size - 1),
+            Pattern::Sawtooth => sawtooth(self.len, self.alphabet_size),
+        };
+        let shifted: Vec<_> = trace.as_slice().iter().map(|&n| n + self.offset).collect();
+        Trace::from(shifted)
+    }
+}
+
+/// Generate a trace which runs through the given phases in the given order.
+///
+/// `order` lists indices into `phases`, which may repeat, so `&[0, 1, 0]` returns to the first
+/// phase after the second. Each occurrence of a phase draws fresh random accesses.
+///
+/// # Panics
+///
+/// Panics if a phase's alphabet is empty or runs past `u32::MAX`.
+pub fn multi_phase(phases: &[PhaseSpec], order: &[usize], seed: u64) -> Trace {
+    let mut trace = Vec::with_capacity(order.iter().map(|&i| phases[i].len).sum());
+    for (occurrence, &i) in order.iter().enumerate() {
+        let phase = phases[i].generate(rng::mix(seed ^ occurrence as u64));
+        trace.extend_from_slice(phase.as_slice());
+    }
+    Trace::from(trace)
+}
+
+/// Generate a sweep over `count` addresses spaced `stride` apart from `base`, repeated to `len`
+/// accesses.
+pub fn strided(len: usize, base: u32, stride: u32, count: u32) -> Trace {
+    assert!(count > 0, "the sweep must be non-empty");
+
+    #[allow(clippy::cast_possible_truncation)]
+    let trace: Vec<_> = (0..len)
+        .map(|i| base + (i % count as usize) as u32 * stride)
+        .collect();
+    Trace::from(trace)
+}
+
+/// The order in which a matrix is traversed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Traversal {
+    /// Along each row in turn, which is sequential in memory.
+    RowMajor,
+    /// Down each column in turn, which strides by a whole row.
+    ColumnMajor,
+}
+
+/// A perfect loop nest which accesses arrays at affine addresses of its loop indices.
+///
+/// Each iteration of the innermost loop accesses every reference in order, at its base plus the
+/// sum of each loop index times that reference's stride for the loop.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LoopNest {
+    // the trip count of each loop, outermost first
+    bounds: Vec<u32>,
+    // the base address and per-loop strides of each reference
+    references: Vec<(u32, Vec<u32>)>,
+}
+
+impl LoopNest {
+    /// Create a loop nest with the given trip counts, outermost first, and no references yet.
+    pub const fn new(bounds: Vec<u32>) -> Self {
+        Self {
+            bounds,
+            references: Vec::new(),
+        }
+    }
+
+    /// Add a reference at `base` with the given stride for each loop, outermost first.
+    ///
+    /// A stride of zero means the reference doesn't depend on that loop.
+    pub fn reference(mut self, base: u32, strides: Vec<u32>) -> Self {
+        assert_eq!(
+            strides.len(),
+            self.bounds.len(),
+            "there must be a stride for each loop"
+        );
+        self.references.push((base, strides));
+        self
+    }
+
+    /// A single traversal of a `rows` by `cols` matrix stored row-major from address zero.
+    pub fn matrix(rows: u32, cols: u32, element_size: u32, traversal: Traversal) -> Self {
+        match traversal {
+            Traversal::RowMajor => {
+                Self::new(vec![rows, cols]).reference(0, vec![cols * element_size, element_size])
+            }
+            Traversal::ColumnMajor => {
+                Self::new(vec![cols, rows]).reference(0, vec![element_size, cols * element_size])
+            }
+        }
+    }
+
+    /// Generate the trace of running the loop nest once.
+    pub fn generate(&self) -> Trace {
+        let mut trace = Vec::new();
+        if self.bounds.contains(&0) {
+            return Trace::from(trace);
+        }
+
+        let mut indices = vec![0; self.bounds.len()];
+        loop {
+            for (base, strides) in &self.references {
+                let offset: u32 = indices.iter().zip(strides).map(|(i, s)| i * s).sum();
+                trace.push(base + offset);
+            }
+
+            // advance the innermost loop, carrying into the outer ones like an odometer
+            let Some(level) = (0..indices.len())
+                .rev()
+                .find(|&level| indices[level] + 1 < self.bounds[level])
+            else {
+                break;
+            };
+            indices[level] += 1;
+            indices[level + 1..].fill(0);
+        }
+
+        Trace::from(trace)
+    }
+}
+
+/// The distribution of object sizes in a key-value workload.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SizeDistribution {
+    /// Every object has the same size.
+    Fixed(u32),
+    /// Sizes are uniform between the bounds, inclusive.
+    Uniform {
+        /// The smallest size.
+        min: u32,
+        /// The largest size.
+        max: u32,
+    },
+    /// Heavy-tailed sizes, at least `scale`, whose tail falls off with exponent `shape`.
+    Pareto {
+        /// The smallest size.
+        scale: f64,
+        /// The tail exponent; smaller is heavier.
+        shape: f64,
+    },
+}
+
+impl SizeDistribution {
+    fn sample(&self, rng: &mut Rng) -> u32 {
+        match *self {
+            Self::Fixed(size) => size,
+            #[allow(clippy::cast_possible_truncation)]
+            Self::Uniform { min, max } => min + rng.below(u64::from(max - min) + 1) as u32,
+            // inverse transform sampling, with 1 - u in (0, 1] to avoid dividing by zero
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            Self::Pareto { scale, shape } => {
+                (scale / (1.0 - rng.next_f64()).powf(shape.recip())).min(f64::from(u32::MAX)) as u32
+            }
+        }
+    }
+}
+
+/// The kind of a key-value request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    /// Read the value of a key.
+    Get,
+    /// Write a new value for a key.
+    Set,
+    /// Remove a key.
+    Delete,
+}
+
+/// A single key-value request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Request {
+    /// The key, which is the symbol for stack distance purposes.
+    pub key: u32,
+    /// The kind of request.
+    pub operation: Operation,
+    /// The size of the value: the written size for a set, and the current size otherwise.
+    pub size: u32,
+    /// The time to live of a set, in requests, if it expires.
+    pub ttl: Option<u32>,
+}
+
+/// A YCSB-like key-value workload, with Zipfian key popularity.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KvWorkload {
+    /// The number of distinct keys.
+    pub keys: u32,
+    /// The Zipf exponent of key popularity.
+    pub skew: f64,
+    /// The relative weights of gets, sets, and deletes.
+    pub mix: [f64; 3],
+    /// The distribution of value sizes.
+    pub sizes: SizeDistribution,
+    /// The range of times to live given to sets, inclusive, or `None` if values never expire.
+    pub ttl: Option<(u32, u32)>,
+}
+
+impl Default for KvWorkload {
+    fn default() -> Self {
+        Self {
+            keys: 1000,
+            skew: 0.99,
+            mix: [0.95, 0.05, 0.0],
+            sizes: SizeDistribution::Fixed(100),
+            ttl: None,
+        }
+    }
+}
+
+impl KvWorkload {
+    /// Generate `len` requests.
+    ///
+    /// Each key keeps its size between sets, so gets see the size of the last value written.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are no keys, or if the bounds of the sizes or the times to live are out
+    /// of order.
+    pub fn generate(&self, len: usize, seed: u64) -> Vec<Request> {
+        assert!(self.keys > 0, "there must be some keys");
+        if let SizeDistribution::Uniform { min, max } = self.sizes {
+            assert!(min <= max, "the smallest size must be at most the largest");
+        }
+        if let Some((min, max)) = self.ttl {
+            assert!(
+                min <= max,
+                "the shortest time to live must be at most the longest"
+            );
+        }
+
+        let keys = Sampler::new(zipf_weights(self.keys, self.skew));
+        let operations = Sampler::new(self.mix);
+        let mut sizes = HashMap::new();
+        let mut rng = Rng::new(seed);
+
+        (0..len)
+            .map(|_| {
+                #[allow(clippy::cast_possible_truncation)]
+                let key = keys.sample(&mut rng) as u32;
+                let operation = [Operation::Get, Operation::Set, Operation::Delete]
+                    [operations.sample(&mut rng)];
+
+                let (size, ttl) = if operation == Operation::Set {
+                    let size = self.sizes.sample(&mut rng);
+                    sizes.insert(key, size);
+                    #[allow(clippy::cast_possible_truncation)]
+                    let ttl = self
+                        .ttl
+                        .map(|(min, max)| min + rng.below(u64::from(max - min) + 1) as u32);
+                    (size, ttl)
+                } else {
+                    let size = *sizes
+                        .entry(key)
+                        .or_insert_with(|| self.sizes.sample(&mut rng));
+                    (size, None)
+                };
+
+                Request {
+                    key,
+                    operation,
+                    size,
+                    ttl,
+                }
+            })
+            .collect()
+    }
+}
+
+/// The trace of keys accessed by a sequence of requests.
+pub fn key_trace(requests: &[Request]) -> Trace {
+    Trace::from(requests.iter().map(|r| r.key).collect::<Vec<_>>())
+}
+
+/// One component of a Gaussian mixture over addresses.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gaussian {
+    /// The relative probability of drawing from this component.
+    pub weight: f64,
+    /// The center of the hot region.
+    pub mean: f64,
+    /// How far the hot region's soft edges spread.
+    pub std_dev: f64,
+}
+
+/// Generate addresses in `range` drawn from a mixture of Gaussians, each a hot region with soft
+/// edges.
+///
+/// Draws which fall outside the range are redrawn from the same component, so components should
+/// mostly lie inside it; one which doesn't falls back to its nearest address in range after a few
+/// tries.
+pub fn gaussian_mixture(
+    len: usize,
+    components: &[Gaussian],
+    range: Range<u32>,
+    seed: u64,
+) -> Trace {
+    const TRIES: usize = 16;
+    assert!(!range.is_empty(), "the range must be non-empty");
+
+    let sampler = Sampler::new(components.iter().map(|c| c.weight));
+    let (low, high) = (f64::from(range.start), f64::from(range.end - 1));
+    let mut rng = Rng::new(seed);
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let trace: Vec<_> = (0..len)
+        .map(|_| {
+            let component = components[sampler.sample(&mut rng)];
+            let mut draw = || (component.mean + component.std_dev * rng.next_normal()).round();
+            let address = (0..TRIES)
+                .map(|_| draw())
+                .find(|x| (low..=high).contains(x))
+                .unwrap_or_else(|| draw().clamp(low, high));
+            address as u32
+        })
+        .collect();
+    Trace::from(trace)
+}
+
+/// A single block I/O request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BlockRequest {
+    /// The byte offset of the start of the request.
+    pub offset: u64,
+    /// The number of bytes requested.
+    pub size: u64,
+    /// Whether the request is a write rather than a read.
+    pub write: bool,
+}
+
+/// A block storage workload, like the SPC traces, with hot regions and sequential runs.
+///
+/// Each request either continues sequentially from the previous one, lands in a random hot
+/// region, or lands anywhere on the device, in that order of precedence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockWorkload {
+    /// The size of the device, in blocks.
+    pub device_blocks: u32,
+    /// The size of a block, in bytes.
+    pub block_size: u32,
+    /// The range of request sizes, in blocks, inclusive.
+    pub request_blocks: (u32, u32),
+    /// The probability that a request starts where the previous one ended.
+    pub sequential: f64,
+    /// The regions of the device, in blocks, which attract extra requests.
+    pub hot_regions: Vec<Range<u32>>,
+    /// The probability that a non-sequential request lands in a hot region.
+    pub hot_fraction: f64,
+    /// The probability that a request is a write.
+    pub write_fraction: f64,
+}
+
+impl Default for BlockWorkload {
+    fn default() -> Self {
+        Self {
+            device_blocks: 1 << 20,
+            block_size: 4096,
+            request_blocks: (1, 16),
+            sequential: 0.3,
+            hot_regions: Vec::new(),
+            hot_fraction: 0.0,
+            write_fraction: 0.3,
+        }
+    }
+}
+
+impl BlockWorkload {
+    /// Generate `len` requests.
+    pub fn generate(&self, len: usize, seed: u64) -> Vec<BlockRequest> {
+        let (min, max) = self.request_blocks;
+        assert!(
+            0 < min && min <= max && max <= self.device_blocks,
+            "request sizes must be positive and fit on the device"
+        );
+
+        let mut rng = Rng::new(seed);
+        let mut next = None;
+        (0..len)
+            .map(|_| {
+                let blocks = uniform_in(&mut rng, min..max + 1);
+                let start = match next {
+                    Some(next) if rng.next_f64() < self.sequential => next,
+                    _ if !self.hot_regions.is_empty() && rng.next_f64() < self.hot_fraction => {
+                        #[allow(clippy::cast_possible_truncation)]
+                        let region =
+                            &self.hot_regions[rng.below(self.hot_regions.len() as u64) as usize];
+                        uniform_in(&mut rng, region.clone())
+                    }
+                    _ => uniform_in(&mut rng, 0..self.device_blocks),
+                };
+                // keep the request on the device
+                let start = start.min(self.device_blocks - blocks);
+                next = Some((start + blocks) % self.device_blocks);
+
+                BlockRequest {
+                    offset: u64::from(start) * u64::from(self.block_size),
+                    size: u64::from(blocks) * u64::from(self.block_size),
+                    write: rng.next_f64() < self.write_fraction,
+                }
+            })
+            .collect()
+    }
+}
+
+// Draw uniformly from a range, or its start if it's empty.
+fn uniform_in(rng: &mut Rng, range: Range<u32>) -> u32 {
+    #[allow(clippy::cast_possible_truncation)]
+    let offset = rng.below(u64::from(range.end.saturating_sub(range.start)).max(1)) as u32;
+    range.start + offset
+}
+
+/// Expand block requests into a trace of the blocks they touch, in order.
+///
+/// A request which isn't aligned to `block_size` touches every block it overlaps.
+pub fn expand_blocks(requests: &[BlockRequest], block_size: u32) -> Trace {
+    assert!(block_size > 0, "the block size must be positive");
+
+    let block_size = u64::from(block_size);
+    let mut trace = Vec::new();
+    for request in requests.iter().filter(|r| r.size > 0) {
+        let first = request.offset / block_size;
+        let last = (request.offset + request.size - 1) / block_size;
+        #[allow(clippy::cast_possible_truncation)]
+        trace.extend((first..=last).map(|block| block as u32));
+    }
+    Trace::from(trace)
+}
+
+/// A process by which accesses arrive over time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arrivals {
+    /// Independent arrivals at a constant average rate, per second.
+    Poisson {
+        /// The mean number of arrivals per second.
+        rate: f64,
+    },
+    /// Bursts of Poisson arrivals separated by idle periods, with exponentially distributed
+    /// lengths.
+    OnOff {
+        /// The mean number of arrivals per second during a burst.
+        rate: f64,
+        /// The mean length of a burst, in seconds.
+        on: f64,
+        /// The mean length of an idle period, in seconds.
+        off: f64,
+    },
+}
+
+// Draw from an exponential distribution with the given mean.
+fn exponential(rng: &mut Rng, mean: f64) -> f64 {
+    -(1.0 - rng.next_f64()).ln() * mean
+}
+
+/// Generate the arrival times, in nanoseconds from the start, of `len` accesses.
+///
+/// Pair these with any trace to replay it at a controlled load.
+///
+/// # Panics
+///
+/// Panics if the rate or the mean length of a burst or idle period isn't positive and finite.
+pub fn timestamps(len: usize, arrivals: Arrivals, seed: u64) -> Vec<u64> {
+    let positive = |x: f64| x > 0.0 && x.is_finite();
+    match arrivals {
+        Arrivals::Poisson { rate } => {
+            assert!(positive(rate), "the rate must be positive and finite");
+        }
+        Arrivals::OnOff { rate, on, off } => assert!(
+            positive(rate) && positive(on) && positive(off),
+            "the rate and the mean lengths must be positive and finite"
+        ),
+    }
+    let mut rng = Rng::new(seed);
+    let mut now = 0.0;
+    // the time left in the current burst
+    let mut burst = match arrivals {
+        Arrivals::Poisson { .. } => f64::INFINITY,
+        Arrivals::OnOff { on, .. } => exponential(&mut rng, on),
+    };
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    (0..len)
+        .map(|_| {
+            match arrivals {
+                Arrivals::Poisson { rate } => now += exponential(&mut rng, rate.recip()),
+                Arrivals::OnOff { rate, on, off } => {
+                    let mut gap = exponential(&mut rng, rate.recip());
+                    // arrivals are memoryless, so one which falls after the burst ends is
+                    // redrawn from the start of the next
+                    while gap > burst {
+                        now += burst + exponential(&mut rng, off);
+                        burst = exponential(&mut rng, on);
+                        gap = exponential(&mut rng, rate.recip());
+                    }
+                    burst -= gap;
+                    now += gap;
+                }
+            }
+            (now * 1e9) as u64
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mrc::MissRatioCurve;
+    use crate::phase::{self, Divergence};
+    use crate::simulate::{self, Policy};
+    use crate::spatial::Strides;
+
+    #[test]
+    fn uniform_works() {
+        let trace = uniform(1000, 10, 1);
+        assert_eq!(trace.as_slice().len(), 1000);
+        assert!(trace.as_slice().iter().all(|&n| n < 10));
+        assert_eq!(trace.frequency_histogram().len(), 10);
+        assert_eq!(trace, uniform(1000, 10, 1));
+        assert_ne!(trace, uniform(1000, 10, 2));
+    }
+
+    #[test]
+    fn zipf_works() {
+        let trace = zipf(50_000, 100, 1.0, 3);
+        assert!(trace.as_slice().iter().all(|&n| n < 100));
+        assert_eq!(trace, zipf(50_000, 100, 1.0, 3));
+
+        let freqs = trace.frequency_histogram();
+        assert!(freqs[0] > freqs[1] && freqs[1] > freqs[9]);
+        let fit = crate::frequency::fit_zipf(&freqs).unwrap();
+        assert!((fit.exponent - 1.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn irm_works() {
+        #[allow(clippy::cast_precision_loss)]
+        let weights: Vec<_> = Trace::from(vec![0, 0, 0, 2, 2, 3])
+            .frequency_histogram()
+            .iter()
+            .map(|&n| n as f64)
+            .collect();
+        let freqs = irm(60_000, &weights, 4).frequency_histogram();
+        assert_eq!(freqs.len(), 4);
+        assert_eq!(freqs[1], 0);
+        assert!((29_000..31_000).contains(&freqs[0]));
+        assert!((19_000..21_000).contains(&freqs[2]));
+    }
+
+    #[test]
+    fn markov_works() {
+        let model = MarkovModel::new(
+            vec![10, 20],
+            vec![vec![0.0, 1.0], vec![1.0, 0.0]],
+            vec![0.0, 1.0],
+        )
+        .unwrap();
+        assert_eq!(markov(&model, 5, 1), Trace::from(vec![20, 10, 20, 10, 20]));
+
+        // fitting a generated trace recovers the model
+        let model = MarkovModel::new(
+            vec![0, 1],
+            vec![vec![0.9, 0.1], vec![0.5, 0.5]],
+            vec![0.5, 0.5],
+        )
+        .unwrap();
+        let fitted = MarkovModel::fit(&markov(&model, 50_000, 2));
+        for (from, to) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            assert!((fitted.probability(from, to) - model.probability(from, to)).abs() < 0.02);
+        }
+    }
+
+    #[test]
+    fn adversarial() {
+        let trace = cyclic(100, 4);
+        assert_eq!(&trace.as_slice()[..7], &[0, 1, 2, 3, 4, 0, 1]);
+        assert_eq!(MissRatioCurve::from(&trace).miss_ratio(4), 1.0);
+        assert!(MissRatioCurve::from(&trace).miss_ratio(5) < 0.1);
+
+        assert_eq!(
+            sawtooth(10, 3),
+            Trace::from(vec![0, 1, 2, 2, 1, 0, 0, 1, 2, 2])
+        );
+
+        let trace = thrash(16, 2, 4);
+        assert_eq!(
+            trace,
+            Trace::from(vec![0, 1, 0, 1, 2, 3, 2, 3, 0, 1, 0, 1, 2, 3, 2, 3])
+        );
+        assert_eq!(MissRatioCurve::from(&trace).miss_ratio(2), 0.5);
+    }
+
+    #[test]
+    fn hot_set_scan() {
+        let workload = HotSetScan::new(4, 6, 3);
+        assert_eq!(workload.scan_fraction(), 1.0 / 3.0);
+
+        let trace = workload.generate(18, 1);
+        assert!(trace.as_slice()[..6].iter().all(|&n| n < 4));
+        assert_eq!(&trace.as_slice()[6..9], &[4, 5, 6]);
+        assert_eq!(&trace.as_slice()[15..], &[7, 8, 9]);
+
+        // a scan-resistant policy keeps the hot set through the scans
+        let trace = HotSetScan::new(50, 1000, 100).generate(20_000, 2);
+        let lru = simulate::miss_ratios(&trace, Policy::Lru, &[60]);
+        let arc = simulate::miss_ratios(&trace, Policy::Arc, &[60]);
+        assert!(arc[0] < lru[0]);
+    }
+
+    #[test]
+    fn multi_phase_works() {
+        let phases = [
+            PhaseSpec {
+                len: 64,
+                alphabet_size: 2,
+                offset: 0,
+                pattern: Pattern::Cyclic,
+            },
+            PhaseSpec {
+                len: 64,
+                alphabet_size: 8,
+                offset: 10,
+                pattern: Pattern::Cyclic,
+            },
+            PhaseSpec {
+                len: 10,
+                alphabet_size: 5,
+                offset: 100,
+                pattern: Pattern::Zipf(1.0),
+            },
+        ];
+
+        let trace = multi_phase(&phases, &[0, 1, 0], 1);
+        assert_eq!(trace.as_slice().len(), 192);
+        assert_eq!(&trace.as_slice()[62..66], &[0, 1, 10, 11]);
+        let detected = phase::detect_phases(&trace, 16, 16, Divergence::EarthMovers, 1.0);
+        let boundaries: Vec<_> = detected.iter().map(|p| p.start).collect();
+        assert_eq!(boundaries, vec![0, 64, 128]);
+
+        let trace = multi_phase(&phases, &[2, 2], 1);
+        assert!(trace.as_slice().iter().all(|n| (100..105).contains(n)));
+        assert_ne!(&trace.as_slice()[..10], &trace.as_slice()[10..]);
+    }
+
+    #[test]
+    fn strided_works() {
+        assert_eq!(
+            strided(5, 100, 8, 3),
+            Trace::from(vec![100, 108, 116, 100, 108])
+        );
+
+        let row = LoopNest::matrix(3, 4, 8, Traversal::RowMajor).generate();
+        assert_eq!(row.as_slice().len(), 12);
+        assert_eq!(&row.as_slice()[..5], &[0, 8, 16, 24, 32]);
+        assert_eq!(Strides::new(&row).dominant(1), vec![(8, 11)]);
+
+        let column = LoopNest::matrix(3, 4, 8, Traversal::ColumnMajor).generate();
+        assert_eq!(&column.as_slice()[..4], &[0, 32, 64, 8]);
+        assert_eq!(Strides::new(&column).dominant(1), vec![(32, 8)]);
+
+        // c[i] += a[i][j] * b[j], with a 2x2 a at 100 and b at 200
+        let nest = LoopNest::new(vec![2, 2])
+            .reference(100, vec![2, 1])
+            .reference(200, vec![0, 1])
+            .reference(300, vec![1, 0]);
+        assert_eq!(
+            nest.generate(),
+            Trace::from(vec![
+                100, 200, 300, 101, 201, 300, 102, 200, 301, 103, 201, 301
+            ])
+        );
+        assert_eq!(LoopNest::new(vec![3, 0]).generate(), Trace::from(vec![]));
+    }
+
+    #[test]
+    fn kv_workload() {
+        let workload = KvWorkload {
+            keys: 100,
+            mix: [0.5, 0.5, 0.0],
+            sizes: SizeDistribution::Uniform { min: 10, max: 20 },
+            ttl: Some((5, 8)),
+            ..KvWorkload::default()
+        };
+        let requests = workload.generate(10_000, 1);
+        assert_eq!(requests, workload.generate(10_000, 1));
+        assert!(requests.iter().all(|r| r.key < 100));
+        assert!(requests.iter().all(|r| (10..=20).contains(&r.size)));
+
+        let sets = requests
+            .iter()
+            .filter(|r| r.operation == Operation::Set)
+            .count();
+        assert!((4500..5500).contains(&sets));
+        for r in &requests {
+            assert_eq!(r.ttl.is_some(), r.operation == Operation::Set);
+            assert!(r.ttl.is_none_or(|ttl| (5..=8).contains(&ttl)));
+        }
+
+        // a get sees the size of the last set to the same key
+        for (i, r) in requests.iter().enumerate() {
+            if r.operation == Operation::Get {
+                if let Some(set) = requests[..i]
+                    .iter()
+                    .rev()
+                    .find(|s| s.key == r.key && s.operation == Operation::Set)
+                {
+                    assert_eq!(set.size, r.size);
+                }
+            }
+        }
+
+        assert_eq!(key_trace(&requests).as_slice().len(), 10_000);
+    }
+
+    #[test]
+    fn pareto_sizes() {
+        let sizes = SizeDistribution::Pareto {
+            scale: 100.0,
+            shape: 1.5,
+        };
+        let mut rng = Rng::new(1);
+        let samples: Vec<_> = (0..10_000).map(|_| sizes.sample(&mut rng)).collect();
+        assert!(samples.iter().all(|&s| s >= 100));
+        assert!(samples.iter().any(|&s| s > 1000));
+    }
+
+    #[test]
+    fn gaussian_mixture_works() {
+        let components = [
+            Gaussian {
+                weight: 3.0,
+                mean: 1000.0,
+                std_dev: 10.0,
+            },
+            Gaussian {
+                weight: 1.0,
+                mean: 5000.0,
+                std_dev: 100.0,
+            },
+        ];
+        let trace = gaussian_mixture(10_000, &components, 0..10_000, 1);
+        assert_eq!(trace, gaussian_mixture(10_000, &components, 0..10_000, 1));
+
+        let near = |center: u32, spread: u32| {
+            trace
+                .as_slice()
+                .iter()
+                .filter(|&&n| n.abs_diff(center) <= spread)
+                .count()
+        };
+        // three standard deviations either side covers nearly everything
+        assert!((7300..7700).contains(&near(1000, 30)));
+        assert!((2300..2700).contains(&near(5000, 300)));
+
+        // a component hanging off the end of the range stays inside it
+        let edge = [Gaussian {
+            weight: 1.0,
+            mean: 0.0,
+            std_dev: 5.0,
+        }];
+        let trace = gaussian_mixture(1000, &edge, 0..100, 2);
+        assert!(trace.as_slice().iter().all(|&n| n < 100));
+    }
+
+    #[test]
+    fn block_workload() {
+        let workload = BlockWorkload {
+            device_blocks: 1000,
+            block_size: 512,
+            request_blocks: (2, 4),
+            sequential: 1.0,
+            ..BlockWorkload::default()
+        };
+        let requests = workload.generate(50, 1);
+        assert_eq!(requests, workload.generate(50, 1));
+        for pair in requests.windows(2) {
+            assert_eq!(
+                pair[1].offset,
+                (pair[0].offset + pair[0].size) % (1000 * 512)
+            );
+        }
+        assert!(requests.iter().all(|r| (1024..=2048).contains(&r.size)));
+
+        let workload = BlockWorkload {
+            device_blocks: 10_000,
+            sequential: 0.0,
+            hot_regions: vec![100..150, 150..200],
+            hot_fraction: 0.9,
+            request_blocks: (1, 1),
+            ..BlockWorkload::default()
+        };
+        let trace = expand_blocks(&workload.generate(10_000, 2), 4096);
+        let hot = trace
+            .as_slice()
+            .iter()
+            .filter(|n| (100..200).contains(*n))
+            .count();
+        assert!((8900..9200).contains(&hot));
+    }
+
+    #[test]
+    fn expand() {
+        let requests = [
+            BlockRequest {
+                offset: 0,
+                size: 8192,
+                write: false,
+            },
+            BlockRequest {
+                offset: 4000,
+                size: 200,
+                write: true,
+            },
+            BlockRequest {
+                offset: 12288,
+                size: 0,
+                write: false,
+            },
+        ];
+        assert_eq!(
+            expand_blocks(&requests, 4096),
+            Trace::from(vec![0, 1, 0, 1])
+        );
+    }
+
+    #[test]
+    fn poisson_arrivals() {
+        let times = timestamps(10_000, Arrivals::Poisson { rate: 1000.0 }, 1);
+        assert!(times.windows(2).all(|pair| pair[0] <= pair[1]));
+        // ten seconds, give or take
+        assert!((9_500_000_000..10_500_000_000).contains(&times[9999]));
+        assert_eq!(
+            times,
+            timestamps(10_000, Arrivals::Poisson { rate: 1000.0 }, 1)
+        );
+    }
+
+    #[test]
+    fn bursty_arrivals() {
+        let arrivals = Arrivals::OnOff {
+            rate: 1000.0,
+            on: 0.1,
+            off: 0.9,
+        };
+        let times = timestamps(10_000, arrivals, 2);
+        assert!(times.windows(2).all(|pair| pair[0] <= pair[1]));
+        // a tenth of the time on, so ten times slower overall
+        assert!((80_000_000_000..120_000_000_000).contains(&times[9999]));
+        // and gaps are far burstier than Poisson
+        let long = times
+            .windows(2)
+            .filter(|pair| pair[1] - pair[0] > 100_000_000)
+            .count();
+        assert!(long > 10);
+    }
+
+    #[test]
+    fn from_histogram_works() {
+        let target = zipf(2000, 50, 0.8, 5).stack_distance_histogram();
+        let trace = from_histogram(&target, 6);
+        assert_eq!(trace.stack_distance_histogram(), target);
+        assert_eq!(trace, from_histogram(&target, 6));
+
+        // a distance of 3 needs at least four symbols, so it's clamped
+        let infeasible = Histogram::new(vec![1, 0, 0, 1], 2);
+        let trace = from_histogram(&infeasible, 1);
+        assert_eq!(trace.as_slice().len(), 4);
+        assert_eq!(trace.stack_distance_histogram().infinities(), 2);
+        assert_eq!(
+            from_histogram(&Histogram::new(vec![2], 0), 1),
+            Trace::from(vec![0, 0])
+        );
+    }
+}
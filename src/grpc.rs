@@ -0,0 +1,109 @@
+//! Contains a gRPC service, for remote tracers to ship accesses to a central analyzer.
+//!
+//! The protocol is `proto/analyzer.proto`.
+
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+
+use tonic::transport::Server;
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::histogram::Histogram;
+use crate::online::OnlineAnalyzer;
+
+/// The messages and service generated from the protocol.
+#[allow(clippy::all, clippy::pedantic)]
+pub mod proto {
+    tonic::include_proto!("stack_distance");
+}
+
+use proto::analyzer_server::{Analyzer, AnalyzerServer};
+
+/// The service, with the analysis of every streamed access.
+#[derive(Debug, Default)]
+pub struct Service {
+    analyzer: Mutex<OnlineAnalyzer>,
+}
+
+impl From<&Histogram> for proto::Histogram {
+    fn from(histogram: &Histogram) -> Self {
+        Self {
+            counts: histogram
+                .frequencies()
+                .iter()
+                .map(|&count| count as u64)
+                .collect(),
+            infinities: histogram.infinities() as u64,
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Analyzer for Service {
+    async fn analyze(
+        &self,
+        request: Request<proto::Accesses>,
+    ) -> Result<Response<proto::Histogram>, Status> {
+        let mut analyzer = OnlineAnalyzer::new();
+        for address in request.into_inner().addresses {
+            analyzer.access(address);
+        }
+        Ok(Response::new(analyzer.histogram().into()))
+    }
+
+    async fn stream(
+        &self,
+        request: Request<Streaming<proto::Accesses>>,
+    ) -> Result<Response<proto::StreamSummary>, Status> {
+        let mut stream = request.into_inner();
+        let mut received = 0;
+        while let Some(accesses) = stream.message().await? {
+            let mut analyzer = self.analyzer.lock().expect("the lock isn't poisoned");
+            for &address in &accesses.addresses {
+                analyzer.access(address);
+            }
+            received += accesses.addresses.len() as u64;
+        }
+        Ok(Response::new(proto::StreamSummary { received }))
+    }
+
+    async fn query(
+        &self,
+        request: Request<proto::QueryRequest>,
+    ) -> Result<Response<proto::QueryResponse>, Status> {
+        let request = request.into_inner();
+        let mut analyzer = self.analyzer.lock().expect("the lock isn't poisoned");
+
+        let mrc = analyzer.mrc();
+        let miss_ratios = if request.sizes.is_empty() {
+            mrc.ratios().to_vec()
+        } else {
+            request
+                .sizes
+                .iter()
+                .map(|&size| mrc.miss_ratio(usize::try_from(size).unwrap_or(usize::MAX)))
+                .collect()
+        };
+        let response = proto::QueryResponse {
+            histogram: Some(analyzer.histogram().into()),
+            miss_ratios,
+        };
+
+        if request.reset {
+            analyzer.reset();
+        }
+        Ok(Response::new(response))
+    }
+}
+
+/// Serve the analyzer on an address until the process ends.
+pub fn serve(addr: SocketAddr) -> Result<(), Box<dyn Error>> {
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(
+        Server::builder()
+            .add_service(AnalyzerServer::new(Service::default()))
+            .serve(addr),
+    )?;
+    Ok(())
+}
@@ -0,0 +1,335 @@
+//! Contains the `Histogram` struct.
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// A histogram of stack distances.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Histogram {
+    // the ith entry is the number of accesses with distance i
+    freqs: Vec<usize>,
+    infinities: usize,
+}
+
+impl Histogram {
+    /// Create a histogram from frequencies of distances plus the count of infinities.
+    pub const fn new(freqs: Vec<usize>, infinities: usize) -> Self {
+        Self { freqs, infinities }
+    }
+
+    /// The frequencies of each finite distance.
+    pub fn frequencies(&self) -> &[usize] {
+        &self.freqs
+    }
+
+    /// The number of infinite distances, i.e. first accesses.
+    pub const fn infinities(&self) -> usize {
+        self.infinities
+    }
+
+    /// Count one more access with the given distance, or an infinity for `None`.
+    pub fn record(&mut self, distance: Option<usize>) {
+        match distance {
+            Some(distance) => {
+                if distance >= self.freqs.len() {
+                    self.freqs.resize(distance + 1, 0);
+                }
+                self.freqs[distance] += 1;
+            }
+            None => self.infinities += 1,
+        }
+    }
+
+    /// Count one fewer access with the given distance, or an infinity for `None`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there's no such access to forget.
+    pub fn forget(&mut self, distance: Option<usize>) {
+        match distance {
+            Some(distance) => {
+                self.freqs[distance] -= 1;
+                // keep the frequencies as short as if the access was never recorded
+                while self.freqs.last() == Some(&0) {
+                    self.freqs.pop();
+                }
+            }
+            None => self.infinities -= 1,
+        }
+    }
+
+    /// The total number of accesses.
+    pub fn total(&self) -> usize {
+        self.freqs.iter().sum::<usize>() + self.infinities
+    }
+
+    /// Calculate the cumulative distribution of distances.
+    ///
+    /// Returns a vector where the ith entry is the fraction of accesses with distance at most i.
+    /// Infinities are counted as larger than every distance, so the last entry is less than one
+    /// whenever there are any.
+    pub fn cdf(&self) -> Vec<f64> {
+        let total = self.total();
+        let mut seen = 0;
+
+        #[allow(clippy::cast_precision_loss)]
+        self.freqs
+            .iter()
+            .map(|n| {
+                seen += n;
+                seen as f64 / total as f64
+            })
+            .collect()
+    }
+
+    /// Calculate the smallest distance `d` such that at least a fraction `p` of accesses have
+    /// distance at most `d`.
+    ///
+    /// Returns `None` if the quantile is infinite, including when the histogram is empty.
+    pub fn quantile(&self, p: f64) -> Option<usize> {
+        if self.total() == 0 {
+            return None;
+        }
+        self.cdf().iter().position(|&c| c >= p)
+    }
+
+    /// Calculate the fraction of accesses with distance greater than `k`, including infinities.
+    ///
+    /// This is the miss ratio of an LRU cache of size `k + 1`.
+    pub fn tail_mass_above(&self, k: usize) -> f64 {
+        let total = self.total();
+        if total == 0 {
+            return 0.0;
+        }
+        let tail = self.freqs.iter().skip(k + 1).sum::<usize>() + self.infinities;
+
+        #[allow(clippy::cast_precision_loss)]
+        let mass = tail as f64 / total as f64;
+        mass
+    }
+
+    /// Calculate a temporal locality score between zero and one, where higher is better.
+    ///
+    /// This is one minus the area under the miss-ratio curve for cache sizes from one to the
+    /// number of distinct symbols, with sizes normalized to that number, so traces over different
+    /// numbers of symbols are comparable. Reusing one symbol scores close to one, and cycling
+    /// through many symbols scores close to zero. An empty histogram scores zero.
+    pub fn locality_score(&self) -> f64 {
+        let total = self.total();
+        if total == 0 {
+            return 0.0;
+        }
+
+        // the miss ratio at size c is the mass above distance c - 1
+        let mut tail = total;
+        let mut area = 0;
+        for c in 1..=self.infinities {
+            tail -= self.freqs.get(c - 1).unwrap_or(&0);
+            area += tail;
+        }
+
+        #[allow(clippy::cast_precision_loss)]
+        let score = 1.0 - area as f64 / (self.infinities * total) as f64;
+        score
+    }
+
+    /// Calculate the mean of `log2(1 + d)` over all distances `d`, counting infinities as the
+    /// number of distinct symbols.
+    ///
+    /// Lower is better. Unlike the locality score, this isn't normalized, so it's better suited
+    /// to comparing traces over the same symbols.
+    pub fn mean_log_distance(&self) -> f64 {
+        let total = self.total();
+        if total == 0 {
+            return 0.0;
+        }
+
+        #[allow(clippy::cast_precision_loss)]
+        let sum: f64 = self
+            .freqs
+            .iter()
+            .enumerate()
+            .map(|(d, &n)| n as f64 * (d as f64).ln_1p())
+            .chain(std::iter::once(
+                self.infinities as f64 * (self.infinities as f64).ln_1p(),
+            ))
+            .sum();
+
+        #[allow(clippy::cast_precision_loss)]
+        let mean = sum / std::f64::consts::LN_2 / total as f64;
+        mean
+    }
+
+    /// Compare the locality of this histogram against a baseline.
+    ///
+    /// Returns the drop in locality score, so a positive value is a regression.
+    pub fn locality_regression(&self, baseline: &Self) -> f64 {
+        baseline.locality_score() - self.locality_score()
+    }
+}
+
+impl From<&[Option<usize>]> for Histogram {
+    /// Bucket per-operation distances into a histogram.
+    fn from(distances: &[Option<usize>]) -> Self {
+        let max = distances.iter().flatten().max();
+
+        let mut freqs = max.map_or_else(Vec::new, |max| vec![0; max + 1]);
+
+        let mut infinities = 0;
+
+        for i in distances {
+            #[allow(clippy::option_if_let_else)]
+            if let Some(i) = i {
+                freqs[*i] += 1;
+            } else {
+                infinities += 1;
+            }
+        }
+
+        Self { freqs, infinities }
+    }
+}
+
+/// An error parsing a histogram, holding the line which didn't parse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseHistogramError(pub String);
+
+impl Display for ParseHistogramError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid histogram row `{}`", self.0)
+    }
+}
+
+impl std::error::Error for ParseHistogramError {}
+
+impl FromStr for Histogram {
+    type Err = ParseHistogramError;
+
+    /// Parse a histogram from CSV rows of a distance and its count, with `inf` for the
+    /// infinities, as written by `stack-distance analyze --histogram`. The header is optional and
+    /// missing distances count zero.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut histogram = Self::default();
+        for line in s.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let error = || ParseHistogramError(line.to_string());
+            let (distance, count) = line.split_once(',').ok_or_else(error)?;
+            if distance == "distance" {
+                continue;
+            }
+            let count = count.trim().parse().map_err(|_| error())?;
+            match distance.trim() {
+                "inf" => histogram.infinities = count,
+                distance => {
+                    let distance: usize = distance.parse().map_err(|_| error())?;
+                    if histogram.freqs.len() <= distance {
+                        histogram.freqs.resize(distance + 1, 0);
+                    }
+                    histogram.freqs[distance] = count;
+                }
+            }
+        }
+        Ok(histogram)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::Trace;
+
+    fn histogram() -> Histogram {
+        // 10 accesses: 4 at distance 0, 2 at 1, 1 at 3, and 3 infinities
+        Histogram::new(vec![4, 2, 0, 1], 3)
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            "distance,count\n0,4\n1,2\n3,1\ninf,3\n".parse(),
+            Ok(histogram())
+        );
+        assert_eq!("".parse(), Ok(Histogram::default()));
+        assert_eq!(
+            "0,4\nx,1".parse::<Histogram>(),
+            Err(ParseHistogramError("x,1".to_string()))
+        );
+    }
+
+    #[test]
+    fn record() {
+        let mut recorded = Histogram::default();
+        for distance in [
+            None,
+            Some(3),
+            Some(0),
+            None,
+            Some(1),
+            Some(0),
+            None,
+            Some(0),
+            Some(1),
+        ] {
+            recorded.record(distance);
+        }
+        recorded.record(Some(0));
+        assert_eq!(recorded, histogram());
+    }
+
+    #[test]
+    fn cdf() {
+        assert_eq!(histogram().cdf(), vec![0.4, 0.6, 0.6, 0.7]);
+        assert_eq!(Histogram::default().cdf(), vec![]);
+    }
+
+    #[test]
+    fn quantile() {
+        let histogram = histogram();
+        assert_eq!(histogram.quantile(0.0), Some(0));
+        assert_eq!(histogram.quantile(0.5), Some(1));
+        assert_eq!(histogram.quantile(0.6), Some(1));
+        assert_eq!(histogram.quantile(0.65), Some(3));
+        assert_eq!(histogram.quantile(0.99), None);
+        assert_eq!(Histogram::default().quantile(0.5), None);
+    }
+
+    #[test]
+    fn tail_mass() {
+        let histogram = histogram();
+        assert!((histogram.tail_mass_above(0) - 0.6).abs() < 1e-12);
+        assert!((histogram.tail_mass_above(2) - 0.4).abs() < 1e-12);
+        assert!((histogram.tail_mass_above(100) - 0.3).abs() < 1e-12);
+        assert_eq!(Histogram::default().tail_mass_above(0), 0.0);
+    }
+
+    #[test]
+    fn locality() {
+        let repeated = Trace::from(vec![0; 100]).stack_distance_histogram();
+        let cyclic =
+            Trace::from((0..100).map(|i| i % 10).collect::<Vec<_>>()).stack_distance_histogram();
+        let random = histogram();
+
+        // sizes 1, 2, 3 miss 0.6, 0.4, 0.4 of the time
+        assert!((random.locality_score() - (1.0 - 1.4 / 3.0)).abs() < 1e-12);
+        assert!((repeated.locality_score() - 0.99).abs() < 1e-12);
+        assert!((cyclic.locality_score() - 0.09).abs() < 1e-12);
+        assert!(cyclic.locality_regression(&repeated) > 0.0);
+        assert_eq!(Histogram::default().locality_score(), 0.0);
+    }
+
+    #[test]
+    fn mean_log_distance() {
+        // log2(1 + 1) = 1, and log2(1 + 3) = 2 for the distance of 3 and the three infinities
+        let histogram = Histogram::new(vec![0, 1, 0, 1], 3);
+        assert!((histogram.mean_log_distance() - 1.8).abs() < 1e-12);
+        assert_eq!(Histogram::default().mean_log_distance(), 0.0);
+    }
+
+    #[test]
+    fn from_distances() {
+        let distances = [None, Some(2), Some(0), None];
+        assert_eq!(
+            Histogram::from(&distances[..]),
+            Histogram::new(vec![1, 0, 1], 2)
+        );
+    }
+}
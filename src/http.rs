@@ -0,0 +1,272 @@
+//! Contains an HTTP API, for dashboards to upload traces and download their analyses as JSON.
+//!
+//! The endpoints are:
+//!
+//! - `POST /traces`, with a trace as the body, starts analyzing it and returns its id.
+//! - `GET /traces/ID` returns the status of the analysis, of `pending`, `done`, and `failed`.
+//! - `GET /traces/ID/summary`, `GET /traces/ID/histogram`, and `GET /traces/ID/mrc` return the
+//!   results of a finished analysis, as tables encoded in JSON.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use tracing::{debug, warn};
+
+use crate::histogram::Histogram;
+use crate::mrc::MissRatioCurve;
+use crate::summary::Summary;
+use crate::table::{json_string, Format, Table};
+use crate::trace::Trace;
+
+/// A response to a request, with a JSON body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Response {
+    fn error(status: u16, message: &str) -> Self {
+        Self {
+            status,
+            body: format!("{{\"error\":{}}}", json_string(message)),
+        }
+    }
+
+    fn table(table: &Table) -> Self {
+        let mut body = Vec::new();
+        table
+            .write(Format::Json, &mut body)
+            .expect("writing to memory succeeds");
+        Self {
+            status: 200,
+            body: String::from_utf8(body).expect("JSON is UTF-8"),
+        }
+    }
+
+    const fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            202 => "Accepted",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            409 => "Conflict",
+            422 => "Unprocessable Entity",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+// The analysis of an uploaded trace.
+enum Job {
+    Pending,
+    Done(Box<(Summary, Histogram)>),
+    Failed(String),
+}
+
+/// The uploaded traces and their analyses, which are numbered in order of upload.
+#[derive(Default)]
+pub struct Api {
+    jobs: Mutex<Vec<Job>>,
+}
+
+impl Api {
+    /// Respond to a request, starting an analysis on another thread for an upload.
+    pub fn handle(self: &Arc<Self>, method: &str, path: &str, body: String) -> Response {
+        let segments: Vec<&str> = path
+            .trim_matches('/')
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect();
+
+        match (method, segments.as_slice()) {
+            ("POST", ["traces"]) => self.upload(body),
+            ("GET", ["traces", id, rest @ ..]) => {
+                let Some(id) = id.parse::<usize>().ok() else {
+                    return Response::error(404, "no such trace");
+                };
+                let jobs = self.jobs.lock().expect("the lock isn't poisoned");
+                let Some(job) = jobs.get(id) else {
+                    return Response::error(404, "no such trace");
+                };
+                match (rest, job) {
+                    ([], job) => {
+                        let (status, error) = match job {
+                            Job::Pending => ("pending", None),
+                            Job::Done(_) => ("done", None),
+                            Job::Failed(error) => ("failed", Some(error)),
+                        };
+                        let error = error
+                            .map_or_else(String::new, |e| format!(",\"error\":{}", json_string(e)));
+                        Response {
+                            status: 200,
+                            body: format!("{{\"id\":{},\"status\":\"{}\"{}}}", id, status, error),
+                        }
+                    }
+                    ([result], Job::Done(analysis)) => {
+                        let (summary, histogram) = analysis.as_ref();
+                        match *result {
+                            "summary" => Response::table(&Table::from(summary)),
+                            "histogram" => Response::table(&Table::from(histogram)),
+                            "mrc" => Response::table(&Table::from(
+                                &MissRatioCurve::from_histogram(histogram),
+                            )),
+                            _ => Response::error(404, "no such result"),
+                        }
+                    }
+                    // the trace will never have results, so there's no use polling
+                    ([_], Job::Failed(error)) => Response::error(422, error),
+                    ([_], Job::Pending) => Response::error(409, "the analysis isn't done"),
+                    _ => Response::error(404, "no such result"),
+                }
+            }
+            (_, ["traces", ..]) => Response::error(405, "use POST to upload and GET to download"),
+            _ => Response::error(404, "no such endpoint"),
+        }
+    }
+
+    fn upload(self: &Arc<Self>, body: String) -> Response {
+        let id = {
+            let mut jobs = self.jobs.lock().expect("the lock isn't poisoned");
+            jobs.push(Job::Pending);
+            jobs.len() - 1
+        };
+        debug!(id, bytes = body.len(), "analyzing an upload");
+
+        let api = Arc::clone(self);
+        thread::spawn(move || {
+            let job = match body.parse::<Trace>() {
+                Ok(trace) => Job::Done(Box::new((
+                    Summary::new(&trace),
+                    trace.stack_distance_histogram(),
+                ))),
+                Err(e) => Job::Failed(format!("the trace doesn't parse: {}", e)),
+            };
+            api.jobs.lock().expect("the lock isn't poisoned")[id] = job;
+        });
+
+        Response {
+            status: 202,
+            body: format!("{{\"id\":{},\"status\":\"pending\"}}", id),
+        }
+    }
+}
+
+// Read one request and write its response.
+fn handle_connection(api: &Arc<Api>, stream: TcpStream) -> io::Result<()> {
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (
+        parts.next().unwrap_or_default().to_string(),
+        parts.next().unwrap_or_default().to_string(),
+    );
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    let mut body = Vec::new();
+    reader.take(content_length as u64).read_to_end(&mut body)?;
+
+    let response = if body.len() == content_length {
+        match String::from_utf8(body) {
+            Ok(body) => api.handle(&method, &path, body),
+            Err(_) => Response::error(400, "the body isn't UTF-8"),
+        }
+    } else {
+        Response::error(400, "the body is shorter than its length")
+    };
+    debug!(%method, %path, status = response.status, "handled a request");
+    write!(
+        &stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        response.status,
+        response.reason(),
+        response.body.len(),
+        response.body
+    )
+}
+
+/// Serve the API on an address until the process ends, handling each connection on its own
+/// thread.
+pub fn serve(addr: SocketAddr) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    let api = Arc::new(Api::default());
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let api = Arc::clone(&api);
+        thread::spawn(move || {
+            if let Err(e) = handle_connection(&api, stream) {
+                warn!(%e, "dropped a connection");
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn api() {
+        let api = Arc::new(Api::default());
+        let upload = api.handle("POST", "/traces", "1 2 1 3 2 1".to_string());
+        assert_eq!(upload.status, 202);
+        assert_eq!(upload.body, "{\"id\":0,\"status\":\"pending\"}");
+
+        // the analysis finishes on another thread
+        while api
+            .handle("GET", "/traces/0", String::new())
+            .body
+            .contains("pending")
+        {
+            thread::yield_now();
+        }
+        assert_eq!(
+            api.handle("GET", "/traces/0", String::new()).body,
+            "{\"id\":0,\"status\":\"done\"}"
+        );
+        assert_eq!(
+            api.handle("GET", "/traces/0/histogram", String::new()).body,
+            "[{\"distance\":0,\"count\":0},{\"distance\":1,\"count\":1},\
+             {\"distance\":2,\"count\":2},{\"distance\":\"inf\",\"count\":3}]\n"
+        );
+        assert_eq!(
+            api.handle("GET", "/traces/0/mrc", String::new()).status,
+            200
+        );
+
+        api.handle("POST", "/traces", "1 x".to_string());
+        while api
+            .handle("GET", "/traces/1", String::new())
+            .body
+            .contains("pending")
+        {
+            thread::yield_now();
+        }
+        assert!(api
+            .handle("GET", "/traces/1", String::new())
+            .body
+            .contains("failed"));
+        let failed = api.handle("GET", "/traces/1/summary", String::new());
+        assert_eq!(failed.status, 422);
+        assert!(failed.body.contains("invalid digit"));
+        assert_eq!(api.handle("GET", "/traces/2", String::new()).status, 404);
+        assert_eq!(api.handle("DELETE", "/traces/0", String::new()).status, 405);
+    }
+}
@@ -0,0 +1,183 @@
+//! Contains asynchronous ingestion of accesses from sockets, for real-time feeds from remote
+//! tracers.
+//!
+//! A feed is a sequence of records, each a little-endian `u32` length and then an address in
+//! that many little-endian bytes, up to eight, so 32-bit tracers can send four. Every connection
+//! feeds the same [`OnlineAnalyzer`].
+
+use std::io;
+use std::net::SocketAddr;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::net::TcpListener;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tracing::{debug, warn};
+
+use crate::online::OnlineAnalyzer;
+
+/// Reads the addresses of a feed as they arrive.
+#[derive(Debug)]
+pub struct TraceStream<R> {
+    reader: R,
+}
+
+impl<R: AsyncRead + Unpin> TraceStream<R> {
+    /// Read a feed from a socket, or anything else asynchronously readable.
+    pub fn new(reader: R) -> Self {
+        Self { reader }
+    }
+
+    /// The next address, or `None` once the feed ends.
+    pub async fn next(&mut self) -> io::Result<Option<u64>> {
+        // the feed may only end between records, so an end after the first byte truncates one
+        let mut prefix = [0; 4];
+        if self.reader.read(&mut prefix[..1]).await? == 0 {
+            return Ok(None);
+        }
+        let truncated = |e: io::Error| match e.kind() {
+            io::ErrorKind::UnexpectedEof => {
+                io::Error::new(io::ErrorKind::InvalidData, "the feed ended within a record")
+            }
+            _ => e,
+        };
+        self.reader
+            .read_exact(&mut prefix[1..])
+            .await
+            .map_err(truncated)?;
+
+        let len = u32::from_le_bytes(prefix) as usize;
+        let mut bytes = [0; 8];
+        let address = bytes.get_mut(..len).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("an address of {} bytes is longer than eight", len),
+            )
+        })?;
+        self.reader.read_exact(address).await.map_err(truncated)?;
+        Ok(Some(u64::from_le_bytes(bytes)))
+    }
+
+    /// Access every address until the feed ends, returning how many there were.
+    pub async fn feed(&mut self, analyzer: &Mutex<OnlineAnalyzer>) -> io::Result<usize> {
+        let mut accesses = 0;
+        while let Some(address) = self.next().await? {
+            analyzer.lock().expect("analyzer poisoned").access(address);
+            accesses += 1;
+        }
+        Ok(accesses)
+    }
+}
+
+/// Where to listen for feeds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Socket {
+    /// A TCP address.
+    Tcp(SocketAddr),
+    /// The path of a Unix socket.
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+// Feed a connection into the analyzer in its own task, so a broken one doesn't stop the rest.
+fn spawn_feed<R>(reader: R, analyzer: &Arc<Mutex<OnlineAnalyzer>>)
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    let analyzer = Arc::clone(analyzer);
+    tokio::spawn(async move {
+        match TraceStream::new(reader).feed(&analyzer).await {
+            Ok(accesses) => debug!(accesses, "feed ended"),
+            Err(e) => warn!("feed failed: {}", e),
+        }
+    });
+}
+
+/// Listen for feeds until the process ends, calling `report` with the analysis so far every
+/// `interval`.
+pub fn serve(
+    socket: &Socket,
+    interval: Duration,
+    mut report: impl FnMut(&OnlineAnalyzer) -> io::Result<()>,
+) -> io::Result<()> {
+    let runtime = tokio::runtime::Runtime::new()?;
+    let analyzer = Arc::new(Mutex::new(OnlineAnalyzer::new()));
+
+    let accepting = Arc::clone(&analyzer);
+    match socket {
+        Socket::Tcp(addr) => {
+            let listener = runtime.block_on(TcpListener::bind(addr))?;
+            runtime.spawn(async move {
+                loop {
+                    match listener.accept().await {
+                        Ok((stream, peer)) => {
+                            debug!(%peer, "feed connected");
+                            spawn_feed(stream, &accepting);
+                        }
+                        Err(e) => warn!("couldn't accept a feed: {}", e),
+                    }
+                }
+            });
+        }
+        #[cfg(unix)]
+        Socket::Unix(path) => {
+            let listener = {
+                let _guard = runtime.enter();
+                UnixListener::bind(path)?
+            };
+            runtime.spawn(async move {
+                loop {
+                    match listener.accept().await {
+                        Ok((stream, _)) => spawn_feed(stream, &accepting),
+                        Err(e) => warn!("couldn't accept a feed: {}", e),
+                    }
+                }
+            });
+        }
+    }
+
+    loop {
+        thread::sleep(interval);
+        report(&analyzer.lock().expect("analyzer poisoned"))?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trace_stream() {
+        let feed: Vec<u8> = [(4, 7), (8, 1 << 40), (4, 7), (1, 7)]
+            .into_iter()
+            .flat_map(|(len, address): (u32, u64)| {
+                let mut record = len.to_le_bytes().to_vec();
+                record.extend_from_slice(&address.to_le_bytes()[..len as usize]);
+                record
+            })
+            .collect();
+        let analyzer = Mutex::new(OnlineAnalyzer::new());
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let accesses = runtime.block_on(TraceStream::new(feed.as_slice()).feed(&analyzer));
+        assert_eq!(accesses.unwrap(), 4);
+        assert_eq!(
+            analyzer.into_inner().unwrap().histogram().frequencies(),
+            [1, 1]
+        );
+
+        let long = [9, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9];
+        assert!(runtime
+            .block_on(TraceStream::new(&long[..]).next())
+            .is_err());
+        for truncated in [&[8, 0][..], &[8, 0, 0, 0, 1, 2]] {
+            let next = runtime.block_on(TraceStream::new(truncated).next());
+            assert_eq!(next.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        }
+    }
+}
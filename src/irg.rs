@@ -0,0 +1,157 @@
+//! Contains per-symbol inter-reference gap (IRG) analysis.
+//!
+//! The gap between two consecutive references to a symbol is the difference of their times, so
+//! immediate reuse is a gap of one.
+
+use std::collections::HashMap;
+
+use crate::trace::Trace;
+
+/// Calculate the inter-reference gaps of every symbol, in trace order.
+///
+/// Symbols referenced only once have no gaps.
+pub fn inter_reference_gaps(trace: &Trace) -> HashMap<u32, Vec<usize>> {
+    let mut last = HashMap::new();
+    let mut gaps: HashMap<u32, Vec<usize>> = HashMap::new();
+
+    for (time, &curr) in trace.as_slice().iter().enumerate() {
+        let entry = gaps.entry(curr).or_default();
+        if let Some(previous) = last.insert(curr, time) {
+            entry.push(time - previous);
+        }
+    }
+
+    gaps
+}
+
+/// A model of the distribution of a symbol's inter-reference gaps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GapModel {
+    /// Gaps of `k` with probability `p (1 - p)^(k - 1)`, as if the symbol were referenced
+    /// independently with probability `p` at every access.
+    Geometric { p: f64 },
+    /// A mixture of two exponentials: with probability `p` the gap has rate `fast`, and
+    /// otherwise it has rate `slow`. This captures bursts of reuse separated by long idle
+    /// periods.
+    Hyperexponential { p: f64, fast: f64, slow: f64 },
+}
+
+// The mean and squared coefficient of variation of some gaps.
+#[allow(clippy::cast_precision_loss)]
+fn moments(gaps: &[usize]) -> Option<(f64, f64)> {
+    if gaps.is_empty() {
+        return None;
+    }
+    let len = gaps.len() as f64;
+    let mean = gaps.iter().sum::<usize>() as f64 / len;
+    let variance = gaps.iter().map(|&g| (g as f64 - mean).powi(2)).sum::<f64>() / len;
+    Some((mean, variance / (mean * mean)))
+}
+
+impl GapModel {
+    /// Fit a geometric model by maximum likelihood.
+    ///
+    /// Returns `None` if there are no gaps.
+    pub fn fit_geometric(gaps: &[usize]) -> Option<Self> {
+        let (mean, _) = moments(gaps)?;
+        Some(Self::Geometric { p: 1.0 / mean })
+    }
+
+    /// Fit a two-phase hyperexponential model by matching the first two moments, with balanced
+    /// means.
+    ///
+    /// Gaps less variable than an exponential can't be matched, so those degenerate to a single
+    /// exponential with both rates equal. Returns `None` if there are no gaps.
+    pub fn fit_hyperexponential(gaps: &[usize]) -> Option<Self> {
+        let (mean, cv2) = moments(gaps)?;
+        let p = if cv2 > 1.0 {
+            0.5 * (1.0 + ((cv2 - 1.0) / (cv2 + 1.0)).sqrt())
+        } else {
+            0.5
+        };
+        Some(Self::Hyperexponential {
+            p,
+            fast: 2.0 * p / mean,
+            slow: 2.0 * (1.0 - p) / mean,
+        })
+    }
+
+    /// The mean gap under the model.
+    pub fn mean(&self) -> f64 {
+        match *self {
+            Self::Geometric { p } => 1.0 / p,
+            Self::Hyperexponential { p, fast, slow } => p / fast + (1.0 - p) / slow,
+        }
+    }
+
+    /// The probability that a gap is at most `t`.
+    ///
+    /// An LRU cache with characteristic time `t` (see [`crate::che`]) keeps a symbol for `t`
+    /// accesses after each reference, so this predicts the hit ratio of the symbol's reuses.
+    pub fn cdf(&self, t: f64) -> f64 {
+        if t <= 0.0 {
+            return 0.0;
+        }
+        match *self {
+            Self::Geometric { p } => 1.0 - (1.0 - p).powf(t.floor()),
+            Self::Hyperexponential { p, fast, slow } => {
+                1.0 - p * (-fast * t).exp() - (1.0 - p) * (-slow * t).exp()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn gaps() {
+        let gaps = inter_reference_gaps(&Trace::from(vec![0, 1, 0, 0, 2, 1]));
+        assert_eq!(gaps[&0], vec![2, 1]);
+        assert_eq!(gaps[&1], vec![4]);
+        assert_eq!(gaps[&2], vec![]);
+        assert_eq!(gaps.len(), 3);
+    }
+
+    #[test]
+    fn geometric() {
+        let model = GapModel::fit_geometric(&[1, 3, 2, 2]).unwrap();
+        assert_eq!(model, GapModel::Geometric { p: 0.5 });
+        assert!(close(model.mean(), 2.0));
+        assert!(close(model.cdf(1.0), 0.5));
+        assert!(close(model.cdf(2.0), 0.75));
+        assert!(close(model.cdf(0.0), 0.0));
+        assert_eq!(GapModel::fit_geometric(&[]), None);
+    }
+
+    #[test]
+    fn hyperexponential() {
+        // bursts of immediate reuse with occasional long gaps
+        let gaps = [1, 1, 1, 1, 1, 1, 1, 1, 1, 91];
+        let model = GapModel::fit_hyperexponential(&gaps).unwrap();
+        assert!(close(model.mean(), 10.0));
+        let GapModel::Hyperexponential { p, fast, slow } = model else {
+            panic!("expected a hyperexponential");
+        };
+        assert!(p > 0.5 && fast > slow);
+        assert!(model.cdf(5.0) > GapModel::fit_geometric(&gaps).unwrap().cdf(5.0));
+    }
+
+    #[test]
+    fn hyperexponential_degenerates() {
+        let model = GapModel::fit_hyperexponential(&[4, 4, 4]).unwrap();
+        assert_eq!(
+            model,
+            GapModel::Hyperexponential {
+                p: 0.5,
+                fast: 0.25,
+                slow: 0.25
+            }
+        );
+    }
+}
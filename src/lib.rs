@@ -0,0 +1,60 @@
+//! A utility for manipulating stack distances.
+
+pub mod calibrate;
+pub mod capture;
+pub mod chart;
+pub mod che;
+pub mod classes;
+pub mod classify;
+pub mod counting;
+#[cfg(feature = "polars")]
+pub mod dataframe;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "flight")]
+pub mod flight;
+pub mod footprint;
+pub mod frequency;
+pub mod generate;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod histogram;
+pub mod http;
+#[cfg(feature = "tokio")]
+pub mod ingest;
+pub mod irg;
+pub mod libcachesim;
+pub mod lifetime;
+pub mod markov;
+pub mod metrics;
+pub mod mrc;
+#[cfg(feature = "node")]
+pub mod node;
+pub mod online;
+pub mod overlap;
+pub mod partial;
+#[cfg(all(feature = "perf-event", target_os = "linux"))]
+pub mod perf_event;
+pub mod phase;
+pub mod pipeline;
+pub mod priority;
+#[cfg(feature = "r")]
+pub mod r;
+pub mod rng;
+pub mod sampling;
+pub mod shard;
+pub mod simulate;
+pub mod spatial;
+#[cfg(feature = "proptest")]
+pub mod strategy;
+pub mod summary;
+pub mod table;
+pub mod timeseries;
+pub mod trace;
+pub mod transform;
+pub mod validate;
+pub mod verify;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod window;
+pub mod working_set;
@@ -0,0 +1,148 @@
+//! Contains the conventions of libCacheSim, so results can feed pipelines built around it.
+//!
+//! Sizes are counts of objects, as in libCacheSim with object sizes ignored, and the results
+//! are written in the layout of the summary lines of its `cachesim` tool.
+
+use std::io::{self, Write};
+
+use crate::histogram::Histogram;
+use crate::simulate::{self, ParseError, Policy};
+
+/// The fractions of the working set simulated when libCacheSim is asked to pick sizes.
+pub const AUTO_FRACTIONS: [f64; 8] = [0.001, 0.003, 0.01, 0.03, 0.1, 0.2, 0.4, 0.8];
+
+/// The name libCacheSim gives a policy.
+pub const fn algorithm_name(policy: Policy) -> &'static str {
+    match policy {
+        Policy::Lru => "LRU",
+        Policy::Fifo => "FIFO",
+        Policy::Arc => "ARC",
+        Policy::Clock => "Clock",
+        Policy::Custom(name) => name,
+    }
+}
+
+// Parse a size with one of libCacheSim's binary units, like `4KiB` or `4KB`.
+fn parse_unit_size(s: &str) -> Result<usize, ParseError> {
+    let lower = s.trim().to_ascii_lowercase();
+    let digits = lower.trim_end_matches("ib").trim_end_matches('b');
+    let shift = match digits.chars().last() {
+        Some('k') => 10,
+        Some('m') => 20,
+        Some('g') => 30,
+        Some('t') => 40,
+        _ => return simulate::parse_size(digits).map_err(|_| ParseError::Size(s.to_string())),
+    };
+
+    digits[..digits.len() - 1]
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(1 << shift))
+        .ok_or_else(|| ParseError::Size(s.to_string()))
+}
+
+/// Parse libCacheSim's cache sizes: a comma-separated list of sizes like `1000` or `4KiB`, or of
+/// fractions of the working set like `0.01`, or `0` to simulate [`AUTO_FRACTIONS`].
+///
+/// A fraction of the working set is at least one object.
+pub fn parse_sizes(s: &str, working_set: usize) -> Result<Vec<usize>, ParseError> {
+    let fraction = |f: f64| {
+        #[allow(
+            clippy::cast_precision_loss,
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss
+        )]
+        let size = (working_set as f64 * f).round() as usize;
+        size.max(1)
+    };
+    if s.trim() == "0" {
+        let mut sizes: Vec<_> = AUTO_FRACTIONS.iter().map(|&f| fraction(f)).collect();
+        sizes.dedup();
+        return Ok(sizes);
+    }
+
+    s.split(',')
+        .map(|size| match size.trim().parse::<f64>() {
+            Ok(f) if f > 0.0 && f < 1.0 => Ok(fraction(f)),
+            _ => parse_unit_size(size),
+        })
+        .collect()
+}
+
+/// Write a `cachesim` summary line for each size a policy was simulated at.
+pub fn write_results<W: Write>(
+    mut w: W,
+    trace_name: &str,
+    policy: Policy,
+    requests: usize,
+    sizes: &[usize],
+    miss_ratios: &[f64],
+) -> io::Result<()> {
+    for (size, miss_ratio) in sizes.iter().zip(miss_ratios) {
+        // every object has size one, so the byte miss ratio is the miss ratio
+        writeln!(
+            w,
+            "{} {} cache size {}, {} req, miss ratio {:.4}, byte miss ratio {:.4}",
+            trace_name,
+            algorithm_name(policy),
+            size,
+            requests,
+            miss_ratio,
+            miss_ratio
+        )?;
+    }
+    Ok(())
+}
+
+/// Write a stack distance histogram as lines of a distance and its count, with first accesses
+/// at distance -1.
+pub fn write_histogram<W: Write>(mut w: W, histogram: &Histogram) -> io::Result<()> {
+    writeln!(w, "# reuse distance, count")?;
+    if histogram.infinities() > 0 {
+        writeln!(w, "-1 {}", histogram.infinities())?;
+    }
+    for (distance, &count) in histogram.frequencies().iter().enumerate() {
+        if count > 0 {
+            writeln!(w, "{} {}", distance, count)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_sizes_works() {
+        assert_eq!(
+            parse_sizes("100,4KiB,1mb", 10),
+            Ok(vec![100, 4096, 1 << 20])
+        );
+        assert_eq!(parse_sizes("0.1,0.5,0.0001", 1000), Ok(vec![100, 500, 1]));
+        assert_eq!(
+            parse_sizes("0", 1000),
+            Ok(vec![1, 3, 10, 30, 100, 200, 400, 800])
+        );
+        assert!(parse_sizes("1.5", 10).is_err());
+        assert!(parse_sizes("4XiB", 10).is_err());
+    }
+
+    #[test]
+    fn output() {
+        let mut out = Vec::new();
+        write_results(&mut out, "t.txt", Policy::Lru, 6, &[1, 2], &[1.0, 0.8333]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "t.txt LRU cache size 1, 6 req, miss ratio 1.0000, byte miss ratio 1.0000\n\
+             t.txt LRU cache size 2, 6 req, miss ratio 0.8333, byte miss ratio 0.8333\n"
+        );
+
+        let mut out = Vec::new();
+        write_histogram(&mut out, &Histogram::new(vec![0, 1, 2], 3)).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "# reuse distance, count\n-1 3\n1 1\n2 2\n"
+        );
+    }
+}
@@ -0,0 +1,85 @@
+//! Contains per-symbol lifetime analysis.
+
+use std::collections::BTreeMap;
+
+use crate::trace::Trace;
+
+/// When a symbol is live in a trace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Lifetime {
+    /// The index of the first access to the symbol.
+    pub first: usize,
+    /// The index of the last access to the symbol.
+    pub last: usize,
+    /// The number of accesses after the last one, during which the symbol is dead.
+    pub dead_time: usize,
+}
+
+impl Lifetime {
+    /// The number of accesses from the first access to the symbol until its last.
+    pub const fn span(&self) -> usize {
+        self.last - self.first
+    }
+}
+
+/// Calculate the lifetime of every symbol in a trace.
+pub fn lifetimes(trace: &Trace) -> BTreeMap<u32, Lifetime> {
+    let len = trace.as_slice().len();
+    let mut lifetimes = BTreeMap::new();
+
+    for (i, &curr) in trace.as_slice().iter().enumerate() {
+        let lifetime = lifetimes.entry(curr).or_insert(Lifetime {
+            first: i,
+            last: i,
+            dead_time: 0,
+        });
+        lifetime.last = i;
+        lifetime.dead_time = len - i - 1;
+    }
+
+    lifetimes
+}
+
+/// Calculate the lifetime histogram.
+///
+/// Returns a vector where the ith entry is the number of symbols with span i.
+pub fn lifetime_histogram(trace: &Trace) -> Vec<usize> {
+    let mut freqs = Vec::new();
+    for lifetime in lifetimes(trace).values() {
+        let span = lifetime.span();
+        if freqs.len() <= span {
+            freqs.resize(span + 1, 0);
+        }
+        freqs[span] += 1;
+    }
+    freqs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lifetimes_work() {
+        let trace = Trace::from(vec![0, 1, 0, 2, 0, 1, 3]);
+        let lifetimes = lifetimes(&trace);
+        assert_eq!(
+            lifetimes[&0],
+            Lifetime {
+                first: 0,
+                last: 4,
+                dead_time: 2
+            }
+        );
+        assert_eq!(lifetimes[&1].span(), 4);
+        assert_eq!(lifetimes[&3].span(), 0);
+        assert_eq!(lifetimes[&3].dead_time, 0);
+        assert_eq!(lifetime_histogram(&trace), vec![2, 0, 0, 0, 2]);
+    }
+
+    #[test]
+    fn empty() {
+        assert!(lifetimes(&Trace::from(vec![])).is_empty());
+        assert_eq!(lifetime_histogram(&Trace::from(vec![])), vec![]);
+    }
+}
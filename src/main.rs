@@ -1,21 +1,2183 @@
-mod trace;
+use std::env;
+use std::error::Error;
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::fs;
+use std::io::{self, BufRead, Read, Write};
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::num::{ParseFloatError, ParseIntError};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, SystemTime};
 
-use trace::{Trace, TraceIter};
+use clap::{crate_version, value_parser, Arg, ArgAction, ArgMatches, Command};
+use clap_complete::Shell;
+use clap_mangen::Man;
+use indicatif::{ProgressBar, ProgressStyle};
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Editor, Helper};
+use tracing::info;
+use tracing_subscriber::EnvFilter;
 
-fn compare(t: Trace) {
-    let (stack_distances, infinities) = t.stack_distance_histogram();
-    let frequencies = t.frequency_histogram();
+use stack_distance::capture;
+use stack_distance::classify::MissBreakdown;
+use stack_distance::generate;
+use stack_distance::histogram::{Histogram, ParseHistogramError};
+use stack_distance::http;
+use stack_distance::libcachesim;
+use stack_distance::metrics;
+use stack_distance::mrc::MissRatioCurve;
+use stack_distance::online::OnlineAnalyzer;
+use stack_distance::partial::PartialResult;
+use stack_distance::phase::{self, Divergence};
+use stack_distance::shard;
+use stack_distance::simulate::{self, CacheConfig, MissRatioMatrix, Policy};
+use stack_distance::summary::{self, Summary};
+use stack_distance::table::{Cell, Format, ParseFormatError, Table};
+use stack_distance::timeseries;
+use stack_distance::trace::{self, Addresses, Constraint, Trace, TraceIter};
+use stack_distance::validate::{Anomaly, Validation};
+use stack_distance::window::SlidingWindow;
+use stack_distance::{verify, working_set};
 
-    // an infinity means a new variable, so it should be equal to the number of non-zero elements
-    // of frequencies
-    assert_eq!(infinities, frequencies.iter().filter(|&&n| n != 0).count());
+fn cli() -> Command {
+    let trace = || {
+        Arg::new("trace")
+            .help(
+                "The trace file, of whitespace-separated addresses, in decimal or in hex after 0x",
+            )
+            .value_parser(value_parser!(PathBuf))
+            .required(true)
+    };
+    let merge_into = || {
+        Arg::new("merge-into")
+            .long("merge-into")
+            .help(
+                "Merge the partial result after the one in this file, creating it if it's missing",
+            )
+            .value_name("FILE")
+            .value_parser(value_parser!(PathBuf))
+    };
+
+    Command::new("stack-distance")
+        .about("Analyze the locality of memory access traces")
+        .after_help(
+            "Exit codes: 1 for other errors, 2 for usage errors, 3 for unparseable input, 4 for \
+             I/O errors, and 5 for traces which violate an invariant.",
+        )
+        .version(crate_version!())
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .arg(
+            Arg::new("verbose")
+                .long("verbose")
+                .short('v')
+                .help("Log more diagnostics to stderr, once for info and twice for debug; RUST_LOG also works")
+                .action(ArgAction::Count)
+                .global(true),
+        )
+        .arg(
+            Arg::new("jobs")
+                .long("jobs")
+                .short('j')
+                .help("The number of threads, the number of cores by default")
+                .value_parser(value_parser!(usize))
+                .global(true),
+        )
+        .arg(
+            Arg::new("watch")
+                .long("watch")
+                .help("Run again whenever a trace file changes or grows, until interrupted")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .help("Read default settings from this TOML file, which flags override")
+                .value_name("FILE")
+                .value_parser(value_parser!(PathBuf))
+                .global(true),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .help("The output format, of table, json, csv, npy, and parquet")
+                .value_parser(value_parser!(Format))
+                .global(true),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .short('o')
+                .alias("out")
+                .help("Write the output to this file instead of stdout")
+                .value_name("FILE")
+                .value_parser(value_parser!(PathBuf))
+                .global(true),
+        )
+        .arg(
+            Arg::new("top-k")
+                .long("top-k")
+                .help("Also report the most frequently accessed symbols of each trace, on stderr")
+                .value_name("K")
+                .value_parser(value_parser!(usize))
+                .global(true),
+        )
+        .arg(
+            Arg::new("granularity")
+                .long("granularity")
+                .help("Analyze symbols as byte addresses, in aligned blocks of this size, like 64 or 4k")
+                .value_parser(simulate::parse_size)
+                .conflicts_with("shift")
+                .global(true),
+        )
+        .arg(
+            Arg::new("shift")
+                .long("shift")
+                .help("Drop this many low bits from every symbol")
+                .value_parser(value_parser!(u32))
+                .global(true),
+        )
+        .subcommand(
+            Command::new("analyze")
+                .about("Summarize the stack distance and frequency histograms of a trace")
+                .arg(
+                    Arg::new("histogram")
+                        .long("histogram")
+                        .help("Print the whole stack distance histogram instead")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("sharded")
+                        .long("sharded")
+                        .help("Compute the histogram with symbols sharded across --jobs threads")
+                        .action(ArgAction::SetTrue)
+                        .requires("histogram"),
+                )
+                .arg(merge_into())
+                .arg(trace()),
+        )
+        .subcommand(
+            Command::new("merge")
+                .about("Combine partial results of consecutive pieces of a trace")
+                .long_about(
+                    "Combine partial results of consecutive pieces of a trace, saved by analyze \
+                     --merge-into, and print the histogram of the whole trace.\n\n\
+                     The results are merged in the order given, which should be the order of \
+                     the pieces, and the pieces must number their symbols the same way.",
+                )
+                .arg(merge_into())
+                .arg(
+                    Arg::new("results")
+                        .help("The partial results, in order")
+                        .value_parser(value_parser!(PathBuf))
+                        .action(ArgAction::Append)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("enumerate")
+                .about("Print every canonical trace of a length")
+                .arg(
+                    Arg::new("len")
+                        .long("len")
+                        .help("The length of the traces")
+                        .value_parser(value_parser!(usize))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("max-symbols")
+                        .long("max-symbols")
+                        .help("Only print traces with at most this many distinct symbols")
+                        .value_parser(value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("min-symbols")
+                        .long("min-symbols")
+                        .help("Only print traces with at least this many distinct symbols")
+                        .value_parser(value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("symbols")
+                        .long("symbols")
+                        .help("Only print traces with exactly this many distinct symbols")
+                        .value_parser(value_parser!(usize))
+                        .conflicts_with_all(["max-symbols", "min-symbols"]),
+                )
+                .arg(
+                    Arg::new("histograms")
+                        .long("histograms")
+                        .help("Print each trace's stack distance histogram after it")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("simulate")
+                .about("Simulate caches of each policy and size, printing their miss ratios")
+                .arg(
+                    Arg::new("policies")
+                        .long("policies")
+                        .alias("policy")
+                        .help("The replacement policies, of lru, fifo, arc, and clock")
+                        .value_parser(value_parser!(Policy))
+                        .value_delimiter(',')
+                        .default_value("lru"),
+                )
+                .arg(
+                    Arg::new("sizes")
+                        .long("sizes")
+                        .help("The fully-associative cache sizes, like 1k or 4m")
+                        .value_parser(simulate::parse_size)
+                        .value_delimiter(',')
+                        .required_unless_present("size"),
+                )
+                .arg(
+                    Arg::new("size")
+                        .long("size")
+                        .help("Simulate one cache of this size instead, in bytes with --line")
+                        .value_parser(simulate::parse_size)
+                        .conflicts_with("sizes"),
+                )
+                .arg(
+                    Arg::new("assoc")
+                        .long("assoc")
+                        .help("The associativity of --size, fully associative by default")
+                        .value_parser(value_parser!(usize))
+                        .requires("size"),
+                )
+                .arg(
+                    Arg::new("line")
+                        .long("line")
+                        .help("The line size of --size in bytes, treating symbols as byte addresses")
+                        .value_parser(simulate::parse_size)
+                        .requires("size")
+                        .conflicts_with("granularity")
+                        .conflicts_with("shift"),
+                )
+                .arg(trace()),
+        )
+        .subcommand(
+            Command::new("mrc")
+                .about("Print the miss-ratio curve of a trace over a grid of cache sizes")
+                .arg(
+                    Arg::new("sizes")
+                        .long("sizes")
+                        .help("The sizes, like 1k,4k or 1k..1g/log; every size up to where the LRU curve is flat by default")
+                        .value_parser(simulate::parse_size_grid),
+                )
+                .arg(
+                    Arg::new("policies")
+                        .long("policies")
+                        .alias("policy")
+                        .help("The replacement policies, of lru, fifo, arc, and clock")
+                        .value_parser(value_parser!(Policy))
+                        .value_delimiter(',')
+                        .default_value("lru"),
+                )
+                .arg(trace()),
+        )
+        .subcommand(
+            Command::new("libcachesim")
+                .about("Simulate like libCacheSim's cachesim, printing its summary lines")
+                .arg(trace())
+                .arg(
+                    Arg::new("trace-type")
+                        .help("The trace type, of which only txt is supported")
+                        .value_parser(["txt"])
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("algos")
+                        .help("The algorithms, like lru,fifo,arc,clock")
+                        .value_parser(value_parser!(Policy))
+                        .value_delimiter(',')
+                        .required_unless_present("reuse"),
+                )
+                .arg(
+                    Arg::new("sizes")
+                        .help("The cache sizes, like 1000,4KiB, or fractions of the working set like 0.01,0.1, or 0 to pick them")
+                        .required_unless_present("reuse"),
+                )
+                .arg(
+                    Arg::new("reuse")
+                        .long("reuse")
+                        .help("Print the stack distance histogram instead")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("generate")
+                .about("Generate a synthetic trace")
+                .arg(
+                    Arg::new("pattern")
+                        .long("pattern")
+                        .help("The access pattern")
+                        .value_parser(["uniform", "zipf", "cyclic", "sawtooth"])
+                        .default_value("uniform"),
+                )
+                .arg(
+                    Arg::new("len")
+                        .long("len")
+                        .help("The number of accesses")
+                        .value_parser(value_parser!(usize))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("alphabet")
+                        .long("alphabet")
+                        .help("The number of distinct symbols")
+                        .value_parser(value_parser!(u32))
+                        .default_value("64"),
+                )
+                .arg(
+                    Arg::new("exponent")
+                        .long("exponent")
+                        .help("The skew of the zipf pattern")
+                        .value_parser(value_parser!(f64))
+                        .default_value("1.0"),
+                )
+                .arg(
+                    Arg::new("seed")
+                        .long("seed")
+                        .help("The seed of the random patterns")
+                        .value_parser(value_parser!(u64))
+                        .default_value("0"),
+                ),
+        )
+        .subcommand(
+            Command::new("compare")
+                .about("Compare the locality of two traces, or of histograms saved by analyze")
+                .arg(
+                    Arg::new("report")
+                        .long("report")
+                        .help("The reports, of diff, mrc, and metrics")
+                        .value_parser(["diff", "mrc", "metrics"])
+                        .value_delimiter(',')
+                        .default_values(["diff", "mrc", "metrics"]),
+                )
+                .arg(trace().help("The baseline trace, or a saved histogram"))
+                .arg(
+                    Arg::new("other")
+                        .help("The trace to compare against the baseline, or a saved histogram")
+                        .value_parser(value_parser!(PathBuf))
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("validate")
+                .about("Check that a trace file parses, without analyzing it")
+                .arg(trace()),
+        )
+        .subcommand(
+            Command::new("verify")
+                .about("Check the invariants of the analyses on every canonical trace")
+                .arg(
+                    Arg::new("max-len")
+                        .long("max-len")
+                        .help("The length of the longest traces to check")
+                        .value_parser(value_parser!(usize))
+                        .default_value("4"),
+                )
+        )
+        .subcommand(
+            Command::new("classify")
+                .about("Classify the misses of a cache as compulsory, capacity, or conflict")
+                .arg(
+                    Arg::new("size")
+                        .long("size")
+                        .help("The cache size, like 1k or 4m")
+                        .value_parser(simulate::parse_size)
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("assoc")
+                        .long("assoc")
+                        .help("The associativity, fully associative by default")
+                        .value_parser(value_parser!(usize)),
+                )
+                .arg(trace()),
+        )
+        .subcommand(
+            Command::new("working-set")
+                .about("Print the working set size after each access")
+                .arg(
+                    Arg::new("window")
+                        .long("window")
+                        .help("The number of accesses in the working set window")
+                        .value_parser(value_parser!(usize))
+                        .required(true),
+                )
+                .arg(trace()),
+        )
+        .subcommand(
+            Command::new("windows")
+                .about("Analyze sliding windows of a trace, to find where its locality drifts")
+                .arg(
+                    Arg::new("window")
+                        .long("window")
+                        .help("The number of accesses in each window, like 1m")
+                        .value_parser(simulate::parse_size)
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("stride")
+                        .long("stride")
+                        .help("The number of accesses between window starts, the window by default")
+                        .value_parser(simulate::parse_size),
+                )
+                .arg(
+                    Arg::new("report")
+                        .long("report")
+                        .help("What to report of each window, or the phases between them")
+                        .value_parser(["histograms", "mrcs", "phases"])
+                        .default_value("histograms"),
+                )
+                .arg(
+                    Arg::new("divergence")
+                        .long("divergence")
+                        .help("How to compare successive windows when finding phases")
+                        .value_parser(["kl", "emd", "chi-square"])
+                        .default_value("kl"),
+                )
+                .arg(
+                    Arg::new("threshold")
+                        .long("threshold")
+                        .help("The divergence between windows which starts a new phase")
+                        .value_parser(value_parser!(f64))
+                        .default_value("0.5"),
+                )
+                .arg(trace()),
+        )
+        .subcommand(
+            Command::new("timeseries")
+                .about("Print locality per access, or per window")
+                .arg(
+                    Arg::new("window")
+                        .long("window")
+                        .help("The number of accesses in each window")
+                        .value_parser(value_parser!(usize)),
+                )
+                .arg(trace()),
+        )
+        .subcommand(
+            Command::new("stream")
+                .about("Analyze accesses from stdin, one per line, as they arrive")
+                .long_about(
+                    "Analyze accesses from stdin, one per line, as they arrive.\n\n\
+                     Besides accesses, a line may be FLUSH, to print the histogram so far on \
+                     one line, or RESET, to forget every access. The histogram is printed once \
+                     more at the end of the input.",
+                ),
+        )
+        .subcommand(
+            Command::new("live")
+                .about("Print the MRC of the most recent accesses from stdin as they arrive")
+                .long_about(
+                    "Print the miss ratio curve of the most recent accesses from stdin, one per \
+                     line, as they arrive.\n\n\
+                     Only the last --window accesses count, so old history doesn't dominate. \
+                     The curve is printed on one line, like `0:1 1:0.5 2:0.25`, every --every \
+                     accesses and once more at the end of the input.",
+                )
+                .arg(
+                    Arg::new("window")
+                        .long("window")
+                        .help("The number of most recent accesses to analyze, like 1m")
+                        .value_parser(simulate::parse_size)
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("every")
+                        .long("every")
+                        .help("The number of accesses between printing curves, the window by default")
+                        .value_parser(simulate::parse_size),
+                ),
+        )
+        .subcommand(
+            Command::new("capture")
+                .about("Sample the data addresses of a process with perf and print its MRC")
+                .long_about(
+                    "Sample the data addresses of a process with perf and print its miss ratio \
+                     curve.\n\n\
+                     The command runs under `perf mem record`, whose samples stream straight \
+                     into the analysis, or --input decodes a perf.data recorded earlier. The \
+                     global --shift or --granularity coarsens addresses, e.g. to cache lines.",
+                )
+                .arg(
+                    Arg::new("input")
+                        .long("input")
+                        .short('i')
+                        .help("A perf.data file to decode instead of running a command")
+                        .value_parser(value_parser!(PathBuf))
+                        .conflicts_with("command"),
+                )
+                .arg(
+                    Arg::new("perf")
+                        .long("perf")
+                        .help("The perf binary")
+                        .value_parser(value_parser!(OsString))
+                        .default_value("perf"),
+                )
+                .arg(
+                    Arg::new("command")
+                        .help("The command to run, after --")
+                        .value_parser(value_parser!(OsString))
+                        .action(ArgAction::Append)
+                        .required_unless_present("input")
+                        .last(true),
+                ),
+        )
+        .subcommand(
+            Command::new("monitor")
+                .about("Sample a running process with perf events, printing its histogram")
+                .long_about(
+                    "Sample a running process with perf events, printing its histogram.\n\n\
+                     The histogram so far is printed on one line every --interval, and once more \
+                     when the process exits or --duration passes. This needs the `perf-event` \
+                     feature on Linux.",
+                )
+                .arg(
+                    Arg::new("pid")
+                        .long("pid")
+                        .short('p')
+                        .help("The process to sample; a multi-threaded one is sampled on this thread")
+                        .value_parser(value_parser!(i32))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("event")
+                        .long("event")
+                        .short('e')
+                        .help("page-faults, or a raw processor event with data addresses, like 0x1cd")
+                        .default_value("page-faults"),
+                )
+                .arg(
+                    Arg::new("period")
+                        .long("period")
+                        .help("Sample a raw event once every this many occurrences")
+                        .value_parser(value_parser!(u64))
+                        .default_value("1000"),
+                )
+                .arg(
+                    Arg::new("interval")
+                        .long("interval")
+                        .help("Seconds between printing histograms")
+                        .value_parser(value_parser!(f64))
+                        .default_value("1"),
+                )
+                .arg(
+                    Arg::new("duration")
+                        .long("duration")
+                        .help("Seconds to sample for, instead of until the process exits")
+                        .value_parser(value_parser!(f64)),
+                ),
+        )
+        .subcommand(
+            Command::new("ingest")
+                .about("Analyze accesses fed to a socket by remote tracers, printing the histogram")
+                .long_about(
+                    "Analyze accesses fed to a socket by remote tracers, printing the histogram.\n\n\
+                     Each access is a little-endian u32 length, then an address of that many \
+                     little-endian bytes, up to eight. Every connection feeds one analysis, whose \
+                     histogram is printed on one line every --interval. This needs the `tokio` \
+                     feature.",
+                )
+                .arg(
+                    Arg::new("tcp")
+                        .long("tcp")
+                        .help("Listen on this TCP address, like 0.0.0.0:7070")
+                        .value_parser(value_parser!(SocketAddr))
+                        .required_unless_present("unix")
+                        .conflicts_with("unix"),
+                )
+                .arg(
+                    Arg::new("unix")
+                        .long("unix")
+                        .help("Listen on a Unix socket at this path")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("interval")
+                        .long("interval")
+                        .help("Seconds between printing histograms")
+                        .value_parser(value_parser!(f64))
+                        .default_value("1"),
+                ),
+        )
+        .subcommand(
+            Command::new("serve")
+                .about("Serve the analysis of accesses shipped from remote tracers")
+                .arg(
+                    Arg::new("grpc")
+                        .long("grpc")
+                        .help("Serve gRPC on this address, like 0.0.0.0:50051")
+                        .value_parser(value_parser!(SocketAddr))
+                        .required_unless_present_any(["http", "flight"])
+                        .conflicts_with_all(["http", "flight"]),
+                )
+                .arg(
+                    Arg::new("http")
+                        .long("http")
+                        .help("Serve the HTTP API on this address, like 0.0.0.0:8080")
+                        .value_parser(value_parser!(SocketAddr))
+                        .conflicts_with("flight"),
+                )
+                .arg(
+                    Arg::new("flight")
+                        .long("flight")
+                        .help("Serve the results of a trace over Arrow Flight on this address")
+                        .value_parser(value_parser!(SocketAddr))
+                        .requires("trace"),
+                )
+                .arg(
+                    Arg::new("trace")
+                        .long("trace")
+                        .help("The trace whose results to serve over Arrow Flight")
+                        .value_parser(value_parser!(PathBuf))
+                        .requires("flight"),
+                ),
+        )
+        .subcommand(
+            Command::new("repl")
+                .about("Load traces and query them interactively")
+                .arg(
+                    Arg::new("trace")
+                        .help("A trace file to load first")
+                        .value_parser(value_parser!(PathBuf)),
+                ),
+        )
+        .subcommand(
+            Command::new("completions")
+                .about("Print a shell completion script")
+                .arg(
+                    Arg::new("shell")
+                        .help("The shell, of bash, elvish, fish, powershell, and zsh")
+                        .value_parser(value_parser!(Shell))
+                        .required(true),
+                ),
+        )
+        .subcommand(Command::new("manpage").about("Print the man page, in roff"))
+}
+
+// Find the path given to --config, which has to be read before the arguments are parsed.
+fn config_path(args: &[OsString]) -> Option<PathBuf> {
+    let mut args = args.iter().map(|arg| arg.to_str());
+    while let Some(arg) = args.next() {
+        match arg {
+            Some("--config") => return args.next().flatten().map(PathBuf::from),
+            Some(arg) if arg.starts_with("--config=") => {
+                return Some(PathBuf::from(&arg["--config=".len()..]))
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// Make the settings of a config file the defaults of their flags, so the command line overrides
+// them. Top-level keys set global flags, and a table named after a subcommand sets its flags.
+fn configure(mut command: Command, config: &toml::Table) -> Result<Command, Box<dyn Error>> {
+    for (key, value) in config {
+        if let toml::Value::Table(table) = value {
+            let subcommand = command
+                .find_subcommand(key)
+                .ok_or_else(|| format!("unknown subcommand `{}` in the config", key))?
+                .clone();
+            let subcommand = configure(subcommand, table)?;
+            command = command.mut_subcommand(key.as_str(), |_| subcommand);
+            continue;
+        }
+
+        if key == "config"
+            || !command
+                .get_arguments()
+                .any(|arg| arg.get_id() == key.as_str() && !arg.is_positional())
+        {
+            return Err(format!("unknown setting `{}` in the config", key).into());
+        }
+        let setting = |value: &toml::Value| match value {
+            toml::Value::String(s) => Ok(s.clone()),
+            toml::Value::Integer(n) => Ok(n.to_string()),
+            toml::Value::Float(x) => Ok(x.to_string()),
+            toml::Value::Boolean(b) => Ok(b.to_string()),
+            _ => Err(format!("unsupported value for `{}` in the config", key)),
+        };
+        let values = match value {
+            toml::Value::Array(values) => {
+                values.iter().map(setting).collect::<Result<Vec<_>, _>>()?
+            }
+            value => vec![setting(value)?],
+        };
+        // a setting satisfies the requirement for its flag, which clap only counts if it's given
+        command = command.mut_arg(key.as_str(), |arg| {
+            arg.required(false)
+                .required_unless_present(None::<&str>)
+                .default_values(values)
+        });
+    }
+    Ok(command)
+}
+
+// Read the trace file given by an argument, at the granularity of --granularity or --shift.
+fn read_trace(matches: &ArgMatches, id: &str) -> Result<Trace, Box<dyn Error>> {
+    Ok(read_addressed_trace(matches, id)?.0)
+}
+
+// Read the trace file given by an argument like `read_trace`, with the addresses its symbols
+// stand for.
+fn read_addressed_trace(
+    matches: &ArgMatches,
+    id: &str,
+) -> Result<(Trace, Addresses), Box<dyn Error>> {
+    let path = matches.get_one::<PathBuf>(id).expect("required");
+    read_trace_from(matches, path, shift(matches)?)
+}
+
+// Read a trace from a path, coarsening its addresses by `shift` bits and applying the other
+// global flags.
+fn read_trace_from(
+    matches: &ArgMatches,
+    path: &Path,
+    shift: u32,
+) -> Result<(Trace, Addresses), Box<dyn Error>> {
+    let file = fs::File::open(path)?;
+    let bar = progress_bar(
+        file.metadata()?.len(),
+        "{bytes}/{total_bytes}, {bytes_per_sec}",
+    );
+    bar.set_message(format!("reading {}", path.display()));
+    let (trace, addresses) =
+        Trace::parse_addresses(&io::read_to_string(bar.wrap_read(file))?, shift)?;
+    bar.finish_and_clear();
+
+    if let Some(&k) = matches.get_one::<usize>("top-k") {
+        report_hot_symbols(&path.display().to_string(), &trace, &addresses, k)?;
+    }
+    Ok((trace, addresses))
+}
+
+// Fail unless a trace's symbols are its addresses, since a cache with `sets` sets maps addresses
+// to sets.
+fn require_addresses(addresses: &Addresses, sets: usize) -> Result<(), Box<dyn Error>> {
+    if sets > 1 && !addresses.is_identity() {
+        return Err(
+            "a set-associative cache needs addresses which fit in 32 bits once \
+                    coarsened, so coarsen them more"
+                .into(),
+        );
+    }
+    Ok(())
+}
+
+// The bits to drop from each symbol, from --granularity or --shift.
+fn shift(matches: &ArgMatches) -> Result<u32, Box<dyn Error>> {
+    let shift = match matches.get_one::<usize>("granularity") {
+        Some(granularity) if !granularity.is_power_of_two() => {
+            return Err("--granularity must be a power of two".into())
+        }
+        Some(granularity) => granularity.trailing_zeros(),
+        None => matches.get_one::<u32>("shift").copied().unwrap_or(0),
+    };
+    if shift > 0 {
+        info!(shift, "coarsening symbols");
+    }
+    Ok(shift)
+}
+
+// The number of threads to run on, from --jobs or the number of cores.
+fn jobs(matches: &ArgMatches) -> Result<usize, Box<dyn Error>> {
+    match matches.get_one::<usize>("jobs") {
+        Some(0) => Err("--jobs must be positive".into()),
+        Some(&jobs) => Ok(jobs),
+        None => Ok(thread::available_parallelism().map_or(1, NonZeroUsize::get)),
+    }
+}
+
+// Create a progress bar on stderr, which hides itself unless stderr is a terminal.
+fn progress_bar(len: u64, counts: &str) -> ProgressBar {
+    let template = format!(
+        "{{msg}} [{{elapsed_precise}}] {{wide_bar}} {} (eta {{eta}})",
+        counts
+    );
+    ProgressBar::new(len).with_style(
+        ProgressStyle::with_template(&template)
+            .expect("the template is valid")
+            .progress_chars("=> "),
+    )
+}
+
+// Write the hottest symbols of a trace to stderr, out of the way of the results.
+fn report_hot_symbols(
+    name: &str,
+    trace: &Trace,
+    addresses: &Addresses,
+    k: usize,
+) -> io::Result<()> {
+    let mut err = io::stderr().lock();
+    writeln!(err, "hottest symbols of {}:", name)?;
+    hot_symbols(trace, addresses, k).write(Format::Table, &mut err)
+}
+
+// Tabulate the `k` hottest symbols of a trace by address.
+fn hot_symbols(trace: &Trace, addresses: &Addresses, k: usize) -> Table {
+    let mut table = Table::new(["symbol", "count", "mean_reuse_distance"]);
+    for hot in summary::top_k(trace, k) {
+        table.push(vec![
+            addresses.address(hot.symbol).into(),
+            hot.count.into(),
+            hot.mean_reuse_distance
+                .map_or_else(|| Cell::from("inf"), Cell::from),
+        ]);
+    }
+    table
+}
+
+// Open the destination of --output, or stdout.
+fn output(matches: &ArgMatches) -> io::Result<Box<dyn Write>> {
+    Ok(match matches.get_one::<PathBuf>("output") {
+        Some(path) => Box::new(io::BufWriter::new(fs::File::create(path)?)),
+        None => Box::new(io::stdout().lock()),
+    })
+}
+
+// Write a table in the format of --format, or the subcommand's default.
+fn emit(matches: &ArgMatches, table: &Table, default: Format) -> Result<(), Box<dyn Error>> {
+    let format = matches
+        .get_one::<Format>("format")
+        .copied()
+        .unwrap_or(default);
+    let mut out = output(matches)?;
+    table.write(format, &mut out)?;
+    out.flush()?;
+    Ok(())
+}
+
+// Write something with its own layout, unless --format asks for a table.
+fn emit_display<T: Display>(
+    matches: &ArgMatches,
+    value: &T,
+    table: impl FnOnce() -> Table,
+) -> Result<(), Box<dyn Error>> {
+    if matches.contains_id("format") {
+        return emit(matches, &table(), Format::Table);
+    }
+    let mut out = output(matches)?;
+    write!(out, "{}", value)?;
+    out.flush()?;
+    Ok(())
+}
+
+fn analyze(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let (trace, addresses) = read_addressed_trace(matches, "trace")?;
+    if let Some(path) = matches.get_one::<PathBuf>("merge-into") {
+        merge_into(path, &PartialResult::with_addresses(&trace, &addresses))?;
+    }
+    if matches.get_flag("histogram") {
+        let histogram = if matches.get_flag("sharded") {
+            shard::histogram(&trace, jobs(matches)?)
+        } else {
+            trace.stack_distance_histogram()
+        };
+        return emit(matches, &Table::from(&histogram), Format::Csv);
+    }
+
+    let summary = Summary::with_addresses(&trace, &addresses);
+    emit_display(matches, &summary, || Table::from(&summary))
+}
+
+// Merge a partial result after the one saved at a path, or save it if there's none.
+fn merge_into(path: &Path, partial: &PartialResult) -> io::Result<()> {
+    let merged = match fs::File::open(path) {
+        Ok(file) => PartialResult::read(io::BufReader::new(file))?.merge(partial),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => partial.clone(),
+        Err(e) => return Err(e),
+    };
+    let mut file = io::BufWriter::new(fs::File::create(path)?);
+    merged.write(&mut file)?;
+    file.flush()
+}
+
+fn merge(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let mut merged = PartialResult::default();
+    for path in matches.get_many::<PathBuf>("results").expect("required") {
+        let file = fs::File::open(path)?;
+        merged = merged.merge(&PartialResult::read(io::BufReader::new(file))?);
+    }
+    if let Some(path) = matches.get_one::<PathBuf>("merge-into") {
+        merge_into(path, &merged)?;
+    }
+    emit(matches, &Table::from(&merged.histogram()), Format::Csv)
+}
+
+// Render a histogram on one line, like `0:2 1:1 inf:3`.
+fn histogram_line(histogram: &Histogram) -> String {
+    histogram
+        .frequencies()
+        .iter()
+        .enumerate()
+        .filter(|&(_, &count)| count > 0)
+        .map(|(distance, count)| format!("{}:{}", distance, count))
+        .chain([format!("inf:{}", histogram.infinities())])
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Render a miss ratio curve on one line, like `0:1 1:0.5 2:0.25`.
+fn mrc_line(mrc: &MissRatioCurve) -> String {
+    mrc.ratios()
+        .iter()
+        .enumerate()
+        .map(|(size, ratio)| format!("{}:{}", size, ratio))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn enumerate(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let len = *matches.get_one::<usize>("len").expect("required");
+    let histograms = matches.get_flag("histograms");
+    let jobs = jobs(matches)?;
+
+    let mut constraints = Vec::new();
+    if let Some(&symbols) = matches.get_one::<usize>("symbols") {
+        constraints.push(Constraint::MinInfinities(symbols));
+        constraints.push(Constraint::MaxInfinities(symbols));
+    }
+    if let Some(&max) = matches.get_one::<usize>("max-symbols") {
+        constraints.push(Constraint::MaxInfinities(max));
+    }
+    if let Some(&min) = matches.get_one::<usize>("min-symbols") {
+        constraints.push(Constraint::MinInfinities(min));
+    }
+
+    let total = TraceIter::count_filtered(len, &constraints);
+    let bar = progress_bar(
+        u64::try_from(&total).unwrap_or(u64::MAX),
+        "{human_pos}/{human_len}, {per_sec}",
+    );
+    bar.set_message("enumerating");
+
+    // render a batch of traces at a time in parallel, then write them in order, so the output
+    // starts at once however many traces there are
+    let mut enumeration = TraceIter::filtered(len, &constraints);
+    let batches = std::iter::from_fn(move || {
+        let batch: Vec<_> = enumeration.by_ref().take(jobs * 1024).collect();
+        (!batch.is_empty()).then_some(batch)
+    });
+    let mut traces = batches.flat_map(|batch| {
+        thread::scope(|scope| {
+            let handles: Vec<_> = batch
+                .chunks(1024)
+                .map(|chunk| {
+                    scope.spawn(|| {
+                        let rendered: Vec<_> = chunk
+                            .iter()
+                            .map(|trace| {
+                                let histogram = histograms
+                                    .then(|| histogram_line(&trace.stack_distance_histogram()));
+                                (trace.to_string(), histogram)
+                            })
+                            .collect();
+                        bar.inc(rendered.len() as u64);
+                        rendered
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("the thread doesn't panic"))
+                .collect::<Vec<_>>()
+        })
+    });
+
+    if matches.contains_id("format") {
+        let mut table = if histograms {
+            Table::new(["trace", "histogram"])
+        } else {
+            Table::new(["trace"])
+        };
+        for (trace, histogram) in traces {
+            table.push(
+                std::iter::once(trace.into())
+                    .chain(histogram.map(Cell::from))
+                    .collect(),
+            );
+        }
+        bar.finish_and_clear();
+        return emit(matches, &table, Format::Csv);
+    }
+    let mut out = output(matches)?;
+    traces.try_for_each(|(trace, histogram)| match histogram {
+        Some(histogram) => writeln!(out, "{}  {}", trace, histogram),
+        None => writeln!(out, "{}", trace),
+    })?;
+    out.flush()?;
+    bar.finish_and_clear();
+    Ok(())
+}
+
+fn simulate(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let policies: Vec<Policy> = matches
+        .get_many::<Policy>("policies")
+        .expect("defaulted")
+        .copied()
+        .collect();
+    if let Some(&size) = matches.get_one::<usize>("size") {
+        return simulate_one(matches, &policies, size);
+    }
+    let sizes: Vec<usize> = matches
+        .get_many::<usize>("sizes")
+        .expect("required")
+        .copied()
+        .collect();
+    let trace = read_trace(matches, "trace")?;
+
+    let matrix = MissRatioMatrix::with_threads(&trace, &policies, &sizes, jobs(matches)?);
+    emit(matches, &Table::from(&matrix), Format::Csv)
+}
+
+// Simulate one cache of each policy, reporting its hits, misses, and evictions.
+fn simulate_one(
+    matches: &ArgMatches,
+    policies: &[Policy],
+    size: usize,
+) -> Result<(), Box<dyn Error>> {
+    let line = matches.get_one::<usize>("line").copied();
+    let (trace, addresses) = match line {
+        Some(line) if !line.is_power_of_two() => return Err("--line must be a power of two".into()),
+        Some(line) => {
+            let path = matches.get_one::<PathBuf>("trace").expect("required");
+            read_trace_from(matches, path, line.trailing_zeros())?
+        }
+        None => read_addressed_trace(matches, "trace")?,
+    };
+    let entries = size / line.unwrap_or(1);
+    let associativity = matches.get_one::<usize>("assoc").copied();
+    let config = CacheConfig::new(entries, associativity.unwrap_or(entries))
+        .ok_or("the associativity must be non-zero and divide the number of lines")?;
+    require_addresses(&addresses, config.sets())?;
+
+    let mut table = Table::new([
+        "policy",
+        "size",
+        "assoc",
+        "line",
+        "accesses",
+        "hits",
+        "misses",
+        "evictions",
+        "miss_ratio",
+    ]);
+    for &policy in policies {
+        let stats = simulate::simulate(&trace, policy, config);
+        table.push(vec![
+            policy.to_string().into(),
+            size.into(),
+            config.associativity().into(),
+            line.unwrap_or(1).into(),
+            stats.accesses().into(),
+            stats.hits.into(),
+            stats.misses.into(),
+            stats.evictions.into(),
+            stats.miss_ratio().into(),
+        ]);
+    }
+    emit(matches, &table, Format::Table)
+}
+
+// Tabulate the miss ratio of each policy at each size, with a row per size.
+fn mrc(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let policies: Vec<Policy> = matches
+        .get_many::<Policy>("policies")
+        .expect("defaulted")
+        .copied()
+        .collect();
+    let trace = read_trace(matches, "trace")?;
+    let sizes = match matches.get_one::<Vec<usize>>("sizes") {
+        Some(sizes) => sizes.clone(),
+        None => (0..MissRatioCurve::from(&trace).ratios().len()).collect(),
+    };
+
+    let matrix = MissRatioMatrix::with_threads(&trace, &policies, &sizes, jobs(matches)?);
+    let mut table = Table::new(
+        ["size".to_string()]
+            .into_iter()
+            .chain(policies.iter().map(ToString::to_string)),
+    );
+    let rows: Vec<_> = policies
+        .iter()
+        .map(|&policy| matrix.ratios(policy).expect("simulated"))
+        .collect();
+    for (i, &size) in sizes.iter().enumerate() {
+        let ratios = rows.iter().map(|row| row[i].into());
+        table.push(std::iter::once(size.into()).chain(ratios).collect());
+    }
+    emit(matches, &table, Format::Csv)
+}
+
+// Print results in libCacheSim's layout, so they can stand in for its own.
+fn libcachesim(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let path = matches.get_one::<PathBuf>("trace").expect("required");
+    let trace = read_trace(matches, "trace")?;
+    let histogram = trace.stack_distance_histogram();
+    let mut out = output(matches)?;
+
+    if matches.get_flag("reuse") {
+        libcachesim::write_histogram(&mut out, &histogram)?;
+    } else {
+        let policies: Vec<Policy> = matches
+            .get_many::<Policy>("algos")
+            .expect("required")
+            .copied()
+            .collect();
+        let sizes = libcachesim::parse_sizes(
+            matches.get_one::<String>("sizes").expect("required"),
+            histogram.infinities(),
+        )?;
+        let matrix = MissRatioMatrix::with_threads(&trace, &policies, &sizes, jobs(matches)?);
+        let name = path
+            .file_name()
+            .unwrap_or(path.as_os_str())
+            .to_string_lossy();
+        for &policy in &policies {
+            libcachesim::write_results(
+                &mut out,
+                &name,
+                policy,
+                trace.len(),
+                &sizes,
+                matrix.ratios(policy).expect("simulated"),
+            )?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn generate(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let len = *matches.get_one::<usize>("len").expect("required");
+    let alphabet = *matches.get_one::<u32>("alphabet").expect("defaulted");
+    let seed = *matches.get_one::<u64>("seed").expect("defaulted");
+    if alphabet == 0 {
+        return Err("--alphabet must be positive".into());
+    }
+
+    let trace = match matches.get_one::<String>("pattern").map(String::as_str) {
+        Some("zipf") => generate::zipf(
+            len,
+            alphabet,
+            *matches.get_one::<f64>("exponent").expect("defaulted"),
+            seed,
+        ),
+        // a cyclic sweep for a cache of one fewer symbol than the alphabet
+        Some("cyclic") => generate::cyclic(len, alphabet.saturating_sub(1)),
+        Some("sawtooth") => generate::sawtooth(len, alphabet),
+        _ => generate::uniform(len, alphabet, seed),
+    };
+
+    // by default, write a trace file that the other subcommands can read back
+    if matches.contains_id("format") {
+        let mut table = Table::new(["symbol"]);
+        for &access in trace.as_slice() {
+            table.push(vec![access.into()]);
+        }
+        return emit(matches, &table, Format::Csv);
+    }
+    let mut out = output(matches)?;
+    for access in trace.as_slice() {
+        writeln!(out, "{}", access)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+// Read the histogram of a trace file, or a histogram saved as csv by analyze --histogram.
+fn read_histogram(matches: &ArgMatches, id: &str) -> Result<Histogram, Box<dyn Error>> {
+    let path = matches.get_one::<PathBuf>(id).expect("required");
+    // saved histograms start with a header, which no trace does
+    let mut start = [0; 9];
+    let read = fs::File::open(path)?.read(&mut start)?;
+    if start[..read] == *b"distance," {
+        Ok(fs::read_to_string(path)?.parse()?)
+    } else {
+        Ok(read_trace(matches, id)?.stack_distance_histogram())
+    }
+}
+
+// Tabulate the differences between two histograms, b minus a.
+#[allow(clippy::cast_precision_loss)]
+fn diff_table(a: &Histogram, b: &Histogram) -> Table {
+    let mut table = Table::new(["distance", "a", "b", "diff"]);
+    let row = |distance: Cell, a: usize, b: usize| {
+        vec![distance, a.into(), b.into(), (b as f64 - a as f64).into()]
+    };
+    let len = a.frequencies().len().max(b.frequencies().len());
+    for distance in 0..len {
+        let count = |h: &Histogram| h.frequencies().get(distance).copied().unwrap_or(0);
+        table.push(row(distance.into(), count(a), count(b)));
+    }
+    table.push(row("inf".into(), a.infinities(), b.infinities()));
+    table
+}
+
+// Tabulate the LRU miss-ratio curves of two histograms, up to where both are flat.
+fn mrc_table(a: &Histogram, b: &Histogram) -> Table {
+    let (a, b) = (
+        MissRatioCurve::from_histogram(a),
+        MissRatioCurve::from_histogram(b),
+    );
+    let mut table = Table::new(["size", "a", "b", "diff"]);
+    for size in 0..a.ratios().len().max(b.ratios().len()) {
+        let (a, b) = (a.miss_ratio(size), b.miss_ratio(size));
+        table.push(vec![size.into(), a.into(), b.into(), (b - a).into()]);
+    }
+    table
+}
+
+fn metrics_table(a: &Histogram, b: &Histogram) -> Table {
+    let mut table = Table::new(["metric", "value"]);
+    for (name, value) in [
+        (
+            "earth_movers_distance",
+            metrics::earth_movers_distance(a, b),
+        ),
+        ("kl_divergence", metrics::kl_divergence(a, b)),
+        ("chi_square_distance", metrics::chi_square_distance(a, b)),
+        ("locality_score_a", a.locality_score()),
+        ("locality_score_b", b.locality_score()),
+        ("locality_regression", b.locality_regression(a)),
+    ] {
+        table.push(vec![name.into(), value.into()]);
+    }
+    table
+}
+
+// Compare two traces or histograms, with the first as the baseline.
+fn compare(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let a = read_histogram(matches, "trace")?;
+    let b = read_histogram(matches, "other")?;
+
+    let tables: Vec<_> = matches
+        .get_many::<String>("report")
+        .expect("defaulted")
+        .map(|report| match report.as_str() {
+            "mrc" => mrc_table(&a, &b),
+            "metrics" => metrics_table(&a, &b),
+            _ => diff_table(&a, &b),
+        })
+        .collect();
+
+    let format = matches
+        .get_one::<Format>("format")
+        .copied()
+        .unwrap_or(Format::Csv);
+    if tables.len() > 1 && !matches!(format, Format::Table | Format::Csv) {
+        return Err("--format only holds one report, so pick one with --report".into());
+    }
+    let mut out = output(matches)?;
+    for (i, table) in tables.iter().enumerate() {
+        if i > 0 {
+            writeln!(out)?;
+        }
+        table.write(format, &mut out)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+// Report what a trace file holds and its anomalies, failing if there are any.
+fn validate(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let path = matches.get_one::<PathBuf>("trace").expect("required");
+    let file = fs::File::open(path)?;
+    let bar = progress_bar(
+        file.metadata()?.len(),
+        "{bytes}/{total_bytes}, {bytes_per_sec}",
+    );
+    bar.set_message(format!("validating {}", path.display()));
+    let validation = Validation::new(io::BufReader::new(bar.wrap_read(file)), shift(matches)?)?;
+    bar.finish_and_clear();
+
+    emit_display(matches, &validation, || {
+        let mut table = Table::new(["metric", "value"]);
+        table.push(vec!["records".into(), validation.records.into()]);
+        table.push(vec!["symbols".into(), validation.symbols.into()]);
+        if let Some((lo, hi)) = validation.range {
+            table.push(vec!["min_address".into(), lo.into()]);
+            table.push(vec!["max_address".into(), hi.into()]);
+        }
+        table.push(vec!["anomalies".into(), validation.anomalies.into()]);
+        table.push(vec![
+            "estimated_bytes".into(),
+            validation.estimated_bytes().into(),
+        ]);
+        table
+    })?;
+
+    match validation.examples.first() {
+        Some(first) => Err(InvalidTrace {
+            anomalies: validation.anomalies,
+            first: first.clone(),
+        }
+        .into()),
+        None => Ok(()),
+    }
+}
+
+// Check the invariants of every canonical trace up to the maximum length, on parallel threads.
+fn verify(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let max_len = *matches.get_one::<usize>("max-len").expect("defaulted");
+    let jobs = jobs(matches)?;
+
+    let failures = Mutex::new(Vec::new());
+    let mut checked = 0;
+    for len in 1..=max_len {
+        let total = TraceIter::count_for(len);
+        let bar = progress_bar(
+            u64::try_from(&total).unwrap_or(u64::MAX),
+            "{human_pos}/{human_len}, {per_sec}",
+        );
+        bar.set_message(format!("length {}", len));
+        info!(len, %total, "verifying traces");
+        // many more parts than threads, handed out as threads finish, keeps them all busy
+        let parts = TraceIter::partition(len, u32::MAX, len.min(6));
+        let next_part = AtomicUsize::new(0);
+        thread::scope(|scope| {
+            for _ in 0..jobs {
+                scope.spawn(|| {
+                    while let Some(part) = parts.get(next_part.fetch_add(1, Ordering::Relaxed)) {
+                        for trace in part.clone() {
+                            let report = verify::check_invariants(&trace);
+                            if !report.is_ok() {
+                                failures.lock().unwrap().push((trace, report));
+                            }
+                            bar.inc(1);
+                        }
+                    }
+                });
+            }
+        });
+        bar.finish();
+        checked += bar.position();
+    }
+
+    let failures = failures.into_inner()?;
+    if matches.contains_id("format") {
+        let mut table = Table::new([
+            "trace",
+            "infinities_match_symbols",
+            "histogram_sums_to_length",
+            "distances_bounded",
+            "footprint_matches_symbols",
+        ]);
+        let ok = |passed: bool| Cell::from(if passed { "ok" } else { "FAILED" });
+        for (trace, report) in &failures {
+            table.push(vec![
+                trace.to_string().into(),
+                ok(report.infinities_match_symbols),
+                ok(report.histogram_sums_to_length),
+                ok(report.distances_bounded),
+                ok(report.footprint_matches_symbols),
+            ]);
+        }
+        emit(matches, &table, Format::Table)?;
+    } else {
+        let mut out = output(matches)?;
+        for (trace, report) in &failures {
+            writeln!(out, "{}:\n{}", trace, report)?;
+        }
+        if failures.is_empty() {
+            writeln!(out, "all {} traces ok", checked)?;
+        }
+        out.flush()?;
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(InvariantViolation {
+            failed: failures.len(),
+            checked,
+        }
+        .into())
+    }
+}
+
+// Report compulsory, capacity, and conflict misses.
+fn classify(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let size = *matches.get_one::<usize>("size").expect("required");
+    let associativity = matches.get_one::<usize>("assoc").copied();
+    let config = CacheConfig::new(size, associativity.unwrap_or(size))
+        .ok_or("the associativity must be non-zero and divide the size")?;
+    let (trace, addresses) = read_addressed_trace(matches, "trace")?;
+    require_addresses(&addresses, config.sets())?;
+
+    let breakdown = MissBreakdown::new(&trace, config);
+    emit_display(matches, &breakdown, || {
+        let mut table = Table::new(["outcome", "count"]);
+        for (name, count) in [
+            ("hits", breakdown.hits),
+            ("compulsory", breakdown.compulsory),
+            ("capacity", breakdown.capacity),
+            ("conflict", breakdown.conflict),
+        ] {
+            table.push(vec![name.into(), count.into()]);
+        }
+        table
+    })
 }
 
-fn main() {
-    const TRACE_SIZE: usize = 4;
+fn working_set(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let window = *matches.get_one::<usize>("window").expect("required");
+    let trace = read_trace(matches, "trace")?;
+
+    let mut table = Table::new(["time", "size"]);
+    for (time, size) in working_set::working_set_sizes(&trace, window)
+        .into_iter()
+        .enumerate()
+    {
+        table.push(vec![time.into(), size.into()]);
+    }
+    emit(matches, &table, Format::Csv)
+}
+
+// Report the histogram or miss-ratio curve of each window, or the phases between them.
+fn windows(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let window = *matches.get_one::<usize>("window").expect("required");
+    let stride = matches
+        .get_one::<usize>("stride")
+        .copied()
+        .unwrap_or(window);
+    if window == 0 || stride == 0 {
+        return Err("--window and --stride must be positive".into());
+    }
+    let trace = read_trace(matches, "trace")?;
+
+    let table = match matches.get_one::<String>("report").map(String::as_str) {
+        Some("phases") => {
+            let divergence = match matches.get_one::<String>("divergence").map(String::as_str) {
+                Some("emd") => Divergence::EarthMovers,
+                Some("chi-square") => Divergence::ChiSquare,
+                _ => Divergence::KullbackLeibler,
+            };
+            let threshold = *matches.get_one::<f64>("threshold").expect("defaulted");
+
+            let mut table = Table::new(["start", "end", "knees"]);
+            for phase in phase::detect_phases(&trace, window, stride, divergence, threshold) {
+                let knees: Vec<_> = phase.mrc.knees().iter().map(ToString::to_string).collect();
+                table.push(vec![
+                    phase.start.into(),
+                    phase.end.into(),
+                    knees.join(" ").into(),
+                ]);
+            }
+            table
+        }
+        Some("mrcs") => {
+            let mut table = Table::new(["start", "size", "miss_ratio"]);
+            for (i, histogram) in phase::windowed_histograms(&trace, window, stride)
+                .iter()
+                .enumerate()
+            {
+                let mrc = MissRatioCurve::from_histogram(histogram);
+                for (size, &ratio) in mrc.ratios().iter().enumerate() {
+                    table.push(vec![(i * stride).into(), size.into(), ratio.into()]);
+                }
+            }
+            table
+        }
+        _ => {
+            let mut table = Table::new(["start", "distance", "count"]);
+            for (i, histogram) in phase::windowed_histograms(&trace, window, stride)
+                .iter()
+                .enumerate()
+            {
+                for row in Table::from(histogram).rows() {
+                    table.push(
+                        [(i * stride).into()]
+                            .into_iter()
+                            .chain(row.clone())
+                            .collect(),
+                    );
+                }
+            }
+            table
+        }
+    };
+    emit(matches, &table, Format::Csv)
+}
+
+fn timeseries(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let window = matches.get_one::<usize>("window").copied();
+    let trace = read_trace(matches, "trace")?;
+
+    let table = match window {
+        Some(0) => return Err("--window must be positive".into()),
+        Some(window) => {
+            let mut table = Table::new(["start", "mean_depth", "infinities", "footprint"]);
+            for sample in timeseries::per_window(&trace, window) {
+                table.push(vec![
+                    sample.start.into(),
+                    // a window of only first accesses has no mean depth
+                    sample.mean_depth.map_or_else(|| Cell::from(""), Cell::from),
+                    sample.infinities.into(),
+                    sample.footprint.into(),
+                ]);
+            }
+            table
+        }
+        None => {
+            let mut table = Table::new(["time", "depth", "footprint"]);
+            for (time, sample) in timeseries::per_access(&trace).into_iter().enumerate() {
+                table.push(vec![
+                    time.into(),
+                    sample.depth.map_or_else(|| Cell::from("inf"), Cell::from),
+                    sample.footprint.into(),
+                ]);
+            }
+            table
+        }
+    };
+    emit(matches, &table, Format::Csv)
+}
+
+// Serve the line protocol on stdin, so another process can drive the analysis over a pipe.
+fn stream(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let shift = shift(matches)?;
+    let mut out = output(matches)?;
+    let mut analyzer = OnlineAnalyzer::new();
+
+    for line in io::stdin().lock().lines() {
+        match line?.trim() {
+            "" => {}
+            "FLUSH" => {
+                writeln!(out, "{}", histogram_line(analyzer.histogram()))?;
+                out.flush()?;
+            }
+            "RESET" => analyzer.reset(),
+            access => match trace::parse_address(access, shift) {
+                Ok(address) => {
+                    analyzer.access(address);
+                }
+                // one bad line shouldn't end the session
+                Err(_) => eprintln!("error: unknown access or command `{}`", access),
+            },
+        }
+    }
+
+    writeln!(out, "{}", histogram_line(analyzer.histogram()))?;
+    out.flush()?;
+    Ok(())
+}
+
+fn live(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let size = *matches.get_one::<usize>("window").expect("required");
+    let every = matches.get_one::<usize>("every").copied().unwrap_or(size);
+    if size == 0 || every == 0 {
+        return Err("the window and the accesses between curves must be positive".into());
+    }
+    let shift = shift(matches)?;
+    let mut out = output(matches)?;
+    let mut window = SlidingWindow::new(size);
+
+    let mut accesses = 0;
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        let access = line.trim();
+        if access.is_empty() {
+            continue;
+        }
+        match trace::parse_address(access, shift) {
+            Ok(address) => {
+                window.access(address);
+                accesses += 1;
+                if accesses % every == 0 {
+                    writeln!(out, "{}", mrc_line(&window.mrc()))?;
+                    out.flush()?;
+                }
+            }
+            Err(_) => eprintln!("error: unknown access `{}`", access),
+        }
+    }
+
+    writeln!(out, "{}", mrc_line(&window.mrc()))?;
+    out.flush()?;
+    Ok(())
+}
+
+fn capture(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let source = match matches.get_one::<PathBuf>("input") {
+        Some(path) => capture::Source::File(path.clone()),
+        None => capture::Source::Record(
+            matches
+                .get_many::<OsString>("command")
+                .expect("required")
+                .cloned()
+                .collect(),
+        ),
+    };
+    let perf = matches.get_one::<OsString>("perf").expect("defaulted");
+    let analyzer = capture::capture(perf, &source, shift(matches)?)?;
+    info!(samples = analyzer.histogram().total(), "captured");
+    emit(matches, &Table::from(&analyzer.mrc()), Format::Csv)
+}
+
+#[cfg(all(feature = "perf-event", target_os = "linux"))]
+fn monitor(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    use stack_distance::perf_event::{self, Event};
+
+    let pid = *matches.get_one::<i32>("pid").expect("required");
+    let event = match matches
+        .get_one::<String>("event")
+        .expect("defaulted")
+        .as_str()
+    {
+        "page-faults" => Event::PageFaults,
+        raw => Event::Raw {
+            config: u64::from_str_radix(raw.trim_start_matches("0x"), 16)?,
+            period: *matches.get_one::<u64>("period").expect("defaulted"),
+        },
+    };
+    let interval =
+        Duration::try_from_secs_f64(*matches.get_one::<f64>("interval").expect("defaulted"))?;
+    let duration = matches
+        .get_one::<f64>("duration")
+        .map(|&secs| Duration::try_from_secs_f64(secs))
+        .transpose()?;
+
+    let mut out = output(matches)?;
+    info!(pid, ?event, "monitoring");
+    let analyzer = perf_event::monitor(
+        pid,
+        event,
+        shift(matches)?,
+        duration,
+        interval,
+        |analyzer| {
+            writeln!(out, "{}", histogram_line(analyzer.histogram()))?;
+            out.flush()
+        },
+    )?;
+    writeln!(out, "{}", histogram_line(analyzer.histogram()))?;
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(not(all(feature = "perf-event", target_os = "linux")))]
+fn monitor(_matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    Err("monitoring needs the `perf-event` feature on Linux".into())
+}
+
+#[cfg(feature = "tokio")]
+fn ingest(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    use stack_distance::ingest::{self, Socket};
+
+    let socket = match matches.get_one::<SocketAddr>("tcp") {
+        Some(&addr) => Socket::Tcp(addr),
+        #[cfg(unix)]
+        None => Socket::Unix(
+            matches
+                .get_one::<PathBuf>("unix")
+                .expect("required")
+                .clone(),
+        ),
+        #[cfg(not(unix))]
+        None => return Err("Unix sockets need a Unix platform".into()),
+    };
+    let interval =
+        Duration::try_from_secs_f64(*matches.get_one::<f64>("interval").expect("defaulted"))?;
+
+    let mut out = output(matches)?;
+    info!(?socket, "ingesting");
+    ingest::serve(&socket, interval, |analyzer| {
+        writeln!(out, "{}", histogram_line(analyzer.histogram()))?;
+        out.flush()
+    })?;
+    Ok(())
+}
+
+#[cfg(not(feature = "tokio"))]
+fn ingest(_matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    Err("ingesting needs the `tokio` feature".into())
+}
+
+fn serve(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    if let Some(&addr) = matches.get_one::<SocketAddr>("http") {
+        info!(%addr, "serving HTTP");
+        return Ok(http::serve(addr)?);
+    }
+    if let Some(&addr) = matches.get_one::<SocketAddr>("flight") {
+        return serve_flight(addr, &read_trace(matches, "trace")?);
+    }
+    serve_grpc(*matches.get_one::<SocketAddr>("grpc").expect("required"))
+}
+
+#[cfg(feature = "flight")]
+fn serve_flight(addr: SocketAddr, trace: &Trace) -> Result<(), Box<dyn Error>> {
+    info!(%addr, "serving Arrow Flight");
+    stack_distance::flight::serve(addr, trace)
+}
+
+#[cfg(not(feature = "flight"))]
+fn serve_flight(_addr: SocketAddr, _trace: &Trace) -> Result<(), Box<dyn Error>> {
+    Err("serving Arrow Flight needs the `flight` feature".into())
+}
+
+#[cfg(feature = "grpc")]
+fn serve_grpc(addr: SocketAddr) -> Result<(), Box<dyn Error>> {
+    info!(%addr, "serving gRPC");
+    stack_distance::grpc::serve(addr)
+}
+
+#[cfg(not(feature = "grpc"))]
+fn serve_grpc(_addr: SocketAddr) -> Result<(), Box<dyn Error>> {
+    Err("serving gRPC needs the `grpc` feature".into())
+}
+
+const REPL_HELP: &str = "\
+load PATH        load a trace, replacing the last one
+summary          summarize the trace's locality
+histogram        print the stack distance histogram
+hit_ratio(SIZE)  the hit ratio of an LRU cache of a size, like 64 or 1k
+miss_ratio(SIZE) the miss ratio of an LRU cache of a size
+mrc              print the miss ratio at every size
+top(K)           print the K hottest symbols
+help             print this
+quit             leave
+";
+
+// Completes command names, and paths after `load`.
+struct ReplHelper {
+    files: FilenameCompleter,
+}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let typed = &line[..pos];
+        if typed.starts_with("load ") {
+            return self.files.complete_path(line, pos);
+        }
+        if typed.contains(char::is_whitespace) {
+            return Ok((pos, Vec::new()));
+        }
+        let commands = REPL_HELP
+            .lines()
+            .filter_map(|line| line.split(['(', ' ']).next())
+            .filter(|command| command.starts_with(typed))
+            .map(|command| Pair {
+                display: command.to_string(),
+                replacement: command.to_string(),
+            });
+        Ok((0, commands.collect()))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+// A trace loaded in the REPL, with its curve so queries don't recompute it.
+struct Loaded {
+    trace: Trace,
+    addresses: Addresses,
+    histogram: Histogram,
+    mrc: MissRatioCurve,
+}
+
+fn repl(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let mut editor = Editor::<ReplHelper, DefaultHistory>::new()?;
+    editor.set_helper(Some(ReplHelper {
+        files: FilenameCompleter::new(),
+    }));
+    let history =
+        env::var_os("HOME").map(|home| PathBuf::from(home).join(".stack_distance_history"));
+    if let Some(history) = &history {
+        // there's no history before the first session
+        let _ = editor.load_history(history);
+    }
+
+    let mut loaded = None;
+    if let Some(path) = matches.get_one::<PathBuf>("trace") {
+        loaded = Some(load(matches, path)?);
+    }
+    loop {
+        let line = match editor.readline("> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        editor.add_history_entry(line.as_str())?;
+        match repl_command(matches, &line, &mut loaded) {
+            Ok(true) => {}
+            Ok(false) => break,
+            // a mistyped command shouldn't end the session
+            Err(e) => eprintln!("error: {}", e),
+        }
+    }
+
+    if let Some(history) = &history {
+        editor.save_history(history)?;
+    }
+    Ok(())
+}
+
+// Load a trace for the REPL.
+fn load(matches: &ArgMatches, path: &Path) -> Result<Loaded, Box<dyn Error>> {
+    let (trace, addresses) = read_trace_from(matches, path, shift(matches)?)?;
+    let histogram = trace.stack_distance_histogram();
+    let mrc = MissRatioCurve::from_histogram(&histogram);
+    eprintln!("loaded {} accesses", trace.len());
+    Ok(Loaded {
+        trace,
+        addresses,
+        histogram,
+        mrc,
+    })
+}
+
+// Run one line of the REPL, returning whether to keep going. Calls like `hit_ratio(64)` are the
+// same as `hit_ratio 64`.
+fn repl_command(
+    matches: &ArgMatches,
+    line: &str,
+    loaded: &mut Option<Loaded>,
+) -> Result<bool, Box<dyn Error>> {
+    let words: Vec<&str> = line
+        .split(|c: char| c.is_whitespace() || "(),".contains(c))
+        .filter(|word| !word.is_empty())
+        .collect();
+    let format = matches
+        .get_one::<Format>("format")
+        .copied()
+        .unwrap_or(Format::Table);
+    let mut out = io::stdout().lock();
+
+    match words.as_slice() {
+        ["quit" | "exit"] => return Ok(false),
+        ["help"] => write!(out, "{}", REPL_HELP)?,
+        ["load", path] => *loaded = Some(load(matches, Path::new(path))?),
+        [command, args @ ..] => {
+            let Loaded {
+                trace,
+                addresses,
+                histogram,
+                mrc,
+            } = loaded
+                .as_ref()
+                .ok_or("no trace is loaded, try `load PATH`")?;
+            match (*command, args) {
+                ("summary", []) => {
+                    write!(out, "{}", Summary::with_addresses(trace, addresses))?;
+                }
+                ("histogram", []) => Table::from(histogram).write(format, &mut out)?,
+                ("hit_ratio", [size]) => {
+                    writeln!(out, "{}", mrc.hit_ratio(simulate::parse_size(size)?))?;
+                }
+                ("miss_ratio", [size]) => {
+                    writeln!(out, "{}", mrc.miss_ratio(simulate::parse_size(size)?))?;
+                }
+                ("mrc", []) => Table::from(mrc).write(format, &mut out)?,
+                ("top", [k]) => {
+                    hot_symbols(trace, addresses, k.parse()?).write(format, &mut out)?
+                }
+                _ => return Err(format!("unknown command `{}`, try `help`", line.trim()).into()),
+            }
+        }
+        [] => {}
+    }
+    Ok(true)
+}
+
+fn completions(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let shell = *matches.get_one::<Shell>("shell").expect("required");
+    let mut out = output(matches)?;
+    clap_complete::generate(shell, &mut cli(), "stack-distance", &mut out);
+    out.flush()?;
+    Ok(())
+}
+
+fn manpage(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let mut out = output(matches)?;
+    Man::new(cli()).render(&mut out)?;
+    out.flush()?;
+    Ok(())
+}
+
+// Log to stderr at the level of -v, or else of RUST_LOG, or else only warnings.
+fn init_logging(verbosity: u8) {
+    let filter = match verbosity {
+        0 => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn")),
+        1 => EnvFilter::new("info"),
+        2 => EnvFilter::new("debug"),
+        _ => EnvFilter::new("trace"),
+    };
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr)
+        .init();
+}
+
+// The entry point of a subcommand.
+type Subcommand = fn(&ArgMatches) -> Result<(), Box<dyn Error>>;
+
+// When a file was last modified, and how long it was, to notice changes.
+fn stamp(path: &Path) -> io::Result<(SystemTime, u64)> {
+    let metadata = fs::metadata(path)?;
+    Ok((metadata.modified()?, metadata.len()))
+}
+
+// Run a subcommand, then again whenever one of its trace files changes, until interrupted.
+fn watch(matches: &ArgMatches, run: Subcommand) -> Result<(), Box<dyn Error>> {
+    let paths: Vec<&Path> = ["trace", "other"]
+        .iter()
+        .filter_map(|id| matches.try_get_one::<PathBuf>(id).ok().flatten())
+        .map(PathBuf::as_path)
+        .collect();
+    if paths.is_empty() {
+        return Err("--watch needs a subcommand which reads a trace".into());
+    }
+
+    let stamps = || {
+        paths
+            .iter()
+            .map(|path| stamp(path).ok())
+            .collect::<Vec<_>>()
+    };
+    let mut last = stamps();
+    loop {
+        // a trace which is still being written may not parse yet, which is worth a retry
+        if let Err(e) = run(matches) {
+            eprintln!("Error: {}", e);
+        }
+        eprintln!("watching for changes...");
+        loop {
+            thread::sleep(Duration::from_millis(500));
+            let current = stamps();
+            if current != last {
+                last = current;
+                break;
+            }
+        }
+    }
+}
+
+// The error from verifying traces which break an invariant.
+#[derive(Debug)]
+struct InvariantViolation {
+    failed: usize,
+    checked: u64,
+}
+
+impl Display for InvariantViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} of {} traces failed", self.failed, self.checked)
+    }
+}
+
+impl Error for InvariantViolation {}
+
+// The error from validating a trace file with anomalies.
+#[derive(Debug)]
+struct InvalidTrace {
+    anomalies: usize,
+    first: Anomaly,
+}
+
+impl Display for InvalidTrace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} anomalies, first at {}", self.anomalies, self.first)
+    }
+}
+
+impl Error for InvalidTrace {}
+
+// The kind of an error and the exit code for it. Clap exits with 2 for usage errors itself.
+fn error_kind(e: &(dyn Error + 'static)) -> (&'static str, u8) {
+    if e.is::<io::Error>() {
+        ("io", 4)
+    } else if e.is::<InvariantViolation>() {
+        ("invariant", 5)
+    } else if e.is::<InvalidTrace>()
+        || e.is::<ParseIntError>()
+        || e.is::<ParseFloatError>()
+        || e.is::<ParseHistogramError>()
+        || e.is::<ParseFormatError>()
+        || e.is::<simulate::ParseError>()
+        || e.is::<toml::de::Error>()
+    {
+        ("parse", 3)
+    } else {
+        ("other", 1)
+    }
+}
+
+// Report an error on stderr, as JSON if asked for, and pick the exit code.
+fn report(e: &(dyn Error + 'static), json: bool) -> ExitCode {
+    let (kind, code) = error_kind(e);
+    if json {
+        let mut table = Table::new(["error", "kind", "code"]);
+        table.push(vec![
+            e.to_string().into(),
+            kind.into(),
+            u32::from(code).into(),
+        ]);
+        // there's nowhere left to report a failure to write to stderr
+        let _ = table.write(Format::Json, io::stderr().lock());
+    } else {
+        eprintln!("Error: {}", e);
+    }
+    ExitCode::from(code)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<OsString> = env::args_os().collect();
+    let configured = config_path(&args).map(|path| -> Result<_, Box<dyn Error>> {
+        configure(cli(), &fs::read_to_string(path)?.parse()?)
+    });
+    let command = match configured {
+        // the format isn't parsed yet
+        Some(Err(e)) => return report(e.as_ref(), false),
+        Some(Ok(command)) => command,
+        None => cli(),
+    };
+    let matches = command.get_matches_from(args);
+    init_logging(matches.get_count("verbose"));
+    let (name, matches) = matches.subcommand().expect("a subcommand is required");
+    let run: Subcommand = match name {
+        "analyze" => analyze,
+        "merge" => merge,
+        "enumerate" => enumerate,
+        "simulate" => simulate,
+        "mrc" => mrc,
+        "libcachesim" => libcachesim,
+        "generate" => generate,
+        "compare" => compare,
+        "validate" => validate,
+        "verify" => verify,
+        "classify" => classify,
+        "working-set" => working_set,
+        "windows" => windows,
+        "timeseries" => timeseries,
+        "stream" => stream,
+        "live" => live,
+        "capture" => capture,
+        "monitor" => monitor,
+        "ingest" => ingest,
+        "repl" => repl,
+        "serve" => serve,
+        "completions" => completions,
+        "manpage" => manpage,
+        _ => unreachable!("every subcommand is handled"),
+    };
+
+    let result = if matches.get_flag("watch") {
+        watch(matches, run)
+    } else {
+        run(matches)
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => report(
+            e.as_ref(),
+            matches.get_one::<Format>("format") == Some(&Format::Json),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cli() {
+        super::cli().debug_assert();
+    }
+
+    #[test]
+    fn config() {
+        let config = "shift = 6\n\n[simulate]\nsizes = [\"1k\", 64]\n"
+            .parse()
+            .unwrap();
+        let matches = configure(super::cli(), &config)
+            .unwrap()
+            .try_get_matches_from(["stack-distance", "simulate", "--shift", "2", "trace"])
+            .unwrap();
+        let (_, matches) = matches.subcommand().unwrap();
+        assert_eq!(
+            matches
+                .get_many::<usize>("sizes")
+                .unwrap()
+                .collect::<Vec<_>>(),
+            [&1024, &64]
+        );
+        assert_eq!(matches.get_one::<u32>("shift"), Some(&2));
+
+        let unknown = "[simulate]\ncapacity = 1\n".parse().unwrap();
+        assert!(configure(super::cli(), &unknown).is_err());
+    }
+
+    #[test]
+    fn generate_cyclic() {
+        let path = env::temp_dir().join(format!("stack-distance-{}.txt", std::process::id()));
+        let matches = super::cli()
+            .try_get_matches_from([
+                "stack-distance",
+                "generate",
+                "--pattern",
+                "cyclic",
+                "--alphabet",
+                "4",
+                "--len",
+                "10",
+                "--output",
+                path.to_str().unwrap(),
+            ])
+            .unwrap();
+        let (_, matches) = matches.subcommand().unwrap();
+        generate(matches).unwrap();
+        let trace = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            trace.parse(),
+            Ok(Trace::from(vec![0, 1, 2, 3, 0, 1, 2, 3, 0, 1]))
+        );
+    }
 
-    for trace in TraceIter::new(TRACE_SIZE) {
-        // println!("{}", trace);
-        compare(trace);
+    #[test]
+    fn exit_codes() {
+        let code = |e: Box<dyn Error>| error_kind(e.as_ref()).1;
+        assert_eq!(code("x".parse::<u32>().unwrap_err().into()), 3);
+        assert_eq!(code(io::Error::from(io::ErrorKind::NotFound).into()), 4);
+        let violation = InvariantViolation {
+            failed: 1,
+            checked: 2,
+        };
+        assert_eq!(code(violation.into()), 5);
+        assert_eq!(code("--jobs must be positive".into()), 1);
     }
 }
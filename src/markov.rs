@@ -0,0 +1,229 @@
+//! Contains first-order Markov models of traces.
+
+use std::collections::HashMap;
+
+use crate::rng::Rng;
+use crate::trace::Trace;
+
+/// A first-order Markov chain over symbols.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkovModel {
+    // the symbol of each state, in order of first appearance
+    symbols: Vec<u32>,
+    // row-stochastic: transitions[i][j] is the probability of going from state i to state j
+    transitions: Vec<Vec<f64>>,
+    // the distribution of the first symbol
+    initial: Vec<f64>,
+}
+
+// Normalize counts to probabilities, leaving an all-zero row alone.
+#[allow(clippy::cast_precision_loss)]
+fn normalize(counts: &[usize]) -> Vec<f64> {
+    let total: usize = counts.iter().sum();
+    counts
+        .iter()
+        .map(|&n| {
+            if total == 0 {
+                0.0
+            } else {
+                n as f64 / total as f64
+            }
+        })
+        .collect()
+}
+
+// Sample an index from a probability distribution.
+fn sample(distribution: &[f64], rng: &mut Rng) -> usize {
+    let mut remaining = rng.next_f64();
+    for (i, &p) in distribution.iter().enumerate() {
+        if remaining < p {
+            return i;
+        }
+        remaining -= p;
+    }
+    // rounding can leave a sliver at the end, which belongs to the last possible state
+    distribution
+        .iter()
+        .rposition(|&p| p > 0.0)
+        .expect("the distribution is non-empty")
+}
+
+// Whether a vector is a probability distribution, up to rounding.
+fn is_distribution(probabilities: &[f64]) -> bool {
+    probabilities.iter().all(|&p| p >= 0.0)
+        && (probabilities.iter().sum::<f64>() - 1.0).abs() < 1e-9
+}
+
+impl MarkovModel {
+    /// Create a model from its states' symbols, transition matrix, and the distribution of the
+    /// first state.
+    ///
+    /// Returns `None` unless there's at least one state, the matrix is square with a row and column
+    /// per symbol, and the initial distribution and every row of the matrix are probability
+    /// distributions.
+    pub fn new(symbols: Vec<u32>, transitions: Vec<Vec<f64>>, initial: Vec<f64>) -> Option<Self> {
+        let n = symbols.len();
+        let valid = n > 0
+            && initial.len() == n
+            && is_distribution(&initial)
+            && transitions.len() == n
+            && transitions
+                .iter()
+                .all(|row| row.len() == n && is_distribution(row));
+
+        valid.then_some(Self {
+            symbols,
+            transitions,
+            initial,
+        })
+    }
+
+    /// Fit a model to a trace by counting transitions between consecutive accesses.
+    ///
+    /// A symbol which is never followed by anything (i.e. only appears last) transitions
+    /// according to the overall symbol frequencies, which is also the initial distribution.
+    pub fn fit(trace: &Trace) -> Self {
+        let mut states = HashMap::new();
+        let mut symbols = Vec::new();
+        let indices: Vec<usize> = trace
+            .as_slice()
+            .iter()
+            .map(|&n| {
+                *states.entry(n).or_insert_with(|| {
+                    symbols.push(n);
+                    symbols.len() - 1
+                })
+            })
+            .collect();
+
+        let mut counts = vec![vec![0; symbols.len()]; symbols.len()];
+        let mut frequencies = vec![0; symbols.len()];
+        for &i in &indices {
+            frequencies[i] += 1;
+        }
+        for pair in indices.windows(2) {
+            counts[pair[0]][pair[1]] += 1;
+        }
+
+        let initial = normalize(&frequencies);
+        let transitions = counts
+            .iter()
+            .map(|row| {
+                if row.iter().all(|&n| n == 0) {
+                    initial.clone()
+                } else {
+                    normalize(row)
+                }
+            })
+            .collect();
+
+        Self {
+            symbols,
+            transitions,
+            initial,
+        }
+    }
+
+    /// The symbol of each state, indexing the rows and columns of the transition matrix.
+    pub fn symbols(&self) -> &[u32] {
+        &self.symbols
+    }
+
+    /// The transition matrix, where the entry at `[i][j]` is the probability that state `i` is
+    /// followed by state `j`.
+    pub fn transition_matrix(&self) -> &[Vec<f64>] {
+        &self.transitions
+    }
+
+    /// The distribution of the first state.
+    pub fn initial_distribution(&self) -> &[f64] {
+        &self.initial
+    }
+
+    /// The probability that `from` is followed by `to`.
+    pub fn probability(&self, from: u32, to: u32) -> f64 {
+        let index = |symbol| self.symbols.iter().position(|&n| n == symbol);
+        match (index(from), index(to)) {
+            (Some(i), Some(j)) => self.transitions[i][j],
+            _ => 0.0,
+        }
+    }
+
+    /// Generate a synthetic trace by walking the chain.
+    pub fn generate(&self, len: usize, rng: &mut Rng) -> Trace {
+        if self.symbols.is_empty() {
+            return Trace::from(vec![]);
+        }
+
+        let mut state = sample(&self.initial, rng);
+        let mut out = Vec::with_capacity(len);
+        for _ in 0..len {
+            out.push(self.symbols[state]);
+            state = sample(&self.transitions[state], rng);
+        }
+        Trace::from(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit() {
+        let model = MarkovModel::fit(&Trace::from(vec![5, 7, 5, 5, 7, 9]));
+        assert_eq!(model.symbols(), &[5, 7, 9]);
+        assert_eq!(
+            model.transition_matrix()[0],
+            vec![1.0 / 3.0, 2.0 / 3.0, 0.0]
+        );
+        assert_eq!(model.probability(7, 5), 0.5);
+        assert_eq!(model.probability(7, 9), 0.5);
+        // 9 is never followed by anything, so it falls back to the frequencies
+        assert_eq!(model.probability(9, 5), 0.5);
+        assert_eq!(model.probability(1, 5), 0.0);
+    }
+
+    #[test]
+    fn new() {
+        let model = MarkovModel::new(
+            vec![3, 4],
+            vec![vec![0.0, 1.0], vec![0.5, 0.5]],
+            vec![1.0, 0.0],
+        )
+        .unwrap();
+        assert_eq!(model.probability(3, 4), 1.0);
+        assert_eq!(model.initial_distribution(), &[1.0, 0.0]);
+
+        assert!(MarkovModel::new(vec![], vec![], vec![]).is_none());
+        assert!(MarkovModel::new(vec![3], vec![vec![0.5]], vec![1.0]).is_none());
+        assert!(MarkovModel::new(vec![3, 4], vec![vec![1.0, 0.0]], vec![1.0, 0.0]).is_none());
+        assert!(MarkovModel::new(
+            vec![3, 4],
+            vec![vec![2.0, -1.0], vec![0.5, 0.5]],
+            vec![1.0, 0.0]
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn generate() {
+        // a deterministic cycle is reproduced exactly, from wherever it starts
+        let model = MarkovModel::fit(&Trace::from(vec![0, 1, 2, 0, 1, 2, 0]));
+        let trace = model.generate(100, &mut Rng::new(1));
+        for pair in trace.as_slice().windows(2) {
+            assert_eq!(pair[1], (pair[0] + 1) % 3);
+        }
+
+        let empty = MarkovModel::fit(&Trace::from(vec![]));
+        assert_eq!(empty.generate(5, &mut Rng::new(1)), Trace::from(vec![]));
+    }
+
+    #[test]
+    fn generated_frequencies() {
+        let model = MarkovModel::fit(&Trace::from(vec![0, 0, 0, 1, 0, 0, 0, 1]));
+        let trace = model.generate(10_000, &mut Rng::new(2));
+        let ones = trace.as_slice().iter().filter(|&&n| n == 1).count();
+        assert!((2000..3000).contains(&ones));
+    }
+}
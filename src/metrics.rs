@@ -0,0 +1,118 @@
+//! Contains distance metrics between stack distance histograms.
+//!
+//! Histograms are normalized to probability distributions before comparing, with infinities as
+//! a final bin past every finite distance, so traces of different lengths are comparable.
+
+use crate::histogram::Histogram;
+
+// Normalize a pair of histograms to probability distributions over the same bins, with
+// infinities in the last bin. `smoothing` is added to every bin first.
+fn distributions(a: &Histogram, b: &Histogram, smoothing: f64) -> (Vec<f64>, Vec<f64>) {
+    let bins = a.frequencies().len().max(b.frequencies().len()) + 1;
+
+    #[allow(clippy::cast_precision_loss)]
+    let normalize = |h: &Histogram| {
+        let mut counts: Vec<f64> = h.frequencies().iter().map(|&n| n as f64).collect();
+        counts.resize(bins - 1, 0.0);
+        counts.push(h.infinities() as f64);
+
+        let total = h.total() as f64 + smoothing * bins as f64;
+        counts
+            .into_iter()
+            .map(|n| (n + smoothing) / total)
+            .collect()
+    };
+
+    (normalize(a), normalize(b))
+}
+
+/// Calculate the earth mover's distance between two histograms, in units of one stack distance.
+///
+/// Returns zero if either histogram is empty.
+pub fn earth_movers_distance(a: &Histogram, b: &Histogram) -> f64 {
+    if a.total() == 0 || b.total() == 0 {
+        return 0.0;
+    }
+
+    // in one dimension, this is the area between the cumulative distributions
+    let (p, q) = distributions(a, b, 0.0);
+    let mut carried = 0.0;
+    let mut work = 0.0;
+    for (p, q) in p.iter().zip(&q) {
+        carried += p - q;
+        work += f64::abs(carried);
+    }
+    work
+}
+
+/// Calculate the Kullback-Leibler divergence of `b` from `a`, in bits.
+///
+/// Every bin gets add-one smoothing, so the divergence is finite even where `b` has no accesses.
+pub fn kl_divergence(a: &Histogram, b: &Histogram) -> f64 {
+    let (p, q) = distributions(a, b, 1.0);
+    p.iter().zip(&q).map(|(p, q)| p * (p / q).log2()).sum()
+}
+
+/// Calculate the chi-square distance between two histograms, between zero and one.
+///
+/// Returns zero if either histogram is empty.
+pub fn chi_square_distance(a: &Histogram, b: &Histogram) -> f64 {
+    if a.total() == 0 || b.total() == 0 {
+        return 0.0;
+    }
+
+    let (p, q) = distributions(a, b, 0.0);
+    p.iter()
+        .zip(&q)
+        .filter(|(p, q)| *p + *q > 0.0)
+        .map(|(p, q)| (p - q).powi(2) / (p + q))
+        .sum::<f64>()
+        / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn earth_movers() {
+        let a = Histogram::new(vec![2], 0);
+        let b = Histogram::new(vec![0, 0, 2], 0);
+        assert!(close(earth_movers_distance(&a, &b), 2.0));
+        assert!(close(earth_movers_distance(&b, &a), 2.0));
+        assert!(close(earth_movers_distance(&a, &a), 0.0));
+        // the infinity bin is one past the last distance
+        let c = Histogram::new(vec![], 5);
+        assert!(close(earth_movers_distance(&a, &c), 1.0));
+    }
+
+    #[test]
+    fn kl() {
+        let a = Histogram::new(vec![2], 0);
+        let b = Histogram::new(vec![0, 0, 2], 0);
+        assert!(close(kl_divergence(&a, &a), 0.0));
+        assert!(kl_divergence(&a, &b) > 0.0);
+    }
+
+    #[test]
+    fn chi_square() {
+        let a = Histogram::new(vec![1, 1], 0);
+        let b = Histogram::new(vec![0, 0, 2], 0);
+        assert!(close(chi_square_distance(&a, &b), 1.0));
+        assert!(close(chi_square_distance(&a, &a), 0.0));
+        // half the mass moves: (0.5^2 / 1.5 + 0.5^2 / 0.5) / 2
+        let c = Histogram::new(vec![2, 0], 0);
+        assert!(close(chi_square_distance(&a, &c), 1.0 / 3.0));
+    }
+
+    #[test]
+    fn empty() {
+        let a = Histogram::new(vec![3], 1);
+        assert_eq!(earth_movers_distance(&a, &Histogram::default()), 0.0);
+        assert_eq!(chi_square_distance(&Histogram::default(), &a), 0.0);
+    }
+}
@@ -0,0 +1,167 @@
+//! Contains the `MissRatioCurve` struct.
+
+use crate::histogram::Histogram;
+use crate::trace::Trace;
+
+/// The miss ratio of an LRU cache at every size.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissRatioCurve {
+    // the ith entry is the miss ratio of a cache of size i; it's constant past the end
+    ratios: Vec<f64>,
+}
+
+impl MissRatioCurve {
+    /// Build the curve from a stack distance histogram.
+    ///
+    /// A cache of size `c` hits exactly the accesses with stack distance less than `c`.
+    pub fn from_histogram(histogram: &Histogram) -> Self {
+        let accesses = histogram.total();
+        if accesses == 0 {
+            return Self { ratios: vec![0.0] };
+        }
+
+        let mut misses = accesses;
+        let freqs = histogram.frequencies();
+        let mut ratios = Vec::with_capacity(freqs.len() + 1);
+
+        #[allow(clippy::cast_precision_loss)]
+        for hits in std::iter::once(0).chain(freqs.iter().copied()) {
+            misses -= hits;
+            ratios.push(misses as f64 / accesses as f64);
+        }
+
+        Self { ratios }
+    }
+
+    // Build the curve from miss ratios at each size, which must be non-empty.
+    pub(crate) fn from_ratios(ratios: Vec<f64>) -> Self {
+        Self { ratios }
+    }
+
+    /// The miss ratio of a cache of the given size.
+    pub fn miss_ratio(&self, size: usize) -> f64 {
+        self.ratios[size.min(self.ratios.len() - 1)]
+    }
+
+    /// The hit ratio of a cache of the given size.
+    pub fn hit_ratio(&self, size: usize) -> f64 {
+        1.0 - self.miss_ratio(size)
+    }
+
+    /// The miss ratios at sizes from zero up to the size after which the curve is flat.
+    pub fn ratios(&self) -> &[f64] {
+        &self.ratios
+    }
+
+    /// Find the knees of the curve, in increasing order of size.
+    ///
+    /// This is the Kneedle algorithm: with both axes normalized, knees are the local maxima of
+    /// the curve's height above the chord from its first to its last point. Since the curve is
+    /// flat past its last size, the chord runs one size further, so a final cliff is also a knee.
+    pub fn knees(&self) -> Vec<usize> {
+        let first = self.ratios[0];
+        let last = self.ratios[self.ratios.len() - 1];
+        if first <= last {
+            return Vec::new();
+        }
+
+        #[allow(clippy::cast_precision_loss)]
+        let heights: Vec<f64> = self
+            .ratios
+            .iter()
+            .enumerate()
+            .map(|(size, &ratio)| {
+                (first - ratio) / (first - last) - size as f64 / self.ratios.len() as f64
+            })
+            .collect();
+
+        (1..heights.len())
+            .filter(|&i| {
+                heights[i] > 0.0
+                    && heights[i] > heights[i - 1]
+                    && heights.get(i + 1).is_none_or(|&next| heights[i] >= next)
+            })
+            .collect()
+    }
+
+    /// Recommend cache sizes at the knees of the curve.
+    ///
+    /// Each recommendation includes the marginal benefit of growing the cache to it from the
+    /// previous recommendation (or from nothing), as the drop in miss ratio per added entry.
+    pub fn recommend(&self) -> Vec<Recommendation> {
+        let mut previous = 0;
+        self.knees()
+            .into_iter()
+            .map(|size| {
+                #[allow(clippy::cast_precision_loss)]
+                let marginal_benefit =
+                    (self.miss_ratio(previous) - self.miss_ratio(size)) / (size - previous) as f64;
+                previous = size;
+                Recommendation {
+                    size,
+                    miss_ratio: self.miss_ratio(size),
+                    marginal_benefit,
+                }
+            })
+            .collect()
+    }
+}
+
+/// A recommended cache size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Recommendation {
+    /// The size of the cache.
+    pub size: usize,
+    /// The miss ratio at that size.
+    pub miss_ratio: f64,
+    /// The drop in miss ratio per entry since the previous recommendation.
+    pub marginal_benefit: f64,
+}
+
+impl From<&Trace> for MissRatioCurve {
+    fn from(trace: &Trace) -> Self {
+        Self::from_histogram(&trace.stack_distance_histogram())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_trace() {
+        // distances: inf inf inf 2 2 inf 2 3 0 3
+        let mrc = MissRatioCurve::from(&Trace::from(vec![0, 1, 2, 0, 1, 3, 0, 2, 2, 1]));
+        assert_eq!(mrc.ratios(), &[1.0, 0.9, 0.9, 0.6, 0.4]);
+        assert_eq!(mrc.miss_ratio(100), 0.4);
+        assert!((mrc.hit_ratio(3) - 0.4).abs() < 1e-12);
+    }
+
+    #[test]
+    fn knees() {
+        // a hot set of two, then a plateau until everything fits at eight
+        let mrc =
+            MissRatioCurve::from_ratios(vec![1.0, 0.6, 0.3, 0.29, 0.28, 0.27, 0.26, 0.25, 0.05]);
+        assert_eq!(mrc.knees(), vec![2, 8]);
+
+        let recommendations = mrc.recommend();
+        assert_eq!(recommendations.len(), 2);
+        assert!((recommendations[0].marginal_benefit - 0.35).abs() < 1e-12);
+        assert!((recommendations[1].miss_ratio - 0.05).abs() < 1e-12);
+        assert!((recommendations[1].marginal_benefit - 0.25 / 6.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn cyclic_cliff() {
+        let mrc = MissRatioCurve::from(&Trace::from((0..40).map(|i| i % 4).collect::<Vec<_>>()));
+        assert_eq!(mrc.knees(), vec![4]);
+        assert_eq!(MissRatioCurve::from(&Trace::from(vec![])).knees(), vec![]);
+    }
+
+    #[test]
+    fn empty() {
+        let mrc = MissRatioCurve::from(&Trace::from(vec![]));
+        assert_eq!(mrc.miss_ratio(0), 0.0);
+        assert_eq!(mrc.miss_ratio(5), 0.0);
+    }
+}
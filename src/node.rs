@@ -0,0 +1,121 @@
+//! Contains a Node.js API, for analyzing access logs in-process from JavaScript and TypeScript.
+//!
+//! Build it with `napi build --release --features node`, which also writes its TypeScript
+//! declarations. Methods are camel-cased in JavaScript, like `missRatios`.
+
+use std::collections::HashMap;
+
+use napi_derive::napi;
+
+use crate::histogram::Histogram;
+use crate::mrc::MissRatioCurve;
+use crate::trace::LruStack;
+
+// Counts go to JavaScript as i64, which it reads as a number.
+fn to_i64(n: usize) -> i64 {
+    i64::try_from(n).unwrap_or(i64::MAX)
+}
+
+/// Computes stack distances of keys, like the URLs of an access log, one access at a time.
+#[napi]
+#[derive(Debug, Clone, Default)]
+pub struct Analyzer {
+    // the keys are arbitrary strings, so number them as they're first seen
+    ids: HashMap<String, u32>,
+    stack: LruStack,
+    histogram: Histogram,
+}
+
+#[napi]
+impl Analyzer {
+    /// Create an analyzer which hasn't seen any accesses.
+    #[napi(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Access a key, returning its stack distance, or `null` if it's the first access.
+    ///
+    /// # Panics
+    ///
+    /// Panics after 2^32 distinct keys.
+    #[napi]
+    pub fn access(&mut self, key: String) -> Option<i64> {
+        let next = u32::try_from(self.ids.len()).expect("fewer than 2^32 distinct keys");
+        let id = *self.ids.entry(key).or_insert(next);
+        let distance = self.stack.access(id);
+        self.histogram.record(distance);
+        distance.map(to_i64)
+    }
+
+    /// Access every key of an array in order.
+    #[napi]
+    pub fn feed(&mut self, keys: Vec<String>) {
+        for key in keys {
+            self.access(key);
+        }
+    }
+
+    /// The number of accesses at each finite distance.
+    #[napi]
+    pub fn frequencies(&self) -> Vec<i64> {
+        self.histogram
+            .frequencies()
+            .iter()
+            .map(|&count| to_i64(count))
+            .collect()
+    }
+
+    /// The number of first accesses, whose distance is infinite.
+    #[napi]
+    pub fn infinities(&self) -> i64 {
+        to_i64(self.histogram.infinities())
+    }
+
+    /// The miss ratio of an LRU cache at each size, from zero up.
+    #[napi]
+    pub fn miss_ratios(&self) -> Vec<f64> {
+        MissRatioCurve::from_histogram(&self.histogram)
+            .ratios()
+            .to_vec()
+    }
+
+    /// The hit ratio of an LRU cache holding `size` objects.
+    #[napi]
+    pub fn hit_ratio(&self, size: u32) -> f64 {
+        MissRatioCurve::from_histogram(&self.histogram).hit_ratio(size as usize)
+    }
+
+    /// Forget every access.
+    #[napi]
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Analyze a whole log of keys at once.
+#[napi]
+pub fn analyze(keys: Vec<String>) -> Analyzer {
+    let mut analyzer = Analyzer::new();
+    analyzer.feed(keys);
+    analyzer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn analyzer() {
+        let keys = ["/a.png", "/b.css", "/a.png"].map(String::from);
+        let mut analyzer = analyze(keys.to_vec());
+        assert_eq!(analyzer.access("/b.css".to_string()), Some(1));
+        assert_eq!(analyzer.access("/c.js".to_string()), None);
+        assert_eq!(analyzer.frequencies(), [0, 2]);
+        assert_eq!(analyzer.infinities(), 3);
+        assert_eq!(analyzer.hit_ratio(2), 0.4);
+
+        analyzer.reset();
+        assert_eq!(analyzer.miss_ratios(), [0.0]);
+    }
+}
@@ -0,0 +1,71 @@
+//! Contains online analysis of traces which arrive one access at a time.
+
+use std::collections::HashMap;
+
+use crate::histogram::Histogram;
+use crate::mrc::MissRatioCurve;
+use crate::trace::LruStack;
+
+/// Computes the stack distances of arbitrary addresses as they arrive, keeping the histogram so
+/// far, for traces which are never held in memory.
+#[derive(Debug, Clone, Default)]
+pub struct OnlineAnalyzer {
+    // the addresses are arbitrary, so number them as they're first seen
+    ids: HashMap<u64, u32>,
+    stack: LruStack,
+    histogram: Histogram,
+}
+
+impl OnlineAnalyzer {
+    /// Create an analyzer which hasn't seen any accesses.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Access an address, returning its stack distance, or `None` if it's the first access.
+    ///
+    /// # Panics
+    ///
+    /// Panics after 2^32 distinct addresses.
+    pub fn access(&mut self, address: u64) -> Option<usize> {
+        let next = u32::try_from(self.ids.len()).expect("fewer than 2^32 distinct addresses");
+        let id = *self.ids.entry(address).or_insert(next);
+        let distance = self.stack.access(id);
+        self.histogram.record(distance);
+        distance
+    }
+
+    /// The histogram of every access so far.
+    pub fn histogram(&self) -> &Histogram {
+        &self.histogram
+    }
+
+    /// The miss ratio curve of every access so far.
+    pub fn mrc(&self) -> MissRatioCurve {
+        MissRatioCurve::from_histogram(&self.histogram)
+    }
+
+    /// Forget every access.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn online_analyzer() {
+        let mut analyzer = OnlineAnalyzer::new();
+        let distances: Vec<_> = [1 << 40, 7, 1 << 40, 9, 7]
+            .into_iter()
+            .map(|address| analyzer.access(address))
+            .collect();
+        assert_eq!(distances, [None, None, Some(1), None, Some(2)]);
+        assert_eq!(analyzer.histogram(), &Histogram::new(vec![0, 1, 1], 3));
+
+        analyzer.reset();
+        assert_eq!(analyzer.access(7), None);
+    }
+}
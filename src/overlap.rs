@@ -0,0 +1,157 @@
+//! Contains overlap analysis between traces which share a cache.
+
+use std::collections::HashSet;
+
+use crate::trace::Trace;
+
+/// The overlap between the symbol sets of several traces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Overlap {
+    /// The number of symbols accessed by every trace.
+    pub shared: usize,
+    /// The number of symbols accessed by any trace.
+    pub total: usize,
+}
+
+impl Overlap {
+    /// Calculate the overlap of the symbol sets of the traces.
+    pub fn new(traces: &[Trace]) -> Self {
+        let sets: Vec<HashSet<u32>> = traces
+            .iter()
+            .map(|trace| trace.as_slice().iter().copied().collect())
+            .collect();
+
+        let total = sets.iter().flatten().collect::<HashSet<_>>().len();
+        let shared = sets.first().map_or(0, |first| {
+            first
+                .iter()
+                .filter(|n| sets.iter().all(|set| set.contains(n)))
+                .count()
+        });
+
+        Self { shared, total }
+    }
+
+    /// The Jaccard index, the fraction of all symbols which are shared, or zero if there are none.
+    pub fn jaccard(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        #[allow(clippy::cast_precision_loss)]
+        let jaccard = self.shared as f64 / self.total as f64;
+        jaccard
+    }
+}
+
+/// Calculate the Jaccard index between every pair of traces.
+///
+/// Returns a symmetric matrix whose `[i][j]` entry is the overlap of traces `i` and `j`.
+pub fn pairwise_jaccard(traces: &[Trace]) -> Vec<Vec<f64>> {
+    traces
+        .iter()
+        .map(|a| {
+            traces
+                .iter()
+                .map(|b| Overlap::new(&[a.clone(), b.clone()]).jaccard())
+                .collect()
+        })
+        .collect()
+}
+
+/// The footprints of several traces over the same window of time.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct WindowFootprints {
+    /// The index of the first access in the window.
+    pub start: usize,
+    /// The number of distinct symbols each trace accesses in the window.
+    pub individual: Vec<usize>,
+    /// The number of distinct symbols the traces access together in the window.
+    pub combined: usize,
+}
+
+impl WindowFootprints {
+    /// How much smaller the combined footprint is than the sum of the individual ones, i.e. how
+    /// many entries a shared cache saves over partitioning it.
+    pub fn shared(&self) -> usize {
+        self.individual.iter().sum::<usize>() - self.combined
+    }
+}
+
+/// Calculate the individual and combined footprints of the traces over consecutive windows.
+///
+/// The traces are aligned at their first access, so each window covers the same accesses of every
+/// trace. The windows extend to the end of the longest trace, and the last may be shorter.
+pub fn windowed_footprints(traces: &[Trace], window: usize) -> Vec<WindowFootprints> {
+    assert!(window > 0, "the window must be positive");
+
+    let len = traces
+        .iter()
+        .map(|trace| trace.as_slice().len())
+        .max()
+        .unwrap_or(0);
+
+    (0..len)
+        .step_by(window)
+        .map(|start| {
+            let sets: Vec<HashSet<u32>> = traces
+                .iter()
+                .map(|trace| {
+                    let accesses = trace.as_slice();
+                    let end = accesses.len().min(start + window);
+                    accesses
+                        .get(start..end)
+                        .unwrap_or_default()
+                        .iter()
+                        .copied()
+                        .collect()
+                })
+                .collect();
+
+            WindowFootprints {
+                start,
+                individual: sets.iter().map(HashSet::len).collect(),
+                combined: sets.iter().flatten().collect::<HashSet<_>>().len(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlap() {
+        let traces = [
+            Trace::from(vec![0, 1, 2, 0]),
+            Trace::from(vec![1, 2, 3]),
+            Trace::from(vec![2, 1, 5]),
+        ];
+        let overlap = Overlap::new(&traces);
+        assert_eq!(
+            overlap,
+            Overlap {
+                shared: 2,
+                total: 5
+            }
+        );
+        assert_eq!(overlap.jaccard(), 0.4);
+        assert_eq!(Overlap::new(&[]).jaccard(), 0.0);
+
+        let jaccard = pairwise_jaccard(&traces[..2]);
+        assert_eq!(jaccard, vec![vec![1.0, 0.5], vec![0.5, 1.0]]);
+    }
+
+    #[test]
+    fn windows() {
+        let traces = [Trace::from(vec![0, 1, 0, 1, 2]), Trace::from(vec![1, 1, 5])];
+        let windows = windowed_footprints(&traces, 2);
+        assert_eq!(windows.len(), 3);
+        assert_eq!(windows[0].individual, vec![2, 1]);
+        assert_eq!(windows[0].combined, 2);
+        assert_eq!(windows[0].shared(), 1);
+        assert_eq!(windows[1].start, 2);
+        assert_eq!(windows[1].combined, 3);
+        assert_eq!(windows[2].individual, vec![1, 0]);
+    }
+}
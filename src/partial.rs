@@ -0,0 +1,247 @@
+//! Contains partial results, which combine the analyses of consecutive pieces of a trace into
+//! the analysis of the whole.
+//!
+//! A first access within a piece isn't necessarily a first access of the whole trace, so a
+//! partial result also keeps the symbols each piece first accessed, in order, and its final LRU
+//! stack. Merging a piece after another resolves its first accesses against the earlier stack.
+//! These keep the addresses which the symbols stand for, so pieces whose addresses were numbered
+//! separately by [`Trace::parse_addresses`] still merge.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Read, Write};
+
+use crate::histogram::Histogram;
+use crate::trace::{Addresses, LruStack, Trace};
+
+// identifies the file format, followed by its version
+const MAGIC: &[u8; 4] = b"SDPR";
+const VERSION: u32 = 2;
+
+/// The analysis of a piece of a trace, which can be merged with the pieces around it.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PartialResult {
+    // the counts of finite distances
+    frequencies: Vec<usize>,
+    // the addresses in order of first access
+    firsts: Vec<u64>,
+    // the addresses in order of last access, most recent first
+    stack: Vec<u64>,
+}
+
+impl From<&Trace> for PartialResult {
+    /// Analyze a piece of a trace whose symbols are addresses.
+    fn from(trace: &Trace) -> Self {
+        Self::with_addresses(trace, &Addresses::Identity)
+    }
+}
+
+// A Fenwick tree over the positions of a stack, counting those still present.
+struct Present(Vec<usize>);
+
+impl Present {
+    fn new(len: usize) -> Self {
+        // the tree of all ones, where node i covers the lowbit(i) positions ending at i
+        Self((0..=len).map(|i| i & i.wrapping_neg()).collect())
+    }
+
+    // The number of present positions before `position`.
+    fn before(&self, position: usize) -> usize {
+        let mut count = 0;
+        let mut i = position;
+        while i > 0 {
+            count += self.0[i];
+            i &= i - 1;
+        }
+        count
+    }
+
+    fn remove(&mut self, position: usize) {
+        let mut i = position + 1;
+        while i < self.0.len() {
+            self.0[i] -= 1;
+            i += i & i.wrapping_neg();
+        }
+    }
+}
+
+impl PartialResult {
+    /// Analyze a piece of a trace read by [`Trace::parse_addresses`], with the addresses its
+    /// symbols stand for.
+    pub fn with_addresses(trace: &Trace, addresses: &Addresses) -> Self {
+        let mut stack = LruStack::default();
+        let mut frequencies = Vec::new();
+        let mut firsts = Vec::new();
+        for &symbol in trace.as_slice() {
+            match stack.access(symbol) {
+                Some(distance) => {
+                    if distance >= frequencies.len() {
+                        frequencies.resize(distance + 1, 0);
+                    }
+                    frequencies[distance] += 1;
+                }
+                None => firsts.push(addresses.address(symbol)),
+            }
+        }
+
+        Self {
+            frequencies,
+            firsts,
+            stack: stack
+                .symbols()
+                .map(|symbol| addresses.address(symbol))
+                .collect(),
+        }
+    }
+
+    /// The stack distance histogram of the piece, or of the whole trace once every piece is
+    /// merged.
+    pub fn histogram(&self) -> Histogram {
+        Histogram::new(self.frequencies.clone(), self.firsts.len())
+    }
+
+    /// Combine this result with the result of the piece of trace right after it.
+    pub fn merge(&self, later: &Self) -> Self {
+        let mut frequencies = self.frequencies.clone();
+        if later.frequencies.len() > frequencies.len() {
+            frequencies.resize(later.frequencies.len(), 0);
+        }
+        for (count, &later) in frequencies.iter_mut().zip(&later.frequencies) {
+            *count += later;
+        }
+
+        // the distance of an earlier symbol is the number of distinct symbols the later piece
+        // accessed before it, plus those above it on the earlier stack which it hasn't
+        let positions: HashMap<u64, usize> = self
+            .stack
+            .iter()
+            .enumerate()
+            .map(|(position, &symbol)| (symbol, position))
+            .collect();
+        let mut present = Present::new(self.stack.len());
+        let mut firsts = self.firsts.clone();
+        for (accessed, &symbol) in later.firsts.iter().enumerate() {
+            match positions.get(&symbol) {
+                Some(&position) => {
+                    let distance = accessed + present.before(position);
+                    if distance >= frequencies.len() {
+                        frequencies.resize(distance + 1, 0);
+                    }
+                    frequencies[distance] += 1;
+                    present.remove(position);
+                }
+                None => firsts.push(symbol),
+            }
+        }
+
+        let later_symbols: HashSet<u64> = later.stack.iter().copied().collect();
+        let stack = later
+            .stack
+            .iter()
+            .chain(self.stack.iter().filter(|s| !later_symbols.contains(s)))
+            .copied()
+            .collect();
+
+        Self {
+            frequencies,
+            firsts,
+            stack,
+        }
+    }
+
+    /// Write the result in a compact binary format, for [`PartialResult::read`].
+    pub fn write<W: Write>(&self, mut w: W) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&VERSION.to_le_bytes())?;
+        w.write_all(&(self.frequencies.len() as u64).to_le_bytes())?;
+        for &count in &self.frequencies {
+            w.write_all(&(count as u64).to_le_bytes())?;
+        }
+        for symbols in [&self.firsts, &self.stack] {
+            w.write_all(&(symbols.len() as u64).to_le_bytes())?;
+            for &symbol in symbols {
+                w.write_all(&symbol.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read a result written by [`PartialResult::write`].
+    pub fn read<R: Read>(mut r: R) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+        let mut header = [0; 8];
+        r.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(invalid("not a partial result"));
+        }
+        if header[4..] != VERSION.to_le_bytes() {
+            return Err(invalid("unsupported partial result version"));
+        }
+
+        let read_u64 = |r: &mut R| -> io::Result<u64> {
+            let mut bytes = [0; 8];
+            r.read_exact(&mut bytes)?;
+            Ok(u64::from_le_bytes(bytes))
+        };
+        let len = read_u64(&mut r)?;
+        let frequencies = (0..len)
+            .map(|_| usize::try_from(read_u64(&mut r)?).map_err(|_| invalid("count is too large")))
+            .collect::<io::Result<_>>()?;
+        let read_symbols = |r: &mut R| -> io::Result<Vec<u64>> {
+            let len = read_u64(r)?;
+            (0..len).map(|_| read_u64(r)).collect()
+        };
+        let firsts = read_symbols(&mut r)?;
+        let stack = read_symbols(&mut r)?;
+
+        Ok(Self {
+            frequencies,
+            firsts,
+            stack,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_matches_whole_trace() {
+        let trace = crate::generate::uniform(600, 50, 7);
+        let symbols = trace.as_slice();
+        let pieces: Vec<_> = [0..100, 100..350, 350..351, 351..600]
+            .into_iter()
+            .map(|range| PartialResult::from(&Trace::from(symbols[range].to_vec())))
+            .collect();
+
+        let merged = pieces
+            .iter()
+            .fold(PartialResult::default(), |merged, piece| {
+                merged.merge(piece)
+            });
+        assert_eq!(merged, PartialResult::from(&trace));
+        assert_eq!(merged.histogram(), trace.stack_distance_histogram());
+        // merging is associative, so pieces can be merged in any tree
+        assert_eq!(
+            pieces[0].merge(&pieces[1].merge(&pieces[2].merge(&pieces[3]))),
+            merged
+        );
+
+        let mut bytes = Vec::new();
+        merged.write(&mut bytes).unwrap();
+        assert_eq!(PartialResult::read(bytes.as_slice()).unwrap(), merged);
+        assert!(PartialResult::read(&b"SDPR\x01\0\0\0"[..]).is_err());
+
+        // pieces read from wide addresses are numbered separately, from zero
+        let wide = |s: &str| {
+            let (trace, addresses) = Trace::parse_addresses(s, 0).unwrap();
+            PartialResult::with_addresses(&trace, &addresses)
+        };
+        let whole =
+            wide("1099511627776 2199023255552 7 2199023255552 1099511627776 7 1099511627776");
+        let pieces = wide("1099511627776 2199023255552 7")
+            .merge(&wide("2199023255552 1099511627776 7 1099511627776"));
+        assert_eq!(pieces, whole);
+        assert_eq!(whole.histogram(), Histogram::new(vec![0, 2, 2], 3));
+    }
+}
@@ -0,0 +1,323 @@
+//! Contains sampling of a running process's memory accesses with Linux perf events, for
+//! always-on locality monitoring without `perf` itself.
+//!
+//! The kernel writes each sample's data address into a ring buffer shared with this process,
+//! which drains it into an [`OnlineAnalyzer`]. Page faults can be sampled on any kernel which
+//! allows perf events; sampling loads needs a processor event with data addresses, like
+//! `mem-loads` on Intel or SPE on Arm, given by its raw encoding.
+
+use std::io;
+use std::ptr;
+use std::sync::atomic::{fence, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::online::OnlineAnalyzer;
+
+/// An event to sample the data addresses of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// Every page fault, at the faulting address.
+    PageFaults,
+    /// A processor event, by its raw encoding, sampled once every `period` occurrences.
+    Raw { config: u64, period: u64 },
+}
+
+// The layout of `struct perf_event_attr` as of version 5, from `linux/perf_event.h`.
+#[repr(C)]
+#[derive(Default)]
+struct Attr {
+    type_: u32,
+    size: u32,
+    config: u64,
+    sample_period: u64,
+    sample_type: u64,
+    read_format: u64,
+    flags: u64,
+    wakeup_events: u32,
+    bp_type: u32,
+    config1: u64,
+    config2: u64,
+    branch_sample_type: u64,
+    sample_regs_user: u64,
+    sample_stack_user: u32,
+    clockid: i32,
+    sample_regs_intr: u64,
+    aux_watermark: u32,
+    sample_max_stack: u16,
+    reserved: u16,
+}
+
+const TYPE_SOFTWARE: u32 = 1;
+const TYPE_RAW: u32 = 4;
+const COUNT_SW_PAGE_FAULTS: u64 = 2;
+const SAMPLE_ADDR: u64 = 1 << 3;
+// the bits of `flags`
+const DISABLED: u64 = 1;
+const EXCLUDE_KERNEL: u64 = 1 << 5;
+const EXCLUDE_HV: u64 = 1 << 6;
+const PRECISE_IP_SHIFT: u64 = 15;
+const RECORD_SAMPLE: u32 = 9;
+const IOC_ENABLE: libc::Ioctl = 0x2400;
+// the ring buffer's head and tail in `struct perf_event_mmap_page`
+const DATA_HEAD: usize = 1024;
+const DATA_TAIL: usize = 1032;
+// the pages of samples, which must be a power of two
+const RING_PAGES: usize = 64;
+
+/// A perf event sampling one thread, with the ring buffer of its samples.
+#[derive(Debug)]
+pub struct Sampler {
+    fd: libc::c_int,
+    // the metadata page, followed by the samples
+    ring: *mut u8,
+    page_size: usize,
+}
+
+impl Sampler {
+    /// Start sampling an event in the thread `pid`, which for a single-threaded process is all of
+    /// it. The kernel can't share samples of the threads it creates afterwards with one buffer.
+    ///
+    /// Sampling another user's process, or with `/proc/sys/kernel/perf_event_paranoid` above
+    /// two, needs `CAP_PERFMON`.
+    pub fn open(pid: libc::pid_t, event: Event) -> io::Result<Self> {
+        let mut attr = Attr {
+            size: std::mem::size_of::<Attr>() as u32,
+            sample_type: SAMPLE_ADDR,
+            flags: DISABLED | EXCLUDE_KERNEL | EXCLUDE_HV,
+            ..Attr::default()
+        };
+        match event {
+            Event::PageFaults => {
+                attr.type_ = TYPE_SOFTWARE;
+                attr.config = COUNT_SW_PAGE_FAULTS;
+                attr.sample_period = 1;
+            }
+            Event::Raw { config, period } => {
+                attr.type_ = TYPE_RAW;
+                attr.config = config;
+                attr.sample_period = period.max(1);
+                // data addresses need the sample to be taken at the access
+                attr.flags |= 2 << PRECISE_IP_SHIFT;
+            }
+        }
+
+        // SAFETY: the attributes outlive the call, and -1 is any CPU with no group or flags
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_perf_event_open,
+                ptr::addr_of!(attr),
+                pid,
+                -1 as libc::c_int,
+                -1 as libc::c_int,
+                0 as libc::c_ulong,
+            )
+        };
+        let fd = libc::c_int::try_from(fd).map_err(|_| io::Error::last_os_error())?;
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // SAFETY: sysconf has no preconditions
+        let page_size = usize::try_from(unsafe { libc::sysconf(libc::_SC_PAGESIZE) })
+            .map_err(|_| io::Error::last_os_error())?;
+        // SAFETY: a fresh shared mapping of the event, which is unmapped on drop
+        let ring = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                (RING_PAGES + 1) * page_size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                fd,
+                0,
+            )
+        };
+        if ring == libc::MAP_FAILED {
+            let error = io::Error::last_os_error();
+            // SAFETY: the event is open and owned here
+            unsafe { libc::close(fd) };
+            return Err(error);
+        }
+
+        Ok(Self {
+            fd,
+            ring: ring.cast(),
+            page_size,
+        })
+    }
+
+    /// Start counting the event.
+    pub fn enable(&self) -> io::Result<()> {
+        // SAFETY: the event is open
+        if unsafe { libc::ioctl(self.fd, IOC_ENABLE, 0) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Wait up to `timeout` for samples, returning whether the process may still be running.
+    pub fn wait(&self, timeout: Duration) -> io::Result<bool> {
+        let mut pollfd = libc::pollfd {
+            fd: self.fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout = libc::c_int::try_from(timeout.as_millis()).unwrap_or(libc::c_int::MAX);
+        // SAFETY: one valid pollfd
+        if unsafe { libc::poll(&mut pollfd, 1, timeout) } < 0 {
+            let error = io::Error::last_os_error();
+            return match error.kind() {
+                io::ErrorKind::Interrupted => Ok(true),
+                _ => Err(error),
+            };
+        }
+        // the event hangs up when the process exits
+        Ok(pollfd.revents & (libc::POLLHUP | libc::POLLERR) == 0)
+    }
+
+    /// Pass the address of every sample in the ring buffer to `f`, emptying it, and return how
+    /// many there were.
+    pub fn drain(&mut self, mut f: impl FnMut(u64)) -> usize {
+        // SAFETY: the head and tail are aligned u64s in the metadata page, and the kernel only
+        // writes the head and the samples before it
+        unsafe {
+            let head = ptr::read_volatile(self.ring.add(DATA_HEAD).cast::<u64>());
+            // read the samples only after the head which covers them
+            fence(Ordering::Acquire);
+            let mut tail = ptr::read_volatile(self.ring.add(DATA_TAIL).cast::<u64>());
+
+            let mut samples = 0;
+            while tail < head {
+                // a record is a `struct perf_event_header` and its body, which may wrap around
+                let mut header = [0; 8];
+                self.read(tail, &mut header);
+                let kind = u32::from_ne_bytes(header[..4].try_into().expect("four bytes"));
+                let size = u16::from_ne_bytes(header[6..].try_into().expect("two bytes"));
+                if size == 0 {
+                    break;
+                }
+                if kind == RECORD_SAMPLE && usize::from(size) >= 16 {
+                    let mut address = [0; 8];
+                    self.read(tail + 8, &mut address);
+                    f(u64::from_ne_bytes(address));
+                    samples += 1;
+                }
+                tail += u64::from(size);
+            }
+
+            // release the space only after reading it
+            fence(Ordering::Release);
+            ptr::write_volatile(self.ring.add(DATA_TAIL).cast::<u64>(), head);
+            samples
+        }
+    }
+
+    // Copy bytes from the ring buffer, starting at an offset into the stream of samples.
+    unsafe fn read(&self, offset: u64, out: &mut [u8]) {
+        let data_size = RING_PAGES * self.page_size;
+        let data = self.ring.add(self.page_size);
+        for (i, byte) in out.iter_mut().enumerate() {
+            #[allow(clippy::cast_possible_truncation)]
+            let at = (offset as usize + i) % data_size;
+            *byte = ptr::read_volatile(data.add(at));
+        }
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        // SAFETY: the mapping and the event are owned here and used no more
+        unsafe {
+            libc::munmap(self.ring.cast(), (RING_PAGES + 1) * self.page_size);
+            libc::close(self.fd);
+        }
+    }
+}
+
+/// Sample a process until it exits or `duration` passes, analyzing addresses coarsened by
+/// `shift` bits and calling `report` with the analysis so far every `interval`.
+pub fn monitor(
+    pid: libc::pid_t,
+    event: Event,
+    shift: u32,
+    duration: Option<Duration>,
+    interval: Duration,
+    mut report: impl FnMut(&OnlineAnalyzer) -> io::Result<()>,
+) -> io::Result<OnlineAnalyzer> {
+    let mut sampler = Sampler::open(pid, event)?;
+    let mut analyzer = OnlineAnalyzer::new();
+    let start = Instant::now();
+    let mut next_report = start + interval;
+    sampler.enable()?;
+
+    loop {
+        let now = Instant::now();
+        let deadline = duration.map_or(next_report, |d| next_report.min(start + d));
+        let running = sampler.wait(deadline.saturating_duration_since(now))?;
+        sampler.drain(|address| {
+            analyzer.access(address.checked_shr(shift).unwrap_or(0));
+        });
+
+        if !running || duration.is_some_and(|d| start.elapsed() >= d) {
+            return Ok(analyzer);
+        }
+        if Instant::now() >= next_report {
+            report(&analyzer)?;
+            next_report += interval;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_page_faults() {
+        // PERF_ATTR_SIZE_VER5
+        assert_eq!(std::mem::size_of::<Attr>(), 112);
+
+        let mut sampler = match Sampler::open(0, Event::PageFaults) {
+            Ok(sampler) => sampler,
+            // containers, CI runners, and paranoid kernels often deny perf events, with EACCES,
+            // EPERM, or ENOSYS
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::PermissionDenied | io::ErrorKind::Unsupported
+                ) =>
+            {
+                eprintln!("skipping: perf events are unavailable: {}", e);
+                return;
+            }
+            Err(e) => panic!("couldn't open a perf event: {}", e),
+        };
+        sampler.enable().unwrap();
+
+        // a fresh mapping, unlike the heap, hasn't been touched, so every page faults
+        let len = 64 * sampler.page_size;
+        // SAFETY: a new private anonymous mapping, unmapped below
+        let pages = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(pages, libc::MAP_FAILED, "{}", io::Error::last_os_error());
+        let pages = pages.cast::<u8>();
+        for offset in (0..len).step_by(sampler.page_size) {
+            // SAFETY: the offset is within the mapping
+            unsafe { ptr::write_volatile(pages.add(offset), 1) };
+        }
+
+        let mut addresses = Vec::new();
+        sampler.drain(|address| addresses.push(address));
+        let range = pages as u64..pages as u64 + len as u64;
+        // SAFETY: the mapping is no longer used
+        unsafe { libc::munmap(pages.cast(), len) };
+        assert!(addresses.iter().any(|address| range.contains(address)));
+    }
+}
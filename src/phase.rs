@@ -0,0 +1,134 @@
+//! Contains phase detection over windowed stack distance histograms.
+
+use crate::histogram::Histogram;
+use crate::metrics;
+use crate::mrc::MissRatioCurve;
+use crate::trace::Trace;
+
+/// A way of measuring how different two histograms are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Divergence {
+    /// Kullback-Leibler divergence, in bits, with add-one smoothing.
+    KullbackLeibler,
+    /// Earth mover's distance, in units of one stack distance.
+    EarthMovers,
+    /// Chi-square distance, between zero and one.
+    ChiSquare,
+}
+
+impl Divergence {
+    /// Calculate the divergence of `b` from `a`.
+    pub fn between(self, a: &Histogram, b: &Histogram) -> f64 {
+        match self {
+            Self::KullbackLeibler => metrics::kl_divergence(a, b),
+            Self::EarthMovers => metrics::earth_movers_distance(a, b),
+            Self::ChiSquare => metrics::chi_square_distance(a, b),
+        }
+    }
+}
+
+/// Calculate the stack distance histogram of each window of a trace.
+///
+/// Windows are `window` accesses long and start every `stride` accesses; a trailing window
+/// shorter than `window` is dropped. Distances are calculated within each window, so the first
+/// access to a symbol in every window is an infinity.
+pub fn windowed_histograms(trace: &Trace, window: usize, stride: usize) -> Vec<Histogram> {
+    assert!(stride > 0, "the stride must be positive");
+
+    let accesses = trace.as_slice();
+    (0..)
+        .map(|i| i * stride)
+        .take_while(|start| start + window <= accesses.len())
+        .map(|start| {
+            Trace::from(accesses[start..start + window].to_vec()).stack_distance_histogram()
+        })
+        .collect()
+}
+
+/// A contiguous range of a trace with consistent locality.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Phase {
+    /// The index of the first access of the phase.
+    pub start: usize,
+    /// The index one past the last access of the phase.
+    pub end: usize,
+    /// The miss-ratio curve of the phase on its own.
+    pub mrc: MissRatioCurve,
+}
+
+/// Split a trace into phases where the histograms of successive windows diverge.
+///
+/// A new phase starts at each window whose histogram diverges from the previous window's by
+/// more than `threshold`.
+pub fn detect_phases(
+    trace: &Trace,
+    window: usize,
+    stride: usize,
+    divergence: Divergence,
+    threshold: f64,
+) -> Vec<Phase> {
+    let histograms = windowed_histograms(trace, window, stride);
+
+    let mut boundaries = vec![0];
+    boundaries.extend(
+        histograms
+            .windows(2)
+            .enumerate()
+            .filter(|(_, pair)| divergence.between(&pair[0], &pair[1]) > threshold)
+            .map(|(i, _)| (i + 1) * stride),
+    );
+    boundaries.push(trace.as_slice().len());
+    boundaries.dedup();
+
+    boundaries
+        .windows(2)
+        .map(|range| Phase {
+            start: range[0],
+            end: range[1],
+            mrc: MissRatioCurve::from(&Trace::from(trace.as_slice()[range[0]..range[1]].to_vec())),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows() {
+        let trace = Trace::from(vec![0, 0, 1, 1, 2]);
+        assert_eq!(
+            windowed_histograms(&trace, 2, 1),
+            vec![
+                Histogram::new(vec![1], 1),
+                Histogram::new(vec![], 2),
+                Histogram::new(vec![1], 1),
+                Histogram::new(vec![], 2),
+            ]
+        );
+        assert_eq!(windowed_histograms(&trace, 2, 2).len(), 2);
+        assert_eq!(windowed_histograms(&trace, 6, 1), vec![]);
+    }
+
+    #[test]
+    fn two_phases() {
+        // a tight loop over two symbols, then a wide loop over eight
+        let mut accesses: Vec<u32> = (0..64).map(|i| i % 2).collect();
+        accesses.extend((0..64).map(|i| 10 + i % 8));
+        let trace = Trace::from(accesses);
+
+        let phases = detect_phases(&trace, 16, 16, Divergence::EarthMovers, 1.0);
+        assert_eq!(phases.len(), 2);
+        assert_eq!((phases[0].start, phases[0].end), (0, 64));
+        assert_eq!((phases[1].start, phases[1].end), (64, 128));
+        assert!(phases[0].mrc.miss_ratio(2) < phases[1].mrc.miss_ratio(2));
+    }
+
+    #[test]
+    fn one_phase() {
+        let trace = Trace::from((0..100).map(|i| i % 3).collect::<Vec<_>>());
+        let phases = detect_phases(&trace, 10, 5, Divergence::KullbackLeibler, 0.5);
+        assert_eq!(phases.len(), 1);
+        assert_eq!((phases[0].start, phases[0].end), (0, 100));
+    }
+}
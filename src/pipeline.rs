@@ -0,0 +1,264 @@
+//! Contains a pipeline of stages, each on its own thread, which analyzes a trace as it's read.
+//!
+//! Stages pass batches to the next over bounded channels, so a stage which falls behind blocks
+//! the ones before it instead of letting batches pile up in memory. A pipeline typically reads,
+//! decodes, coarsens, and analyzes a trace, and ends in a sink on the calling thread:
+//!
+//! ```no_run
+//! # use stack_distance::pipeline::Pipeline;
+//! let file = std::io::BufReader::new(std::fs::File::open("trace.txt")?);
+//! Pipeline::read(file, 16)
+//!     .decode()
+//!     .granularity(6)
+//!     .analyze(1_000_000)
+//!     .sink(|histogram| {
+//!         println!("{:?}", histogram);
+//!         Ok(())
+//!     })?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::io::{self, BufRead};
+use std::mem;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread::{self, JoinHandle};
+
+use crate::histogram::Histogram;
+use crate::online::OnlineAnalyzer;
+
+/// A pipeline whose last stage produces items of type `T`.
+#[derive(Debug)]
+pub struct Pipeline<T> {
+    output: Receiver<T>,
+    // the stages so far, in order
+    stages: Vec<JoinHandle<io::Result<()>>>,
+    // the number of items each channel holds before its sender blocks
+    capacity: usize,
+}
+
+impl Pipeline<String> {
+    /// Start a pipeline reading text, in chunks which end between accesses, with up to `capacity`
+    /// chunks waiting between each pair of stages.
+    ///
+    /// Each chunk is sent as soon as it's read, so a live feed is analyzed as it arrives.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn read<R: BufRead + Send + 'static>(mut reader: R, capacity: usize) -> Self {
+        assert!(capacity > 0, "a channel must hold an item");
+        let (sender, output) = mpsc::sync_channel(capacity);
+        let reader = thread::spawn(move || {
+            let mut chunk = Vec::new();
+            loop {
+                let buf = reader.fill_buf()?;
+                let end = buf.is_empty();
+                let len = buf.len();
+                chunk.extend_from_slice(buf);
+                reader.consume(len);
+
+                // keep an access cut off at the end of the chunk for the next one
+                let split = if end {
+                    chunk.len()
+                } else {
+                    chunk
+                        .iter()
+                        .rposition(u8::is_ascii_whitespace)
+                        .map_or(0, |i| i + 1)
+                };
+                let rest = chunk.split_off(split);
+                let full = mem::replace(&mut chunk, rest);
+                if !full.is_empty() {
+                    let text = String::from_utf8(full)
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                    if sender.send(text).is_err() {
+                        // a later stage stopped, and it reports why
+                        return Ok(());
+                    }
+                }
+                if end {
+                    return Ok(());
+                }
+            }
+        });
+
+        Self {
+            output,
+            stages: vec![reader],
+            capacity,
+        }
+    }
+
+    /// Decode whitespace-separated addresses, in decimal or in hex after `0x`.
+    pub fn decode(self) -> Pipeline<Vec<u64>> {
+        self.try_stage(|chunk| {
+            chunk
+                .split_whitespace()
+                .map(|access| {
+                    match access.strip_prefix("0x") {
+                        Some(hex) => u64::from_str_radix(hex, 16),
+                        None => access.parse(),
+                    }
+                    .map_err(|e| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("bad access `{}`: {}", access, e),
+                        )
+                    })
+                })
+                .collect::<io::Result<_>>()
+                .map(Some)
+        })
+    }
+}
+
+impl Pipeline<Vec<u64>> {
+    /// Coarsen addresses by `shift` bits, e.g. six for 64-byte cache lines.
+    pub fn granularity(self, shift: u32) -> Self {
+        self.stage(move |mut addresses| {
+            for address in &mut addresses {
+                *address = address.checked_shr(shift).unwrap_or(0);
+            }
+            Some(addresses)
+        })
+    }
+
+    /// Analyze the addresses, producing the histogram so far after every `every` of them, give or
+    /// take a batch, and once more at the end.
+    pub fn analyze(self, every: usize) -> Pipeline<Histogram> {
+        self.spawn(move |input, output| {
+            let mut analyzer = OnlineAnalyzer::new();
+            let mut since = 0;
+            for addresses in input {
+                for &address in &addresses {
+                    analyzer.access(address);
+                }
+                since += addresses.len();
+                if since >= every {
+                    since = 0;
+                    if output.send(analyzer.histogram().clone()).is_err() {
+                        return Ok(());
+                    }
+                }
+            }
+            // the end of the input may be an earlier stage failing, which the sink reports
+            let _ = output.send(analyzer.histogram().clone());
+            Ok(())
+        })
+    }
+}
+
+impl<T: Send + 'static> Pipeline<T> {
+    // Add a stage on its own thread, which receives the items of this one and sends its own.
+    fn spawn<U, F>(self, body: F) -> Pipeline<U>
+    where
+        U: Send + 'static,
+        F: FnOnce(Receiver<T>, SyncSender<U>) -> io::Result<()> + Send + 'static,
+    {
+        let (sender, output) = mpsc::sync_channel(self.capacity);
+        let input = self.output;
+        let mut stages = self.stages;
+        stages.push(thread::spawn(move || body(input, sender)));
+
+        Pipeline {
+            output,
+            stages,
+            capacity: self.capacity,
+        }
+    }
+
+    /// Add a stage on its own thread, which maps each item to one for the next stage, or drops it
+    /// for `None`.
+    pub fn stage<U, F>(self, mut f: F) -> Pipeline<U>
+    where
+        U: Send + 'static,
+        F: FnMut(T) -> Option<U> + Send + 'static,
+    {
+        self.try_stage(move |item| Ok(f(item)))
+    }
+
+    /// Add a stage like [`Pipeline::stage`] which may fail, stopping the pipeline.
+    pub fn try_stage<U, F>(self, mut f: F) -> Pipeline<U>
+    where
+        U: Send + 'static,
+        F: FnMut(T) -> io::Result<Option<U>> + Send + 'static,
+    {
+        self.spawn(move |input, output| {
+            for item in input {
+                if let Some(item) = f(item)? {
+                    if output.send(item).is_err() {
+                        return Ok(());
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Pass every item to `f` on this thread until the pipeline ends, then return the first
+    /// error of any stage, in order.
+    ///
+    /// If `f` fails, that error is returned at once, without waiting for the stages. They stop
+    /// in the background once they next send, which for the reader may be after its next read.
+    pub fn sink(self, mut f: impl FnMut(T) -> io::Result<()>) -> io::Result<()> {
+        for item in self.output.iter() {
+            // dropping the receiver and the stages' handles detaches them, so a reader blocked
+            // on a live feed can't keep this waiting
+            f(item)?;
+        }
+
+        let mut stages = Ok(());
+        for stage in self.stages {
+            let stage = stage.join().expect("the thread doesn't panic");
+            if stages.is_ok() {
+                stages = stage;
+            }
+        }
+        stages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::Trace;
+
+    #[test]
+    fn pipeline() {
+        let text = "1 0x2 1\n3\n\n2 1\n";
+        let mut histograms = Vec::new();
+        Pipeline::read(text.as_bytes(), 1)
+            .decode()
+            .granularity(0)
+            .analyze(4)
+            .sink(|histogram| {
+                histograms.push(histogram);
+                Ok(())
+            })
+            .unwrap();
+        let trace: Trace = "1 2 1 3 2 1".parse().unwrap();
+        assert_eq!(histograms.last(), Some(&trace.stack_distance_histogram()));
+
+        let result = Pipeline::read("1 x".as_bytes(), 1)
+            .decode()
+            .analyze(1)
+            .sink(|_| Ok(()));
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failing_sink_doesnt_wait_for_input() {
+        use std::io::Write;
+        use std::os::unix::net::UnixStream;
+
+        // the feed stays open, so the reader blocks after the first accesses
+        let (mut feed, socket) = UnixStream::pair().unwrap();
+        feed.write_all(b"1 2 1\n").unwrap();
+        let result = Pipeline::read(io::BufReader::new(socket), 1)
+            .decode()
+            .analyze(1)
+            .sink(|_| Err(io::Error::other("the sink failed")));
+        assert_eq!(result.unwrap_err().to_string(), "the sink failed");
+    }
+}
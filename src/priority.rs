@@ -0,0 +1,165 @@
+//! Contains generalized stack distances for priority-based replacement policies.
+//!
+//! This is Mattson's stack algorithm: any policy which evicts the lowest-priority resident, where
+//! priorities don't depend on the cache size, has the inclusion property, so a single stack
+//! gives the hits for every cache size at once. LRU is the special case where priority is
+//! recency.
+
+use std::collections::HashMap;
+
+use crate::histogram::Histogram;
+use crate::trace::Trace;
+
+/// A priority over symbols, defining a stack policy.
+pub trait Priority {
+    /// Record an access to a symbol at the given time.
+    fn access(&mut self, symbol: u32, time: usize);
+
+    /// Whether `a` should be kept over `b`, i.e. `b` is evicted first.
+    fn outranks(&self, a: u32, b: u32) -> bool;
+}
+
+/// Recency priority, for which priority distances are the usual stack distances.
+#[derive(Debug, Default)]
+pub struct Lru {
+    last: HashMap<u32, usize>,
+}
+
+impl Priority for Lru {
+    fn access(&mut self, symbol: u32, time: usize) {
+        self.last.insert(symbol, time);
+    }
+
+    fn outranks(&self, a: u32, b: u32) -> bool {
+        self.last.get(&a) > self.last.get(&b)
+    }
+}
+
+/// Frequency priority over the whole trace so far, with ties broken by recency.
+#[derive(Debug, Default)]
+pub struct Lfu {
+    counts: HashMap<u32, usize>,
+    recency: Lru,
+}
+
+impl Priority for Lfu {
+    fn access(&mut self, symbol: u32, time: usize) {
+        *self.counts.entry(symbol).or_insert(0) += 1;
+        self.recency.access(symbol, time);
+    }
+
+    fn outranks(&self, a: u32, b: u32) -> bool {
+        let (count_a, count_b) = (self.counts.get(&a), self.counts.get(&b));
+        count_a > count_b || (count_a == count_b && self.recency.outranks(a, b))
+    }
+}
+
+/// Calculate the priority distances per-operation.
+///
+/// Returns a vector where the ith entry is the depth of the accessed symbol in the priority
+/// stack, so the access hits in exactly the caches larger than it.
+pub fn priority_distance<P: Priority>(trace: &Trace, mut priority: P) -> Vec<Option<usize>> {
+    // the top of the stack is at the front
+    let mut stack: Vec<u32> = Vec::new();
+
+    trace
+        .as_slice()
+        .iter()
+        .enumerate()
+        .map(|(time, &curr)| {
+            let position = stack.iter().position(|&n| n == curr);
+            priority.access(curr, time);
+
+            // the accessed symbol goes on top, and the symbol it displaces is carried down. At
+            // each level, whichever of the carried symbol and the resident has lower priority is
+            // the one evicted from the next-larger cache, so it continues down.
+            let depth = position.unwrap_or(stack.len());
+            if depth == stack.len() {
+                stack.push(curr);
+            }
+            let mut carried = curr;
+            for (level, resident) in stack.iter_mut().take(depth + 1).enumerate() {
+                if level == 0 || *resident == curr || priority.outranks(carried, *resident) {
+                    std::mem::swap(resident, &mut carried);
+                }
+            }
+
+            position
+        })
+        .collect()
+}
+
+/// Calculate the priority distance histogram.
+///
+/// Returns a histogram of frequencies of priority distances, plus the count of infinities.
+pub fn priority_distance_histogram<P: Priority>(trace: &Trace, priority: P) -> Histogram {
+    Histogram::from(&priority_distance(trace, priority)[..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::TraceIter;
+
+    // Simulate a cache which evicts its lowest-priority resident, returning the number of hits.
+    fn simulate<P: Priority>(trace: &Trace, mut priority: P, size: usize) -> usize {
+        let mut cache: Vec<u32> = Vec::new();
+        let mut hits = 0;
+        for (time, &curr) in trace.as_slice().iter().enumerate() {
+            priority.access(curr, time);
+            if cache.contains(&curr) {
+                hits += 1;
+            } else {
+                if cache.len() == size {
+                    let victim = (0..cache.len())
+                        .reduce(|a, b| {
+                            if priority.outranks(cache[a], cache[b]) {
+                                b
+                            } else {
+                                a
+                            }
+                        })
+                        .expect("the cache is full");
+                    cache.remove(victim);
+                }
+                cache.push(curr);
+            }
+        }
+        hits
+    }
+
+    #[test]
+    fn lru_is_stack_distance() {
+        for trace in TraceIter::new(6) {
+            assert_eq!(
+                priority_distance_histogram(&trace, Lru::default()),
+                trace.stack_distance_histogram()
+            );
+        }
+    }
+
+    #[test]
+    fn lfu_distances() {
+        // A is accessed three times, so it stays above the more recent B and C
+        assert_eq!(
+            priority_distance(&Trace::from(vec![0, 0, 0, 1, 2, 1, 0]), Lfu::default()),
+            vec![None, Some(0), Some(0), None, None, Some(2), Some(1)]
+        );
+    }
+
+    #[test]
+    fn lfu_matches_simulation() {
+        for trace in TraceIter::new(7) {
+            let histogram = priority_distance_histogram(&trace, Lfu::default());
+            for size in 1..=4 {
+                assert_eq!(
+                    histogram.frequencies().iter().take(size).sum::<usize>(),
+                    simulate(&trace, Lfu::default(), size),
+                    "{} at size {}",
+                    trace,
+                    size
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! Contains R bindings, which return the analyses as data frames.
+//!
+//! The R package's `src/rust` crate depends on this one with the `r` feature and links the
+//! static library; rextendr then generates the R wrappers, like
+//! `stack_distance_histogram(c(1, 2, 1))`.
+
+use std::collections::HashMap;
+
+use extendr_api::prelude::*;
+
+use crate::histogram::Histogram;
+use crate::mrc::MissRatioCurve;
+use crate::trace::LruStack;
+
+// Compute the histogram of a trace of R numbers, which can be any values, like addresses.
+fn histogram_of(trace: &[f64]) -> Histogram {
+    let mut ids = HashMap::new();
+    let mut stack = LruStack::default();
+    let mut histogram = Histogram::default();
+    for &access in trace {
+        let next = u32::try_from(ids.len()).expect("fewer than 2^32 distinct accesses");
+        // compare bits, so every value is a symbol, even NaN
+        let id = *ids.entry(access.to_bits()).or_insert(next);
+        histogram.record(stack.access(id));
+    }
+    histogram
+}
+
+/// The stack distance histogram of a trace, with an infinite distance for first accesses.
+/// @param trace A vector of accesses.
+/// @return A data frame with columns `distance` and `count`.
+/// @export
+#[extendr]
+#[allow(clippy::cast_precision_loss, clippy::needless_pass_by_value)]
+fn stack_distance_histogram(trace: Vec<f64>) -> Robj {
+    let histogram = histogram_of(&trace);
+    let distances: Vec<f64> = (0..histogram.frequencies().len())
+        .map(|distance| distance as f64)
+        .chain([f64::INFINITY])
+        .collect();
+    let counts: Vec<f64> = histogram
+        .frequencies()
+        .iter()
+        .chain([&histogram.infinities()])
+        .map(|&count| count as f64)
+        .collect();
+    data_frame!(distance = distances, count = counts)
+}
+
+/// The miss ratio curve of LRU caches on a trace.
+/// @param trace A vector of accesses.
+/// @return A data frame with columns `size` and `miss_ratio`, from size zero up to the size after
+/// which the curve is flat.
+/// @export
+#[extendr]
+#[allow(clippy::cast_precision_loss, clippy::needless_pass_by_value)]
+fn miss_ratio_curve(trace: Vec<f64>) -> Robj {
+    let mrc = MissRatioCurve::from_histogram(&histogram_of(&trace));
+    let sizes: Vec<f64> = (0..mrc.ratios().len()).map(|size| size as f64).collect();
+    data_frame!(size = sizes, miss_ratio = mrc.ratios().to_vec())
+}
+
+extendr_module! {
+    mod stackdistance;
+    fn stack_distance_histogram;
+    fn miss_ratio_curve;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_of_numbers() {
+        let trace = [4096.0, 1e12, 4096.0, -1.0, 1e12, 4096.0];
+        assert_eq!(histogram_of(&trace), Histogram::new(vec![0, 1, 2], 3));
+    }
+}
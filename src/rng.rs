@@ -0,0 +1,88 @@
+//! Contains a small seedable pseudorandom number generator.
+//!
+//! This is SplitMix64. It's not cryptographic, but it's fast and, unlike external generators,
+//! produces the same sequence on every platform and version, so seeded results are reproducible.
+
+/// A seedable pseudorandom number generator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Create a generator from a seed.
+    pub const fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Generate a uniformly random `u64`.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        mix(self.state)
+    }
+
+    /// Generate a uniformly random integer in `[0, n)`, which must be non-empty.
+    pub fn below(&mut self, n: u64) -> u64 {
+        assert!(n > 0, "the range must be non-empty");
+        // the high bits of a widening multiply, which is unbiased enough for our purposes
+        ((u128::from(self.next_u64()) * u128::from(n)) >> 64) as u64
+    }
+
+    /// Generate a uniformly random float in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        #[allow(clippy::cast_precision_loss)]
+        let f = (self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64;
+        f
+    }
+
+    /// Generate a standard normal float, with mean zero and variance one.
+    pub fn next_normal(&mut self) -> f64 {
+        // Box-Muller, with 1 - u in (0, 1] so the log is finite
+        let u = 1.0 - self.next_f64();
+        let v = self.next_f64();
+        (-2.0 * u.ln()).sqrt() * (std::f64::consts::TAU * v).cos()
+    }
+}
+
+/// Scramble the bits of a `u64`, e.g. to hash a symbol.
+pub(crate) const fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reproducible() {
+        let (mut a, mut b) = (Rng::new(7), Rng::new(7));
+        for _ in 0..5 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+        assert_ne!(Rng::new(8).next_u64(), Rng::new(7).next_u64());
+    }
+
+    #[test]
+    fn ranges() {
+        let mut rng = Rng::new(1);
+        let mut seen = [false; 6];
+        for _ in 0..1000 {
+            seen[rng.below(6) as usize] = true;
+            let f = rng.next_f64();
+            assert!((0.0..1.0).contains(&f));
+        }
+        assert!(seen.iter().all(|&s| s));
+    }
+
+    #[test]
+    fn normal() {
+        let mut rng = Rng::new(2);
+        let samples: Vec<_> = (0..10_000).map(|_| rng.next_normal()).collect();
+        let mean = samples.iter().sum::<f64>() / 10_000.0;
+        let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / 10_000.0;
+        assert!(mean.abs() < 0.05);
+        assert!((variance - 1.0).abs() < 0.05);
+    }
+}
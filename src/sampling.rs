@@ -0,0 +1,197 @@
+//! Contains approximate miss-ratio curves from spatially sampled traces.
+//!
+//! This is SHARDS: a symbol is sampled if its hash falls below a threshold, so every access to a
+//! sampled symbol is kept. Stack distances in the sampled trace then estimate the full distances
+//! scaled down by the sampling rate.
+
+use tracing::debug;
+
+use crate::histogram::Histogram;
+use crate::mrc::MissRatioCurve;
+use crate::rng::{self, Rng};
+use crate::trace::Trace;
+
+// hashes are compared against the rate in this many buckets
+const BUCKETS: u64 = 1 << 24;
+
+/// Whether a symbol is sampled at the given rate.
+pub(crate) fn is_sampled(symbol: u32, rate: f64) -> bool {
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    let threshold = (rate * BUCKETS as f64) as u64;
+    rng::mix(u64::from(symbol)) % BUCKETS < threshold
+}
+
+/// The scaled stack distances of a spatially sampled trace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sampled {
+    distances: Vec<Option<usize>>,
+    rate: f64,
+}
+
+impl Sampled {
+    /// Sample the symbols of a trace at the given rate, between zero and one.
+    pub fn new(trace: &Trace, rate: f64) -> Self {
+        let sampled: Vec<_> = trace
+            .as_slice()
+            .iter()
+            .copied()
+            .filter(|&n| is_sampled(n, rate))
+            .collect();
+        debug!(
+            rate,
+            sampled = sampled.len(),
+            accesses = trace.len(),
+            "spatially sampled trace"
+        );
+        Self::from_sampled(&Trace::from(sampled), rate)
+    }
+
+    // Scale the distances of a trace which was already sampled at the given rate.
+    pub(crate) fn from_sampled(sampled: &Trace, rate: f64) -> Self {
+        #[allow(
+            clippy::cast_precision_loss,
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss
+        )]
+        let distances = sampled
+            .stack_distance()
+            .into_iter()
+            .map(|d| d.map(|d| (d as f64 / rate) as usize))
+            .collect();
+
+        Self { distances, rate }
+    }
+
+    /// The sampling rate.
+    pub const fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// The number of sampled accesses.
+    pub fn len(&self) -> usize {
+        self.distances.len()
+    }
+
+    /// Whether no accesses were sampled.
+    pub fn is_empty(&self) -> bool {
+        self.distances.is_empty()
+    }
+
+    /// The estimated stack distance histogram, normalized to the sampled accesses.
+    pub fn histogram(&self) -> Histogram {
+        Histogram::from(&self.distances[..])
+    }
+
+    /// The estimated miss-ratio curve.
+    pub fn miss_ratio_curve(&self) -> MissRatioCurve {
+        MissRatioCurve::from_histogram(&self.histogram())
+    }
+
+    /// Estimate a confidence band for the miss-ratio curve by bootstrap resampling.
+    ///
+    /// Each of `resamples` resamples draws the sampled accesses with replacement, and the band
+    /// at each cache size is the central `confidence` interval of the resampled miss ratios.
+    pub fn bootstrap(&self, resamples: usize, confidence: f64, seed: u64) -> ConfidenceBand {
+        let estimate = self.miss_ratio_curve();
+        let sizes = estimate.ratios().len();
+        let mut rng = Rng::new(seed);
+
+        // the miss ratios at each size, across resamples
+        let mut ratios = vec![Vec::with_capacity(resamples); sizes];
+        for _ in 0..resamples {
+            let resampled: Vec<_> = (0..self.len())
+                .map(|_| self.distances[rng.below(self.len() as u64) as usize])
+                .collect();
+            let mrc = MissRatioCurve::from_histogram(&Histogram::from(&resampled[..]));
+            for (size, ratios) in ratios.iter_mut().enumerate() {
+                ratios.push(mrc.miss_ratio(size));
+            }
+        }
+
+        let tail = (1.0 - confidence) / 2.0;
+        let percentile = |sorted: &[f64], p: f64| {
+            #[allow(
+                clippy::cast_precision_loss,
+                clippy::cast_possible_truncation,
+                clippy::cast_sign_loss
+            )]
+            let i = ((sorted.len() - 1) as f64 * p).round() as usize;
+            sorted[i]
+        };
+
+        let (lower, upper) = ratios
+            .into_iter()
+            .zip(estimate.ratios())
+            .map(|(mut ratios, &estimate)| {
+                if ratios.is_empty() {
+                    return (estimate, estimate);
+                }
+                ratios.sort_unstable_by(f64::total_cmp);
+                (percentile(&ratios, tail), percentile(&ratios, 1.0 - tail))
+            })
+            .unzip();
+
+        ConfidenceBand {
+            estimate,
+            lower,
+            upper,
+        }
+    }
+}
+
+/// A miss-ratio curve with a confidence band around it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfidenceBand {
+    /// The point estimate of the curve.
+    pub estimate: MissRatioCurve,
+    /// The lower bound of the miss ratio at each size covered by the estimate.
+    pub lower: Vec<f64>,
+    /// The upper bound of the miss ratio at each size covered by the estimate.
+    pub upper: Vec<f64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate;
+
+    #[test]
+    fn full_rate_is_exact() {
+        let trace = generate::uniform(500, 40, 3);
+        assert_eq!(
+            Sampled::new(&trace, 1.0).miss_ratio_curve(),
+            MissRatioCurve::from(&trace)
+        );
+        assert!(Sampled::new(&trace, 0.0).is_empty());
+    }
+
+    #[test]
+    fn sampling_approximates() {
+        let trace = generate::uniform(20_000, 1000, 4);
+        let exact = MissRatioCurve::from(&trace);
+        let sampled = Sampled::new(&trace, 0.1);
+        assert!(sampled.len() < trace.as_slice().len() / 5);
+        for size in [100, 500, 900] {
+            assert!(
+                (sampled.miss_ratio_curve().miss_ratio(size) - exact.miss_ratio(size)).abs() < 0.1
+            );
+        }
+    }
+
+    #[test]
+    fn bootstrap_band() {
+        let trace = generate::uniform(5000, 200, 5);
+        let sampled = Sampled::new(&trace, 0.2);
+        let band = sampled.bootstrap(50, 0.9, 1);
+        for (size, &estimate) in band.estimate.ratios().iter().enumerate() {
+            assert!(band.lower[size] <= estimate + 1e-12);
+            assert!(estimate <= band.upper[size] + 1e-12);
+        }
+        assert!(band.lower.iter().zip(&band.upper).any(|(l, u)| l < u));
+        assert_eq!(band, sampled.bootstrap(50, 0.9, 1));
+    }
+}
@@ -0,0 +1,295 @@
+//! Contains sharded computation of stack distance histograms, for huge traces on many cores.
+//!
+//! Symbols are partitioned by hash across threads. The distance of a reuse is the number of
+//! accesses between it and the previous access of its symbol which are the first of theirs in
+//! between, so it depends on other shards only through the positions of their previous accesses.
+//! Each shard finds those links for its own symbols, a shared index counts them over the whole
+//! trace, and each shard then computes the distances of its own reuses. The shards' histograms
+//! are summed in order, so the result is exact and the same for any number of shards.
+//!
+//! Each shard visits only its own accesses, and the positions are bucketed by shard and the
+//! index built a run of positions per thread, so the total work doesn't grow with the number of
+//! shards and almost none of it is left on one thread.
+
+use std::collections::HashMap;
+use std::mem;
+use std::thread;
+
+use crate::histogram::Histogram;
+use crate::trace::Trace;
+
+/// The shard of `shards` which a symbol belongs to.
+pub fn shard_of(symbol: u32, shards: usize) -> usize {
+    // Fibonacci hashing spreads out symbols which are consecutive, like addresses
+    #[allow(clippy::cast_possible_truncation)]
+    let hash = (u64::from(symbol).wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 32) as usize;
+    hash % shards
+}
+
+// A wavelet matrix, for counting values below a bound in a range of positions.
+struct Wavelet {
+    // the bits of each level, most significant first, with the ones before each word
+    levels: Vec<(Vec<u64>, Vec<usize>)>,
+    // the number of zeros in each level
+    zeros: Vec<usize>,
+}
+
+impl Wavelet {
+    // Build the matrix on `threads` threads, each taking a run of positions at every level.
+    fn new(mut values: Vec<usize>, threads: usize) -> Self {
+        let len = values.len();
+        let max = values.iter().copied().max().unwrap_or(0);
+        let bits = (usize::BITS - max.leading_zeros()).max(1);
+        // runs of whole words, so each thread has its own
+        let words_per_run = len.div_ceil(64 * threads).max(1);
+        let run = 64 * words_per_run;
+
+        let mut levels = Vec::new();
+        let mut zeros = Vec::new();
+        for bit in (0..bits).rev() {
+            let mut words = vec![0; len / 64 + 1];
+            let ones: Vec<usize> = thread::scope(|scope| {
+                let handles: Vec<_> = values
+                    .chunks(run)
+                    .zip(words.chunks_mut(words_per_run))
+                    .map(|(values, words)| {
+                        scope.spawn(move || {
+                            for (i, value) in values.iter().enumerate() {
+                                words[i / 64] |= ((value >> bit & 1) as u64) << (i % 64);
+                            }
+                            words.iter().map(|word| word.count_ones() as usize).sum()
+                        })
+                    })
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|handle| handle.join().expect("the thread doesn't panic"))
+                    .collect()
+            });
+            let level_zeros = len - ones.iter().sum::<usize>();
+
+            // each run's ones come before those of the later runs, and its zeros likewise
+            let mut ranks = vec![0; words.len()];
+            let mut next = vec![0; len];
+            thread::scope(|scope| {
+                let (mut low, mut high) = next.split_at_mut(level_zeros);
+                let mut ones_before = 0;
+                let mut handles = Vec::new();
+                for (i, (words, ranks)) in words
+                    .chunks(words_per_run)
+                    .zip(ranks.chunks_mut(words_per_run))
+                    .enumerate()
+                {
+                    let values = &values[(i * run).min(len)..((i + 1) * run).min(len)];
+                    let run_ones = ones.get(i).copied().unwrap_or(0);
+                    let (run_low, rest) = mem::take(&mut low).split_at_mut(values.len() - run_ones);
+                    low = rest;
+                    let (run_high, rest) = mem::take(&mut high).split_at_mut(run_ones);
+                    high = rest;
+                    let before = ones_before;
+                    ones_before += run_ones;
+                    handles.push(scope.spawn(move || {
+                        let mut ones = before;
+                        for (rank, word) in ranks.iter_mut().zip(words) {
+                            *rank = ones;
+                            ones += word.count_ones() as usize;
+                        }
+                        // the next level orders positions by this bit, stably
+                        let (mut zero, mut one) = (0, 0);
+                        for &value in values {
+                            if value >> bit & 1 == 0 {
+                                run_low[zero] = value;
+                                zero += 1;
+                            } else {
+                                run_high[one] = value;
+                                one += 1;
+                            }
+                        }
+                    }));
+                }
+                for handle in handles {
+                    handle.join().expect("the thread doesn't panic");
+                }
+            });
+            zeros.push(level_zeros);
+            values = next;
+            levels.push((words, ranks));
+        }
+
+        Self { levels, zeros }
+    }
+
+    // The number of ones in a level before a position.
+    fn rank(&self, level: usize, position: usize) -> usize {
+        let (words, ranks) = &self.levels[level];
+        let mask = (1 << (position % 64)) - 1;
+        ranks[position / 64] + (words[position / 64] & mask).count_ones() as usize
+    }
+
+    // The number of values below `bound` at positions in `start..end`.
+    fn count_below(&self, mut start: usize, mut end: usize, bound: usize) -> usize {
+        let bits = self.levels.len();
+        if bits < usize::BITS as usize && bound >> bits > 0 {
+            return end - start;
+        }
+        let mut count = 0;
+        for level in 0..bits {
+            let (start_ones, end_ones) = (self.rank(level, start), self.rank(level, end));
+            if bound >> (bits - 1 - level) & 1 == 1 {
+                // every value with a zero here is below the bound
+                count += (end - end_ones) - (start - start_ones);
+                start = self.zeros[level] + start_ones;
+                end = self.zeros[level] + end_ones;
+            } else {
+                start -= start_ones;
+                end -= end_ones;
+            }
+        }
+        count
+    }
+}
+
+/// Calculate the stack distance histogram of a trace with `shards` threads.
+///
+/// # Panics
+///
+/// Panics if `shards` is zero.
+pub fn histogram(trace: &Trace, shards: usize) -> Histogram {
+    assert!(shards > 0, "there must be a shard");
+    let symbols = trace.as_slice();
+    // the trace is split into a run of positions for each shard
+    let run = symbols.len().div_ceil(shards).max(1);
+
+    // bucket the positions of each run by shard, so each shard visits only its own accesses
+    let buckets: Vec<Vec<Vec<usize>>> = thread::scope(|scope| {
+        let handles: Vec<_> = symbols
+            .chunks(run)
+            .enumerate()
+            .map(|(i, accesses)| {
+                scope.spawn(move || {
+                    let mut bucket = vec![Vec::new(); shards];
+                    for (position, &symbol) in (i * run..).zip(accesses) {
+                        bucket[shard_of(symbol, shards)].push(position);
+                    }
+                    bucket
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("the thread doesn't panic"))
+            .collect()
+    });
+    let buckets = &buckets;
+    // the positions of a shard's accesses, in order
+    let positions = move |shard: usize| buckets.iter().flat_map(move |bucket| &bucket[shard]);
+
+    // each shard links its accesses to the previous ones of their symbols, as one plus the
+    // position of the previous access, or zero for a first access
+    let links: Vec<Vec<usize>> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..shards)
+            .map(|shard| {
+                scope.spawn(move || {
+                    let mut last = HashMap::new();
+                    positions(shard)
+                        .map(|&position| {
+                            last.insert(symbols[position], position)
+                                .map_or(0, |previous| previous + 1)
+                        })
+                        .collect()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("the thread doesn't panic"))
+            .collect()
+    });
+
+    // gather the links in order of position, each run on its own thread
+    let mut gathered = vec![0; symbols.len()];
+    thread::scope(|scope| {
+        let mut linked = vec![0; shards];
+        let mut handles = Vec::new();
+        for (bucket, gathered) in buckets.iter().zip(gathered.chunks_mut(run)) {
+            let start = linked.clone();
+            for (linked, positions) in linked.iter_mut().zip(bucket) {
+                *linked += positions.len();
+            }
+            let links = &links;
+            handles.push(scope.spawn(move || {
+                for ((positions, links), start) in bucket.iter().zip(links).zip(start) {
+                    for (&position, &link) in positions.iter().zip(&links[start..]) {
+                        gathered[position % run] = link;
+                    }
+                }
+            }));
+        }
+        for handle in handles {
+            handle.join().expect("the thread doesn't panic");
+        }
+    });
+
+    // the distance of a reuse counts the accesses since the previous one whose own previous
+    // access came before it, or which are first accesses
+    let index = Wavelet::new(gathered, shards);
+    let index = &index;
+    let links = &links;
+    let counts: Vec<(Vec<usize>, usize)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..shards)
+            .map(|shard| {
+                scope.spawn(move || {
+                    let mut frequencies = Vec::new();
+                    let mut infinities = 0;
+                    for (&position, &link) in positions(shard).zip(&links[shard]) {
+                        if link == 0 {
+                            infinities += 1;
+                            continue;
+                        }
+                        let distance = index.count_below(link, position, link + 1);
+                        if distance >= frequencies.len() {
+                            frequencies.resize(distance + 1, 0);
+                        }
+                        frequencies[distance] += 1;
+                    }
+                    (frequencies, infinities)
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("the thread doesn't panic"))
+            .collect()
+    });
+
+    let mut frequencies = Vec::new();
+    let mut infinities = 0;
+    for (shard_frequencies, shard_infinities) in counts {
+        if shard_frequencies.len() > frequencies.len() {
+            frequencies.resize(shard_frequencies.len(), 0);
+        }
+        for (count, shard_count) in frequencies.iter_mut().zip(shard_frequencies) {
+            *count += shard_count;
+        }
+        infinities += shard_infinities;
+    }
+    Histogram::new(frequencies, infinities)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_unsharded() {
+        let trace = crate::generate::uniform(2000, 150, 11);
+        for shards in [1, 2, 3, 4, 7, 64] {
+            assert_eq!(histogram(&trace, shards), trace.stack_distance_histogram());
+        }
+        let trace: Trace = "1 2 1 3 2 1".parse().unwrap();
+        for shards in [3, 4, 8] {
+            assert_eq!(histogram(&trace, shards), trace.stack_distance_histogram());
+        }
+        assert_eq!(histogram(&Trace::from(Vec::new()), 2), Histogram::default());
+    }
+}
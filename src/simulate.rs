@@ -0,0 +1,850 @@
+//! Contains cache simulations for comparing replacement policies.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::thread;
+
+use tracing::debug;
+
+use crate::mrc::MissRatioCurve;
+use crate::trace::Trace;
+
+/// A cache replacement policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Policy {
+    Lru,
+    Fifo,
+    Arc,
+    Clock,
+    /// A policy from outside this crate, by the name it was [registered](register) under.
+    Custom(&'static str),
+}
+
+impl FromStr for Policy {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "lru" => Ok(Self::Lru),
+            "fifo" => Ok(Self::Fifo),
+            "arc" => Ok(Self::Arc),
+            "clock" => Ok(Self::Clock),
+            _ => custom_policies()
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(s))
+                .map(|&(name, _)| Self::Custom(name))
+                .ok_or_else(|| ParseError::Policy(s.to_string())),
+        }
+    }
+}
+
+impl Display for Policy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Lru => "lru",
+            Self::Fifo => "fifo",
+            Self::Arc => "arc",
+            Self::Clock => "clock",
+            Self::Custom(name) => name,
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A replacement policy which other crates can implement, to simulate it like the built-in
+/// ones.
+///
+/// The simulator tracks which symbols are resident and calls the hooks as each access is
+/// processed: first [`on_access`](Self::on_access), and then, on a miss to a full cache,
+/// [`victim`](Self::victim) and [`on_evict`](Self::on_evict), and finally, on any miss,
+/// [`on_insert`](Self::on_insert).
+pub trait ReplacementPolicy {
+    /// Note an access to a symbol, and whether it was resident.
+    fn on_access(&mut self, symbol: u32, hit: bool);
+
+    /// Choose a resident symbol to evict, to make room for a missed one.
+    fn victim(&mut self) -> u32;
+
+    /// Note that a symbol was evicted.
+    fn on_evict(&mut self, _symbol: u32) {}
+
+    /// Note that a missed symbol was inserted.
+    fn on_insert(&mut self, _symbol: u32) {}
+}
+
+/// Creates a policy for a cache of the given size, which is never zero.
+pub type PolicyFactory = fn(usize) -> Box<dyn ReplacementPolicy>;
+
+static CUSTOM_POLICIES: Mutex<Vec<(&'static str, PolicyFactory)>> = Mutex::new(Vec::new());
+
+fn custom_policies() -> std::sync::MutexGuard<'static, Vec<(&'static str, PolicyFactory)>> {
+    CUSTOM_POLICIES.lock().expect("the lock isn't poisoned")
+}
+
+/// Register a policy under a name, returning the [`Policy`] which simulates it. It can then also
+/// be parsed from its name, e.g. by `--policies`. Registering a name again replaces its policy.
+///
+/// # Panics
+///
+/// Panics if the name is a built-in policy's.
+pub fn register(name: &'static str, factory: PolicyFactory) -> Policy {
+    assert!(
+        !matches!(
+            name.parse(),
+            Ok(Policy::Lru | Policy::Fifo | Policy::Arc | Policy::Clock)
+        ),
+        "`{}` is a built-in policy",
+        name
+    );
+    let mut policies = custom_policies();
+    policies.retain(|&(registered, _)| registered != name);
+    policies.push((name, factory));
+    Policy::Custom(name)
+}
+
+/// An error parsing a policy name or a cache size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    Policy(String),
+    Size(String),
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Policy(s) => write!(f, "unknown policy `{}`", s),
+            Self::Size(s) => write!(f, "invalid cache size `{}`", s),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parse a cache size, with an optional binary `k`, `m`, or `g` suffix.
+///
+/// For example, `4k` is 4096.
+pub fn parse_size(s: &str) -> Result<usize, ParseError> {
+    let lower = s.trim().to_ascii_lowercase();
+    let (digits, multiplier) = match lower.chars().last() {
+        Some('k') => (&lower[..lower.len() - 1], 1 << 10),
+        Some('m') => (&lower[..lower.len() - 1], 1 << 20),
+        Some('g') => (&lower[..lower.len() - 1], 1 << 30),
+        _ => (lower.as_str(), 1),
+    };
+
+    digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| ParseError::Size(s.to_string()))
+}
+
+/// Parse a grid of cache sizes: a comma-separated list like `1k,4k`, or a range like `1k..1g`
+/// with an optional step after a slash.
+///
+/// The step is `log` for doubling sizes, like `1k..1g/log`, or else a size to add, like
+/// `0..64/8`; it's one by default.
+pub fn parse_size_grid(s: &str) -> Result<Vec<usize>, ParseError> {
+    let Some((start, rest)) = s.split_once("..") else {
+        return s.split(',').map(parse_size).collect();
+    };
+    let (end, step) = rest.split_once('/').unwrap_or((rest, "1"));
+    let (start, end) = (parse_size(start)?, parse_size(end)?);
+    let error = || ParseError::Size(s.to_string());
+
+    let next: Box<dyn Fn(usize) -> Option<usize>> = if step.trim().eq_ignore_ascii_case("log") {
+        if start == 0 {
+            return Err(error());
+        }
+        Box::new(|size: usize| size.checked_mul(2))
+    } else {
+        let step = parse_size(step)?;
+        if step == 0 {
+            return Err(error());
+        }
+        Box::new(move |size: usize| size.checked_add(step))
+    };
+
+    let grid: Vec<_> = std::iter::successors(Some(start), |&size| next(size))
+        .take_while(|&size| size <= end)
+        .collect();
+    if grid.is_empty() {
+        return Err(error());
+    }
+    Ok(grid)
+}
+
+/// A cache which can be driven by a trace.
+trait Cache {
+    /// Access a symbol, returning whether it was a hit.
+    fn access(&mut self, symbol: u32) -> bool;
+}
+
+/// A list of distinct symbols ordered from least to most recently inserted.
+#[derive(Default)]
+struct OrderedSet {
+    stamps: HashMap<u32, u64>,
+    order: BTreeMap<u64, u32>,
+    clock: u64,
+}
+
+impl OrderedSet {
+    fn len(&self) -> usize {
+        self.stamps.len()
+    }
+
+    fn contains(&self, symbol: u32) -> bool {
+        self.stamps.contains_key(&symbol)
+    }
+
+    // Insert a symbol at the most-recent end, moving it there if it's already present.
+    fn push(&mut self, symbol: u32) {
+        self.remove(symbol);
+        self.clock += 1;
+        self.stamps.insert(symbol, self.clock);
+        self.order.insert(self.clock, symbol);
+    }
+
+    fn remove(&mut self, symbol: u32) -> bool {
+        self.stamps
+            .remove(&symbol)
+            .map(|stamp| self.order.remove(&stamp))
+            .is_some()
+    }
+
+    // Remove and return the least-recent symbol.
+    fn pop_oldest(&mut self) -> Option<u32> {
+        let (_, symbol) = self.order.pop_first()?;
+        self.stamps.remove(&symbol);
+        Some(symbol)
+    }
+}
+
+struct Lru {
+    size: usize,
+    stack: OrderedSet,
+}
+
+impl Cache for Lru {
+    fn access(&mut self, symbol: u32) -> bool {
+        let hit = self.stack.contains(symbol);
+        if !hit && self.stack.len() == self.size {
+            self.stack.pop_oldest();
+        }
+        self.stack.push(symbol);
+        hit
+    }
+}
+
+struct Fifo {
+    size: usize,
+    queue: OrderedSet,
+}
+
+impl Cache for Fifo {
+    fn access(&mut self, symbol: u32) -> bool {
+        if self.queue.contains(symbol) {
+            return true;
+        }
+        if self.queue.len() == self.size {
+            self.queue.pop_oldest();
+        }
+        self.queue.push(symbol);
+        false
+    }
+}
+
+struct Clock {
+    slots: Vec<(u32, bool)>,
+    index: HashMap<u32, usize>,
+    hand: usize,
+    size: usize,
+}
+
+impl Cache for Clock {
+    fn access(&mut self, symbol: u32) -> bool {
+        if let Some(&i) = self.index.get(&symbol) {
+            self.slots[i].1 = true;
+            return true;
+        }
+
+        if self.slots.len() < self.size {
+            self.index.insert(symbol, self.slots.len());
+            self.slots.push((symbol, false));
+            return false;
+        }
+
+        // sweep until we find an unreferenced slot, clearing reference bits as we go
+        while self.slots[self.hand].1 {
+            self.slots[self.hand].1 = false;
+            self.hand = (self.hand + 1) % self.size;
+        }
+
+        self.index.remove(&self.slots[self.hand].0);
+        self.index.insert(symbol, self.hand);
+        self.slots[self.hand] = (symbol, false);
+        self.hand = (self.hand + 1) % self.size;
+        false
+    }
+}
+
+/// Megiddo and Modha's adaptive replacement cache.
+#[derive(Default)]
+struct Arc {
+    size: usize,
+    target: usize,
+    t1: OrderedSet,
+    t2: OrderedSet,
+    b1: OrderedSet,
+    b2: OrderedSet,
+}
+
+impl Arc {
+    // Evict from either t1 or t2 into its ghost list, depending on the target size of t1.
+    fn replace(&mut self, in_b2: bool) {
+        let t1_len = self.t1.len();
+        if t1_len >= 1 && ((in_b2 && t1_len == self.target) || t1_len > self.target) {
+            if let Some(evicted) = self.t1.pop_oldest() {
+                self.b1.push(evicted);
+            }
+        } else if let Some(evicted) = self.t2.pop_oldest() {
+            self.b2.push(evicted);
+        }
+    }
+}
+
+impl Cache for Arc {
+    fn access(&mut self, symbol: u32) -> bool {
+        if self.t1.remove(symbol) || self.t2.contains(symbol) {
+            self.t2.push(symbol);
+            return true;
+        }
+
+        if self.b1.contains(symbol) {
+            let delta = (self.b2.len() / self.b1.len()).max(1);
+            self.target = (self.target + delta).min(self.size);
+            self.replace(false);
+            self.b1.remove(symbol);
+            self.t2.push(symbol);
+        } else if self.b2.contains(symbol) {
+            let delta = (self.b1.len() / self.b2.len()).max(1);
+            self.target = self.target.saturating_sub(delta);
+            self.replace(true);
+            self.b2.remove(symbol);
+            self.t2.push(symbol);
+        } else {
+            let l1 = self.t1.len() + self.b1.len();
+            let total = l1 + self.t2.len() + self.b2.len();
+            if l1 == self.size {
+                if self.t1.len() < self.size {
+                    self.b1.pop_oldest();
+                    self.replace(false);
+                } else {
+                    self.t1.pop_oldest();
+                }
+            } else if total >= self.size {
+                if total == 2 * self.size {
+                    self.b2.pop_oldest();
+                }
+                self.replace(false);
+            }
+            self.t1.push(symbol);
+        }
+        false
+    }
+}
+
+/// A cache whose evictions a registered policy chooses.
+struct Custom {
+    size: usize,
+    resident: HashSet<u32>,
+    policy: Box<dyn ReplacementPolicy>,
+}
+
+impl Cache for Custom {
+    fn access(&mut self, symbol: u32) -> bool {
+        let hit = self.resident.contains(&symbol);
+        self.policy.on_access(symbol, hit);
+        if hit {
+            return true;
+        }
+
+        if self.resident.len() == self.size {
+            let victim = self.policy.victim();
+            assert!(
+                self.resident.remove(&victim),
+                "the policy evicts a resident symbol"
+            );
+            self.policy.on_evict(victim);
+        }
+        self.resident.insert(symbol);
+        self.policy.on_insert(symbol);
+        false
+    }
+}
+
+/// A cache which never holds anything, used for zero-sized configurations.
+struct Empty;
+
+impl Cache for Empty {
+    fn access(&mut self, _symbol: u32) -> bool {
+        false
+    }
+}
+
+fn new_cache(policy: Policy, size: usize) -> Box<dyn Cache> {
+    if size == 0 {
+        return Box::new(Empty);
+    }
+    match policy {
+        Policy::Lru => Box::new(Lru {
+            size,
+            stack: OrderedSet::default(),
+        }),
+        Policy::Fifo => Box::new(Fifo {
+            size,
+            queue: OrderedSet::default(),
+        }),
+        Policy::Arc => Box::new(Arc {
+            size,
+            ..Arc::default()
+        }),
+        Policy::Clock => Box::new(Clock {
+            slots: Vec::with_capacity(size),
+            index: HashMap::new(),
+            hand: 0,
+            size,
+        }),
+        Policy::Custom(name) => {
+            let factory = custom_policies()
+                .iter()
+                .find(|&&(registered, _)| registered == name)
+                .map(|&(_, factory)| factory)
+                .unwrap_or_else(|| panic!("`{}` is registered", name));
+            Box::new(Custom {
+                size,
+                resident: HashSet::new(),
+                policy: factory(size),
+            })
+        }
+    }
+}
+
+/// The geometry of a set-associative cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheConfig {
+    size: usize,
+    associativity: usize,
+}
+
+impl CacheConfig {
+    /// Create a cache of `size` entries in sets of `associativity` ways.
+    ///
+    /// Returns `None` unless the associativity is non-zero and divides the size.
+    pub const fn new(size: usize, associativity: usize) -> Option<Self> {
+        if size == 0 || associativity == 0 || !size.is_multiple_of(associativity) {
+            return None;
+        }
+        Some(Self {
+            size,
+            associativity,
+        })
+    }
+
+    /// Create a fully-associative cache of `size` entries.
+    pub const fn fully_associative(size: usize) -> Option<Self> {
+        Self::new(size, size)
+    }
+
+    /// The total number of entries in the cache.
+    pub const fn size(&self) -> usize {
+        self.size
+    }
+
+    /// The number of ways in each set.
+    pub const fn associativity(&self) -> usize {
+        self.associativity
+    }
+
+    /// The number of sets.
+    pub const fn sets(&self) -> usize {
+        self.size / self.associativity
+    }
+}
+
+/// A set-associative cache, where each symbol maps to the set given by its low bits.
+pub(crate) struct SetAssociative {
+    sets: Vec<Box<dyn Cache>>,
+}
+
+impl SetAssociative {
+    pub(crate) fn new(policy: Policy, config: CacheConfig) -> Self {
+        Self {
+            sets: (0..config.sets())
+                .map(|_| new_cache(policy, config.associativity()))
+                .collect(),
+        }
+    }
+
+    /// The set a symbol maps to.
+    pub(crate) fn set_of(&self, symbol: u32) -> usize {
+        symbol as usize % self.sets.len()
+    }
+
+    /// Access a symbol, returning whether it was a hit.
+    pub(crate) fn access(&mut self, symbol: u32) -> bool {
+        let set = self.set_of(symbol);
+        self.sets[set].access(symbol)
+    }
+}
+
+/// The counts of outcomes from simulating one cache over a trace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    /// The number of misses which had to evict an entry, i.e. the writebacks if every entry is
+    /// dirty, since traces don't record which accesses are writes.
+    pub evictions: usize,
+}
+
+impl CacheStats {
+    /// The total number of accesses.
+    pub const fn accesses(&self) -> usize {
+        self.hits + self.misses
+    }
+
+    /// The fraction of accesses which missed, or zero if there were none.
+    pub fn miss_ratio(&self) -> f64 {
+        if self.accesses() == 0 {
+            return 0.0;
+        }
+        #[allow(clippy::cast_precision_loss)]
+        let ratio = self.misses as f64 / self.accesses() as f64;
+        ratio
+    }
+}
+
+/// Simulate a set-associative cache over a trace.
+pub fn simulate(trace: &Trace, policy: Policy, config: CacheConfig) -> CacheStats {
+    let mut cache = SetAssociative::new(policy, config);
+    let mut set_misses = vec![0; config.sets()];
+    let mut stats = CacheStats::default();
+    for &symbol in trace.as_slice() {
+        if cache.access(symbol) {
+            stats.hits += 1;
+        } else {
+            stats.misses += 1;
+            set_misses[cache.set_of(symbol)] += 1;
+        }
+    }
+
+    // every policy inserts on a miss and only removes to make room, so each set fills up
+    // without evicting and then evicts on every miss
+    stats.evictions = set_misses
+        .iter()
+        .map(|&misses: &usize| misses.saturating_sub(config.associativity()))
+        .sum();
+    stats
+}
+
+/// Calculate the miss ratio of a policy at each of the given cache sizes.
+///
+/// This makes a single pass over the trace, driving a cache of every size at once. LRU is read
+/// directly off the miss-ratio curve instead.
+pub fn miss_ratios(trace: &Trace, policy: Policy, sizes: &[usize]) -> Vec<f64> {
+    if policy == Policy::Lru {
+        let mrc = MissRatioCurve::from(trace);
+        return sizes.iter().map(|&size| mrc.miss_ratio(size)).collect();
+    }
+
+    let accesses = trace.as_slice().len();
+    if accesses == 0 {
+        return vec![0.0; sizes.len()];
+    }
+
+    let mut caches: Vec<_> = sizes.iter().map(|&size| new_cache(policy, size)).collect();
+    let mut hits = vec![0; sizes.len()];
+    for &symbol in trace.as_slice() {
+        for (cache, hits) in caches.iter_mut().zip(&mut hits) {
+            if cache.access(symbol) {
+                *hits += 1;
+            }
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    hits.into_iter()
+        .map(|hits| (accesses - hits) as f64 / accesses as f64)
+        .collect()
+}
+
+/// A table of miss ratios, for each policy at each cache size.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissRatioMatrix {
+    policies: Vec<Policy>,
+    sizes: Vec<usize>,
+    ratios: Vec<Vec<f64>>,
+}
+
+impl MissRatioMatrix {
+    /// Simulate every policy at every size, with one pass over the trace per policy.
+    pub fn new(trace: &Trace, policies: &[Policy], sizes: &[usize]) -> Self {
+        Self::with_threads(trace, policies, sizes, 1)
+    }
+
+    /// Simulate every policy at every size, like [`MissRatioMatrix::new`], but with the policies
+    /// spread over `threads` threads.
+    pub fn with_threads(
+        trace: &Trace,
+        policies: &[Policy],
+        sizes: &[usize],
+        threads: usize,
+    ) -> Self {
+        let threads = threads.clamp(1, policies.len().max(1));
+        // without spawning, this also runs where there are no threads, like wasm32
+        if threads == 1 {
+            return Self {
+                policies: policies.to_vec(),
+                sizes: sizes.to_vec(),
+                ratios: policies
+                    .iter()
+                    .map(|&policy| {
+                        debug!(%policy, sizes = sizes.len(), "simulating");
+                        miss_ratios(trace, policy, sizes)
+                    })
+                    .collect(),
+            };
+        }
+        let mut ratios = vec![Vec::new(); policies.len()];
+
+        thread::scope(|scope| {
+            let handles: Vec<_> = (0..threads)
+                .map(|t| {
+                    scope.spawn(move || {
+                        policies
+                            .iter()
+                            .enumerate()
+                            .skip(t)
+                            .step_by(threads)
+                            .map(|(i, &policy)| {
+                                debug!(%policy, sizes = sizes.len(), "simulating");
+                                (i, miss_ratios(trace, policy, sizes))
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+
+            for handle in handles {
+                for (i, row) in handle.join().expect("the thread doesn't panic") {
+                    ratios[i] = row;
+                }
+            }
+        });
+
+        Self {
+            policies: policies.to_vec(),
+            sizes: sizes.to_vec(),
+            ratios,
+        }
+    }
+
+    /// The simulated policies, in order.
+    pub fn policies(&self) -> &[Policy] {
+        &self.policies
+    }
+
+    /// The simulated cache sizes, in order.
+    pub fn sizes(&self) -> &[usize] {
+        &self.sizes
+    }
+
+    /// The miss ratio of the given policy at the given cache size, if it was simulated.
+    pub fn get(&self, policy: Policy, size: usize) -> Option<f64> {
+        let row = self.policies.iter().position(|&p| p == policy)?;
+        let col = self.sizes.iter().position(|&s| s == size)?;
+        Some(self.ratios[row][col])
+    }
+
+    /// The miss ratios of the given policy at each size, in order, if it was simulated.
+    pub fn ratios(&self, policy: Policy) -> Option<&[f64]> {
+        let row = self.policies.iter().position(|&p| p == policy)?;
+        Some(&self.ratios[row])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::table::{Format, Table};
+
+    fn hits(policy: Policy, size: usize, trace: &[u32]) -> Vec<bool> {
+        let mut cache = new_cache(policy, size);
+        trace.iter().map(|&n| cache.access(n)).collect()
+    }
+
+    #[test]
+    fn parse_size_works() {
+        assert_eq!(parse_size("16"), Ok(16));
+        assert_eq!(parse_size("1k"), Ok(1024));
+        assert_eq!(parse_size("4K"), Ok(4096));
+        assert_eq!(parse_size("2m"), Ok(2 << 20));
+        assert!(parse_size("k").is_err());
+        assert!(parse_size("-1").is_err());
+    }
+
+    #[test]
+    fn parse_size_grid_works() {
+        assert_eq!(parse_size_grid("1k,4k"), Ok(vec![1024, 4096]));
+        assert_eq!(
+            parse_size_grid("1k..8k/log"),
+            Ok(vec![1024, 2048, 4096, 8192])
+        );
+        assert_eq!(parse_size_grid("0..20/8"), Ok(vec![0, 8, 16]));
+        assert_eq!(parse_size_grid("2..4"), Ok(vec![2, 3, 4]));
+        assert!(parse_size_grid("0..8/log").is_err());
+        assert!(parse_size_grid("8..4").is_err());
+        assert!(parse_size_grid("1..4/0").is_err());
+    }
+
+    #[test]
+    fn parse_policy_works() {
+        assert_eq!("LRU".parse(), Ok(Policy::Lru));
+        assert_eq!("clock".parse(), Ok(Policy::Clock));
+        assert!("opt".parse::<Policy>().is_err());
+    }
+
+    #[test]
+    fn fifo_ignores_hits() {
+        // A is evicted first despite being reused, unlike LRU
+        assert_eq!(
+            hits(Policy::Fifo, 2, &[0, 1, 0, 2, 0]),
+            vec![false, false, true, false, false]
+        );
+    }
+
+    #[test]
+    fn clock_gives_second_chance() {
+        // A is referenced, so B is evicted to make room for C
+        assert_eq!(
+            hits(Policy::Clock, 2, &[0, 1, 0, 2, 0, 1]),
+            vec![false, false, true, false, true, false]
+        );
+    }
+
+    #[test]
+    fn arc_adapts_to_reuse() {
+        // LRU misses both of the last two accesses, but ARC learns from the ghost hit on A
+        assert_eq!(
+            hits(Policy::Arc, 2, &[0, 0, 1, 1, 2, 0, 1]),
+            vec![false, true, false, true, false, false, true]
+        );
+    }
+
+    // LRU again, from outside the simulator.
+    #[derive(Default)]
+    struct Recency(Vec<u32>);
+
+    impl ReplacementPolicy for Recency {
+        fn on_access(&mut self, symbol: u32, _hit: bool) {
+            self.0.retain(|&s| s != symbol);
+            self.0.push(symbol);
+        }
+
+        fn victim(&mut self) -> u32 {
+            // the least recent symbol other than the one being accessed
+            self.0[0]
+        }
+
+        fn on_evict(&mut self, symbol: u32) {
+            self.0.retain(|&s| s != symbol);
+        }
+    }
+
+    #[test]
+    fn custom_policy() {
+        let policy = register("recency", |_| Box::<Recency>::default());
+        assert_eq!("Recency".parse(), Ok(policy));
+        assert_eq!(policy.to_string(), "recency");
+
+        let trace = crate::generate::uniform(500, 40, 3);
+        let sizes = [0, 1, 4, 16, 64];
+        assert_eq!(
+            miss_ratios(&trace, policy, &sizes),
+            miss_ratios(&trace, Policy::Lru, &sizes)
+        );
+    }
+
+    #[test]
+    fn lru_matches_stack_distance() {
+        let trace = vec![0, 1, 2, 0, 1, 3, 0, 2, 2, 1];
+        // distances: inf inf inf 2 2 inf 2 3 0 3
+        assert_eq!(
+            miss_ratios(&Trace::from(trace.clone()), Policy::Lru, &[0, 1, 3, 4]),
+            vec![1.0, 0.9, 0.6, 0.4]
+        );
+        assert_eq!(
+            hits(Policy::Lru, 3, &trace).iter().filter(|&&h| h).count(),
+            4
+        );
+    }
+
+    #[test]
+    fn empty_trace() {
+        let trace = Trace::from(vec![]);
+        assert_eq!(miss_ratios(&trace, Policy::Arc, &[1, 2]), vec![0.0, 0.0]);
+    }
+
+    #[test]
+    fn simulate_works() {
+        // two sets of one way: 0 and 2 share set 0, while 1 and 3 share set 1
+        let trace = Trace::from(vec![0, 1, 0, 2, 0, 1, 3, 1]);
+        let stats = simulate(&trace, Policy::Lru, CacheConfig::new(2, 1).unwrap());
+        assert_eq!(
+            stats,
+            CacheStats {
+                hits: 2,
+                misses: 6,
+                evictions: 4,
+            }
+        );
+        assert_eq!(stats.miss_ratio(), 0.75);
+
+        let full = simulate(
+            &trace,
+            Policy::Lru,
+            CacheConfig::fully_associative(4).unwrap(),
+        );
+        assert_eq!(full.evictions, 0);
+        assert_eq!(full.misses, 4);
+    }
+
+    #[test]
+    fn matrix_threads() {
+        let trace = crate::generate::uniform(500, 40, 3);
+        let policies = [Policy::Lru, Policy::Fifo, Policy::Arc, Policy::Clock];
+        assert_eq!(
+            MissRatioMatrix::with_threads(&trace, &policies, &[4, 16], 3),
+            MissRatioMatrix::new(&trace, &policies, &[4, 16])
+        );
+    }
+
+    #[test]
+    fn matrix_output() {
+        let trace = Trace::from(vec![0, 1, 0, 1]);
+        let matrix = MissRatioMatrix::new(&trace, &[Policy::Lru, Policy::Fifo], &[1, 2]);
+        assert_eq!(matrix.get(Policy::Fifo, 2), Some(0.5));
+        assert_eq!(matrix.get(Policy::Arc, 2), None);
+        assert_eq!(matrix.ratios(Policy::Lru), Some(&[1.0, 0.5][..]));
+
+        let mut csv = Vec::new();
+        Table::from(&matrix).write(Format::Csv, &mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "policy,1,2\nlru,1,0.5\nfifo,1,0.5\n"
+        );
+    }
+}
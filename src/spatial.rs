@@ -0,0 +1,97 @@
+//! Contains spatial locality analysis over the differences between consecutive accesses.
+//!
+//! Stack distances only capture temporal locality; here symbols are treated as addresses, so
+//! nearby symbols are nearby in memory. That doesn't hold for a trace whose addresses
+//! [`Trace::parse_addresses`] renumbered.
+
+use std::collections::HashMap;
+
+use crate::trace::Trace;
+
+/// The strides between consecutive accesses of a trace.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Strides {
+    counts: HashMap<i64, usize>,
+    total: usize,
+}
+
+impl Strides {
+    /// Tally the stride between every pair of consecutive accesses.
+    pub fn new(trace: &Trace) -> Self {
+        let mut strides = Self::default();
+        for pair in trace.as_slice().windows(2) {
+            *strides
+                .counts
+                .entry(i64::from(pair[1]) - i64::from(pair[0]))
+                .or_insert(0) += 1;
+            strides.total += 1;
+        }
+        strides
+    }
+
+    /// The number of times the given stride occurs.
+    pub fn count(&self, stride: i64) -> usize {
+        self.counts.get(&stride).copied().unwrap_or(0)
+    }
+
+    /// The `k` most common strides and their counts, most common first.
+    ///
+    /// Ties are broken by smaller stride magnitude.
+    pub fn dominant(&self, k: usize) -> Vec<(i64, usize)> {
+        let mut strides: Vec<_> = self.counts.iter().map(|(&s, &n)| (s, n)).collect();
+        strides.sort_unstable_by_key(|&(s, n)| (std::cmp::Reverse(n), s.abs(), s));
+        strides.truncate(k);
+        strides
+    }
+
+    /// The fraction of consecutive accesses which step forward by exactly one.
+    pub fn sequential_fraction(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+
+        #[allow(clippy::cast_precision_loss)]
+        let fraction = self.count(1) as f64 / self.total as f64;
+        fraction
+    }
+
+    /// Calculate the spatial locality histogram.
+    ///
+    /// Returns a vector where entry 0 counts strides of zero, and entry `i` counts strides with
+    /// magnitude in `[2^(i - 1), 2^i)`.
+    pub fn histogram(&self) -> Vec<usize> {
+        let mut freqs = Vec::new();
+        for (&stride, &n) in &self.counts {
+            let bucket = (u64::BITS - stride.unsigned_abs().leading_zeros()) as usize;
+            if freqs.len() <= bucket {
+                freqs.resize(bucket + 1, 0);
+            }
+            freqs[bucket] += n;
+        }
+        freqs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strides() {
+        let strides = Strides::new(&Trace::from(vec![0, 1, 2, 3, 10, 8, 6, 6]));
+        assert_eq!(strides.count(1), 3);
+        assert_eq!(strides.count(-2), 2);
+        assert_eq!(strides.dominant(2), vec![(1, 3), (-2, 2)]);
+        assert!((strides.sequential_fraction() - 3.0 / 7.0).abs() < 1e-12);
+        // buckets: {0}, {1, 1, 1}, {-2, -2}, {7}
+        assert_eq!(strides.histogram(), vec![1, 3, 2, 1]);
+    }
+
+    #[test]
+    fn too_short() {
+        let strides = Strides::new(&Trace::from(vec![4]));
+        assert_eq!(strides.dominant(3), vec![]);
+        assert_eq!(strides.sequential_fraction(), 0.0);
+        assert_eq!(strides.histogram(), vec![]);
+    }
+}
@@ -0,0 +1,62 @@
+//! Contains `proptest` strategies for generating traces.
+
+use proptest::collection;
+use proptest::prelude::*;
+
+use crate::trace::Trace;
+
+/// A strategy for traces of up to `max_len` accesses over the symbols `0..alphabet_size`.
+///
+/// A small alphabet makes reuses, and so interesting stack distances, common.
+pub fn traces(max_len: usize, alphabet_size: u32) -> impl Strategy<Value = Trace> {
+    assert!(alphabet_size > 0, "the alphabet must be non-empty");
+    collection::vec(0..alphabet_size, 0..=max_len).prop_map(Trace::from)
+}
+
+/// The parameters of the `Arbitrary` strategy for traces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TraceParameters {
+    /// The maximum number of accesses.
+    pub max_len: usize,
+    /// The number of symbols.
+    pub alphabet_size: u32,
+}
+
+impl Default for TraceParameters {
+    fn default() -> Self {
+        Self {
+            max_len: 64,
+            alphabet_size: 8,
+        }
+    }
+}
+
+impl Arbitrary for Trace {
+    type Parameters = TraceParameters;
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(parameters: Self::Parameters) -> Self::Strategy {
+        traces(parameters.max_len, parameters.alphabet_size).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mrc::MissRatioCurve;
+
+    proptest! {
+        #[test]
+        fn histogram_counts_every_access(trace in traces(100, 10)) {
+            prop_assert_eq!(trace.stack_distance_histogram().total(), trace.as_slice().len());
+        }
+
+        #[test]
+        fn miss_ratio_curve_is_monotone(trace in any::<Trace>()) {
+            let mrc = MissRatioCurve::from(&trace);
+            for pair in mrc.ratios().windows(2) {
+                prop_assert!(pair[1] <= pair[0]);
+            }
+        }
+    }
+}
@@ -0,0 +1,188 @@
+//! Contains one-screen summaries of the locality of a trace.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use crate::frequency;
+use crate::trace::{Addresses, Trace};
+
+/// The headline numbers of a trace's stack distance and frequency histograms.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary {
+    /// The number of accesses.
+    pub accesses: usize,
+    /// The number of distinct symbols, which is also the number of infinite distances.
+    pub symbols: usize,
+    /// The median stack distance, or `None` if it's infinite.
+    pub median_distance: Option<usize>,
+    /// The 90th percentile stack distance, or `None` if it's infinite.
+    pub p90_distance: Option<usize>,
+    /// The largest finite stack distance, or `None` if nothing is reused.
+    pub max_distance: Option<usize>,
+    /// The locality score, between zero and one, where higher is better.
+    pub locality_score: f64,
+    /// The entropy of the access frequencies, in bits.
+    pub entropy: f64,
+    /// The address of the most frequently accessed symbol and its number of accesses, preferring
+    /// the smallest symbol on ties, or `None` if the trace is empty.
+    pub hottest: Option<(u64, usize)>,
+}
+
+impl Summary {
+    /// Summarize a trace whose symbols are addresses.
+    pub fn new(trace: &Trace) -> Self {
+        Self::with_addresses(trace, &Addresses::Identity)
+    }
+
+    /// Summarize a trace read by [`Trace::parse_addresses`], with the addresses its symbols stand
+    /// for.
+    pub fn with_addresses(trace: &Trace, addresses: &Addresses) -> Self {
+        let histogram = trace.stack_distance_histogram();
+
+        // symbols can be large, like addresses, so count them sparsely
+        let mut counts = HashMap::new();
+        for &n in trace.as_slice() {
+            *counts.entry(n).or_insert(0) += 1;
+        }
+        let frequencies: Vec<usize> = counts.values().copied().collect();
+        let hottest = counts
+            .into_iter()
+            .max_by_key(|&(symbol, count)| (count, std::cmp::Reverse(symbol)))
+            .map(|(symbol, count)| (addresses.address(symbol), count));
+
+        Self {
+            accesses: trace.len(),
+            symbols: histogram.infinities(),
+            median_distance: histogram.quantile(0.5),
+            p90_distance: histogram.quantile(0.9),
+            max_distance: histogram.frequencies().len().checked_sub(1),
+            locality_score: histogram.locality_score(),
+            entropy: frequency::entropy(&frequencies),
+            hottest,
+        }
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let distance = |d: Option<usize>| d.map_or_else(|| "inf".to_string(), |d| d.to_string());
+        let rows = [
+            ("accesses", self.accesses.to_string()),
+            ("symbols", self.symbols.to_string()),
+            ("median distance", distance(self.median_distance)),
+            ("p90 distance", distance(self.p90_distance)),
+            (
+                "max distance",
+                self.max_distance
+                    .map_or_else(|| "-".to_string(), |d| d.to_string()),
+            ),
+            ("locality score", format!("{:.4}", self.locality_score)),
+            ("entropy (bits)", format!("{:.4}", self.entropy)),
+            (
+                "hottest symbol",
+                self.hottest.map_or_else(
+                    || "-".to_string(),
+                    |(symbol, count)| format!("{} ({} accesses)", symbol, count),
+                ),
+            ),
+        ];
+        for (name, value) in rows {
+            writeln!(f, "{:<16}{}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// A frequently accessed symbol.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HotSymbol {
+    pub symbol: u32,
+    /// The number of accesses to the symbol.
+    pub count: usize,
+    /// The mean stack distance of the symbol's reuses, or `None` if it's never reused.
+    pub mean_reuse_distance: Option<f64>,
+}
+
+/// Find the `k` most frequently accessed symbols, most frequent first, preferring the smallest
+/// symbol on ties.
+pub fn top_k(trace: &Trace, k: usize) -> Vec<HotSymbol> {
+    // the count, and the number and sum of finite distances
+    let mut stats: HashMap<u32, (usize, usize, usize)> = HashMap::new();
+    for (&symbol, distance) in trace.as_slice().iter().zip(trace.stack_distance()) {
+        let entry = stats.entry(symbol).or_default();
+        entry.0 += 1;
+        if let Some(distance) = distance {
+            entry.1 += 1;
+            entry.2 += distance;
+        }
+    }
+
+    let mut hot: Vec<_> = stats.into_iter().collect();
+    hot.sort_unstable_by_key(|&(symbol, (count, _, _))| (std::cmp::Reverse(count), symbol));
+    hot.truncate(k);
+    hot.into_iter()
+        .map(|(symbol, (count, reuses, total))| {
+            #[allow(clippy::cast_precision_loss)]
+            let mean_reuse_distance = (reuses > 0).then(|| total as f64 / reuses as f64);
+            HotSymbol {
+                symbol,
+                count,
+                mean_reuse_distance,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary() {
+        let summary = Summary::new(&Trace::from(vec![0, 1, 0, 2, 1, 0, 0]));
+        assert_eq!(summary.accesses, 7);
+        assert_eq!(summary.symbols, 3);
+        // distances are inf, inf, 1, inf, 2, 2, 0
+        assert_eq!(summary.median_distance, Some(2));
+        assert_eq!(summary.p90_distance, None);
+        assert_eq!(summary.max_distance, Some(2));
+        assert_eq!(summary.hottest, Some((0, 4)));
+        assert!(summary
+            .to_string()
+            .contains("hottest symbol  0 (4 accesses)\n"));
+
+        let addresses = Addresses::Renumbered(vec![1 << 40, 7, 9]);
+        let trace = Trace::from(vec![0, 1, 0, 2, 1, 0, 0]);
+        assert_eq!(
+            Summary::with_addresses(&trace, &addresses).hottest,
+            Some((1 << 40, 4))
+        );
+
+        let empty = Summary::new(&Trace::from(vec![]));
+        assert_eq!(empty.hottest, None);
+        assert_eq!(empty.max_distance, None);
+        assert!(empty.to_string().contains("median distance inf\n"));
+    }
+
+    #[test]
+    fn top_k_works() {
+        // distances are inf, inf, 1, inf, 2, 2, 0
+        let hot = top_k(&Trace::from(vec![0, 1, 0, 2, 1, 0, 0]), 2);
+        assert_eq!(
+            hot,
+            vec![
+                HotSymbol {
+                    symbol: 0,
+                    count: 4,
+                    mean_reuse_distance: Some(1.0),
+                },
+                HotSymbol {
+                    symbol: 1,
+                    count: 2,
+                    mean_reuse_distance: Some(2.0),
+                },
+            ]
+        );
+        assert_eq!(top_k(&Trace::from(vec![3]), 5)[0].mean_reuse_distance, None);
+    }
+}
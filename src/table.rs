@@ -0,0 +1,453 @@
+//! Contains tables of results and their encodings, for routing results into other tools.
+
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use crate::histogram::Histogram;
+use crate::mrc::MissRatioCurve;
+use crate::simulate::MissRatioMatrix;
+use crate::summary::Summary;
+
+/// A value in a table.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Int(u64),
+    Float(f64),
+    Text(String),
+}
+
+impl Cell {
+    // The value as a float, for numeric encodings.
+    #[allow(clippy::cast_precision_loss)]
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Int(n) => Some(*n as f64),
+            Self::Float(x) => Some(*x),
+            Self::Text(_) => None,
+        }
+    }
+}
+
+impl Display for Cell {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Int(n) => write!(f, "{}", n),
+            Self::Float(x) => write!(f, "{}", x),
+            Self::Text(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl From<usize> for Cell {
+    fn from(n: usize) -> Self {
+        Self::Int(n as u64)
+    }
+}
+
+impl From<u32> for Cell {
+    fn from(n: u32) -> Self {
+        Self::Int(n.into())
+    }
+}
+
+impl From<u64> for Cell {
+    fn from(n: u64) -> Self {
+        Self::Int(n)
+    }
+}
+
+impl From<f64> for Cell {
+    fn from(x: f64) -> Self {
+        Self::Float(x)
+    }
+}
+
+impl From<&str> for Cell {
+    fn from(s: &str) -> Self {
+        Self::Text(s.to_string())
+    }
+}
+
+impl From<String> for Cell {
+    fn from(s: String) -> Self {
+        Self::Text(s)
+    }
+}
+
+/// An encoding of a table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// Columns aligned for reading in a terminal.
+    Table,
+    /// An array of objects, one per row.
+    Json,
+    Csv,
+    /// A two-dimensional NumPy array of `f64`, with text as NaN. The column names are lost.
+    Npy,
+    /// Apache Parquet, with a column of `f64` for each numeric column and of strings otherwise.
+    /// This needs the `parquet` feature.
+    Parquet,
+}
+
+/// An error parsing a format name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseFormatError(String);
+
+impl Display for ParseFormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown format `{}`", self.0)
+    }
+}
+
+impl std::error::Error for ParseFormatError {}
+
+impl FromStr for Format {
+    type Err = ParseFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "table" => Ok(Self::Table),
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            "npy" => Ok(Self::Npy),
+            "parquet" => Ok(Self::Parquet),
+            _ => Err(ParseFormatError(s.to_string())),
+        }
+    }
+}
+
+/// A table of results, with named columns.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Table {
+    columns: Vec<String>,
+    rows: Vec<Vec<Cell>>,
+}
+
+impl From<&Histogram> for Table {
+    /// Tabulate the count at each distance, with the infinities last.
+    fn from(histogram: &Histogram) -> Self {
+        let mut table = Self::new(["distance", "count"]);
+        for (distance, &count) in histogram.frequencies().iter().enumerate() {
+            table.push(vec![distance.into(), count.into()]);
+        }
+        table.push(vec!["inf".into(), histogram.infinities().into()]);
+        table
+    }
+}
+
+impl From<&MissRatioCurve> for Table {
+    /// Tabulate the miss ratio at each size up to where the curve is flat.
+    fn from(mrc: &MissRatioCurve) -> Self {
+        let mut table = Self::new(["size", "miss_ratio"]);
+        for (size, &ratio) in mrc.ratios().iter().enumerate() {
+            table.push(vec![size.into(), ratio.into()]);
+        }
+        table
+    }
+}
+
+impl From<&MissRatioMatrix> for Table {
+    /// Tabulate the miss ratios with one row per policy and one column per size.
+    fn from(matrix: &MissRatioMatrix) -> Self {
+        let mut table = Self::new(
+            ["policy".to_string()]
+                .into_iter()
+                .chain(matrix.sizes().iter().map(ToString::to_string)),
+        );
+        for &policy in matrix.policies() {
+            let ratios = matrix.ratios(policy).expect("simulated");
+            table.push(
+                std::iter::once(policy.to_string().into())
+                    .chain(ratios.iter().map(|&ratio| ratio.into()))
+                    .collect(),
+            );
+        }
+        table
+    }
+}
+
+impl From<&Summary> for Table {
+    /// Tabulate each metric and its value.
+    fn from(summary: &Summary) -> Self {
+        let distance = |d: Option<usize>| d.map_or_else(|| Cell::from("inf"), Cell::from);
+        let mut table = Self::new(["metric", "value"]);
+        table.push(vec!["accesses".into(), summary.accesses.into()]);
+        table.push(vec!["symbols".into(), summary.symbols.into()]);
+        table.push(vec![
+            "median_distance".into(),
+            distance(summary.median_distance),
+        ]);
+        table.push(vec!["p90_distance".into(), distance(summary.p90_distance)]);
+        table.push(vec![
+            "max_distance".into(),
+            summary
+                .max_distance
+                .map_or_else(|| Cell::from("-"), Cell::from),
+        ]);
+        table.push(vec!["locality_score".into(), summary.locality_score.into()]);
+        table.push(vec!["entropy".into(), summary.entropy.into()]);
+        if let Some((symbol, count)) = summary.hottest {
+            table.push(vec!["hottest_symbol".into(), symbol.into()]);
+            table.push(vec!["hottest_count".into(), count.into()]);
+        }
+        table
+    }
+}
+
+/// Quote a string for JSON.
+pub(crate) fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+impl Table {
+    /// Create an empty table with the given columns.
+    pub fn new<S: Into<String>>(columns: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            columns: columns.into_iter().map(Into::into).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Add a row, which must have a cell for each column.
+    pub fn push(&mut self, row: Vec<Cell>) {
+        assert_eq!(
+            row.len(),
+            self.columns.len(),
+            "the row must fill every column"
+        );
+        self.rows.push(row);
+    }
+
+    /// The names of the columns.
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// The rows, in order.
+    pub fn rows(&self) -> &[Vec<Cell>] {
+        &self.rows
+    }
+
+    /// Write the table in the given format.
+    pub fn write<W: Write>(&self, format: Format, w: W) -> io::Result<()> {
+        match format {
+            Format::Table => self.write_table(w),
+            Format::Json => self.write_json(w),
+            Format::Csv => self.write_csv(w),
+            Format::Npy => self.write_npy(w),
+            Format::Parquet => self.write_parquet(w),
+        }
+    }
+
+    fn write_table<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let cells: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| row.iter().map(ToString::to_string).collect())
+            .collect();
+        let widths: Vec<usize> = (0..self.columns.len())
+            .map(|i| {
+                cells
+                    .iter()
+                    .map(|row| row[i].len())
+                    .chain([self.columns[i].len()])
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        let mut line = |row: &[String]| {
+            let padded: Vec<_> = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, &width)| format!("{:<width$}", cell, width = width))
+                .collect();
+            writeln!(w, "{}", padded.join("  ").trim_end())
+        };
+        line(&self.columns)?;
+        for row in &cells {
+            line(row)?;
+        }
+        Ok(())
+    }
+
+    fn write_csv<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let quote = |s: &str| {
+            if s.contains([',', '"', '\n']) {
+                format!("\"{}\"", s.replace('"', "\"\""))
+            } else {
+                s.to_string()
+            }
+        };
+        let header: Vec<_> = self.columns.iter().map(|c| quote(c)).collect();
+        writeln!(w, "{}", header.join(","))?;
+        for row in &self.rows {
+            let row: Vec<_> = row.iter().map(|cell| quote(&cell.to_string())).collect();
+            writeln!(w, "{}", row.join(","))?;
+        }
+        Ok(())
+    }
+
+    fn write_json<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let rows: Vec<_> = self
+            .rows
+            .iter()
+            .map(|row| {
+                let fields: Vec<_> = self
+                    .columns
+                    .iter()
+                    .zip(row)
+                    .map(|(column, cell)| {
+                        let value = match cell {
+                            Cell::Int(n) => n.to_string(),
+                            Cell::Float(x) if x.is_finite() => x.to_string(),
+                            // JSON has no infinities or NaN
+                            Cell::Float(_) => "null".to_string(),
+                            Cell::Text(s) => json_string(s),
+                        };
+                        format!("{}:{}", json_string(column), value)
+                    })
+                    .collect();
+                format!("{{{}}}", fields.join(","))
+            })
+            .collect();
+        writeln!(w, "[{}]", rows.join(","))
+    }
+
+    fn write_npy<W: Write>(&self, mut w: W) -> io::Result<()> {
+        // version 1.0: magic, version, header length, then a header padded so the data is aligned
+        // to 64 bytes
+        let mut header = format!(
+            "{{'descr': '<f8', 'fortran_order': False, 'shape': ({}, {}), }}",
+            self.rows.len(),
+            self.columns.len()
+        );
+        let unpadded = 10 + header.len() + 1;
+        header.push_str(&" ".repeat((64 - unpadded % 64) % 64));
+        header.push('\n');
+        let header_len = u16::try_from(header.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many columns"))?;
+
+        w.write_all(b"\x93NUMPY\x01\x00")?;
+        w.write_all(&header_len.to_le_bytes())?;
+        w.write_all(header.as_bytes())?;
+        for cell in self.rows.iter().flatten() {
+            w.write_all(&cell.as_f64().unwrap_or(f64::NAN).to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "parquet"))]
+    #[allow(clippy::unused_self)]
+    fn write_parquet<W: Write>(&self, _w: W) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "writing parquet needs the `parquet` feature",
+        ))
+    }
+
+    #[cfg(feature = "parquet")]
+    fn write_parquet<W: Write>(&self, w: W) -> io::Result<()> {
+        use std::sync::Arc;
+
+        use arrow::array::{ArrayRef, Float64Array, StringArray};
+        use arrow::record_batch::RecordBatch;
+        use parquet::arrow::ArrowWriter;
+
+        let columns = self.columns.iter().enumerate().map(|(i, name)| {
+            let cells: Vec<&Cell> = self.rows.iter().map(|row| &row[i]).collect();
+            let array: ArrayRef = if cells.iter().all(|cell| cell.as_f64().is_some()) {
+                Arc::new(Float64Array::from_iter_values(
+                    cells.iter().filter_map(|cell| cell.as_f64()),
+                ))
+            } else {
+                Arc::new(StringArray::from_iter_values(
+                    cells.iter().map(ToString::to_string),
+                ))
+            };
+            (name.clone(), array)
+        });
+
+        let batch = RecordBatch::try_from_iter(columns).map_err(io::Error::other)?;
+        // the writer needs to own a `Send` sink, so buffer the file
+        let mut buffer = Vec::new();
+        let mut writer =
+            ArrowWriter::try_new(&mut buffer, batch.schema(), None).map_err(io::Error::other)?;
+        writer.write(&batch).map_err(io::Error::other)?;
+        writer.close().map_err(io::Error::other)?;
+
+        let mut w = w;
+        w.write_all(&buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> Table {
+        let mut table = Table::new(["distance", "count"]);
+        table.push(vec![0_usize.into(), 3_usize.into()]);
+        table.push(vec!["inf".into(), 2.5.into()]);
+        table
+    }
+
+    fn encode(format: Format) -> Vec<u8> {
+        let mut out = Vec::new();
+        table().write(format, &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn text_formats() {
+        assert_eq!(
+            String::from_utf8(encode(Format::Csv)).unwrap(),
+            "distance,count\n0,3\ninf,2.5\n"
+        );
+        assert_eq!(
+            String::from_utf8(encode(Format::Json)).unwrap(),
+            "[{\"distance\":0,\"count\":3},{\"distance\":\"inf\",\"count\":2.5}]\n"
+        );
+        assert_eq!(
+            String::from_utf8(encode(Format::Table)).unwrap(),
+            "distance  count\n0         3\ninf       2.5\n"
+        );
+    }
+
+    #[test]
+    fn npy() {
+        let out = encode(Format::Npy);
+        assert_eq!(&out[..8], b"\x93NUMPY\x01\x00");
+        let header_len = u16::from_le_bytes([out[8], out[9]]) as usize;
+        assert_eq!((10 + header_len) % 64, 0);
+        let header = std::str::from_utf8(&out[10..10 + header_len]).unwrap();
+        assert!(header.contains("'shape': (2, 2)"));
+
+        let data: Vec<f64> = out[10 + header_len..]
+            .chunks(8)
+            .map(|b| f64::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        assert_eq!(data[..2], [0.0, 3.0]);
+        assert!(data[2].is_nan());
+        assert_eq!(data[3], 2.5);
+    }
+
+    #[test]
+    fn parse_format() {
+        assert_eq!("JSON".parse(), Ok(Format::Json));
+        assert!("xml".parse::<Format>().is_err());
+    }
+}
@@ -0,0 +1,111 @@
+//! Contains time series of locality over the course of a trace.
+
+use crate::trace::Trace;
+
+/// The locality of a single access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Sample {
+    /// The LRU stack depth of the accessed symbol, or `None` on its first access.
+    pub depth: Option<usize>,
+    /// The number of distinct symbols accessed so far, including this one.
+    pub footprint: usize,
+}
+
+/// Calculate the stack depth and cumulative footprint at every access.
+pub fn per_access(trace: &Trace) -> Vec<Sample> {
+    let mut footprint = 0;
+    trace
+        .stack_distance()
+        .into_iter()
+        .map(|depth| {
+            if depth.is_none() {
+                footprint += 1;
+            }
+            Sample { depth, footprint }
+        })
+        .collect()
+}
+
+/// The locality of a window of consecutive accesses.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WindowSample {
+    /// The index of the first access in the window.
+    pub start: usize,
+    /// The mean stack depth of accesses in the window which aren't first accesses, or `None` if
+    /// there are no such accesses.
+    pub mean_depth: Option<f64>,
+    /// The number of first accesses in the window.
+    pub infinities: usize,
+    /// The number of distinct symbols accessed up to the end of the window.
+    pub footprint: usize,
+}
+
+/// Summarize the per-access time series over consecutive windows of the given length.
+///
+/// The last window may be shorter.
+pub fn per_window(trace: &Trace, window: usize) -> Vec<WindowSample> {
+    assert!(window > 0, "the window must be positive");
+
+    per_access(trace)
+        .chunks(window)
+        .enumerate()
+        .map(|(i, samples)| {
+            let depths: Vec<_> = samples.iter().filter_map(|s| s.depth).collect();
+
+            #[allow(clippy::cast_precision_loss)]
+            let mean_depth = (!depths.is_empty())
+                .then(|| depths.iter().sum::<usize>() as f64 / depths.len() as f64);
+
+            WindowSample {
+                start: i * window,
+                mean_depth,
+                infinities: samples.len() - depths.len(),
+                footprint: samples.last().map_or(0, |s| s.footprint),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples() {
+        let samples = per_access(&Trace::from(vec![0, 1, 0, 2]));
+        assert_eq!(
+            samples,
+            vec![
+                Sample {
+                    depth: None,
+                    footprint: 1
+                },
+                Sample {
+                    depth: None,
+                    footprint: 2
+                },
+                Sample {
+                    depth: Some(1),
+                    footprint: 2
+                },
+                Sample {
+                    depth: None,
+                    footprint: 3
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn windows() {
+        let windows = per_window(&Trace::from(vec![0, 1, 0, 1, 1, 2, 3]), 3);
+        assert_eq!(windows.len(), 3);
+        assert_eq!(windows[0].mean_depth, Some(1.0));
+        assert_eq!(windows[0].infinities, 2);
+        assert_eq!(windows[1].start, 3);
+        assert_eq!(windows[1].mean_depth, Some(0.5));
+        assert_eq!(windows[1].footprint, 3);
+        assert_eq!(windows[2].mean_depth, None);
+        assert_eq!(windows[2].footprint, 4);
+    }
+}
@@ -1,10 +1,18 @@
 //! Contains the `Trace` struct.
 
+use std::collections::HashMap;
 use std::fmt::Display;
+use std::num::ParseIntError;
+use std::str::FromStr;
 
-use itertools::Itertools;
+use num_bigint::BigUint;
+use tracing::{debug, warn};
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+use crate::counting::{self, Completions};
+use crate::histogram::Histogram;
+use crate::rng::Rng;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Trace {
     trace: Vec<u32>,
 }
@@ -15,48 +23,237 @@ impl From<Vec<u32>> for Trace {
     }
 }
 
+impl FromStr for Trace {
+    type Err = ParseIntError;
+
+    /// Parse a trace from whitespace-separated symbols.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trace = s
+            .split_whitespace()
+            .enumerate()
+            .map(|(position, symbol)| {
+                symbol
+                    .parse()
+                    .inspect_err(|_| warn!(position, symbol, "unparseable symbol"))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(Self::from)?;
+        debug!(accesses = trace.len(), "parsed trace");
+        Ok(trace)
+    }
+}
+
+/// Parse an address of a trace file, in decimal or in hex after `0x`, dropping its low `shift`
+/// bits, e.g. six for 64-byte cache lines.
+pub fn parse_address(token: &str, shift: u32) -> Result<u64, ParseIntError> {
+    match token.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => token.parse(),
+    }
+    .map(|address| address.checked_shr(shift).unwrap_or(0))
+}
+
+/// The addresses which the symbols of a trace read by [`Trace::parse_addresses`] stand for.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Addresses {
+    /// Each symbol is its address.
+    #[default]
+    Identity,
+    /// The symbols number the addresses in order of first access, and this lists them in that
+    /// order.
+    Renumbered(Vec<u64>),
+}
+
+impl Addresses {
+    /// The address of a symbol.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the addresses were renumbered and the trace has no such symbol.
+    pub fn address(&self, symbol: u32) -> u64 {
+        match self {
+            Self::Identity => u64::from(symbol),
+            Self::Renumbered(addresses) => addresses[symbol as usize],
+        }
+    }
+
+    /// Whether each symbol is its address, so symbols close together are close in memory.
+    pub fn is_identity(&self) -> bool {
+        matches!(self, Self::Identity)
+    }
+}
+
 impl Trace {
-    // Calculate the stack distances per-operation.
-    //
-    // Returns a vector where the ith entry represents the stack distance at that point.
-    fn stack_distance(&self) -> Vec<Option<usize>> {
-        let mut out = vec![Some(0); self.trace.len()];
-
-        let mut stack = Vec::new();
-
-        for (i, curr) in self.trace.iter().enumerate() {
-            let position = stack.iter().position(|n| n == &curr);
-            out[i] = position.map(|n| stack.len() - n - 1); // the stack is right-to-left
-            if let Some(position) = position {
-                stack.remove(position);
+    /// Parse a trace from whitespace-separated 64-bit addresses, coarsening them by `shift` bits
+    /// as they're read.
+    ///
+    /// The symbols are the coarsened addresses if they all fit in a `u32`, and otherwise the
+    /// addresses numbered in order of first access, which keeps every stack distance but not
+    /// where the addresses are. Either way the [`Addresses`] map the symbols back to them.
+    ///
+    /// # Panics
+    ///
+    /// Panics after 2^32 distinct addresses.
+    pub fn parse_addresses(s: &str, shift: u32) -> Result<(Self, Addresses), ParseIntError> {
+        let addresses = s
+            .split_whitespace()
+            .enumerate()
+            .map(|(position, token)| {
+                parse_address(token, shift)
+                    .inspect_err(|_| warn!(position, token, "unparseable address"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (trace, addresses) = match addresses
+            .iter()
+            .map(|&address| u32::try_from(address))
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(symbols) => (Self::from(symbols), Addresses::Identity),
+            Err(_) => {
+                debug!("renumbering addresses wider than 32 bits");
+                let mut ids = HashMap::new();
+                let mut firsts = Vec::new();
+                let symbols = addresses
+                    .into_iter()
+                    .map(|address| {
+                        *ids.entry(address).or_insert_with(|| {
+                            firsts.push(address);
+                            u32::try_from(firsts.len() - 1)
+                                .expect("fewer than 2^32 distinct addresses")
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                (Self::from(symbols), Addresses::Renumbered(firsts))
             }
-            stack.push(curr);
-        }
+        };
+        debug!(accesses = trace.len(), "parsed trace");
+        Ok((trace, addresses))
+    }
 
-        out
+    /// The accesses of the trace, in order.
+    pub fn as_slice(&self) -> &[u32] {
+        &self.trace
     }
 
-    /// Calculate the stack distance histogram.
+    /// The number of accesses in the trace.
+    pub fn len(&self) -> usize {
+        self.trace.len()
+    }
+
+    /// Whether the trace has no accesses.
+    pub fn is_empty(&self) -> bool {
+        self.trace.is_empty()
+    }
+
+    /// Construct the canonical trace of length `len` at position `index` in the order
+    /// [`TraceIter`] enumerates them, without enumerating the ones before it.
     ///
-    /// Returns a vector of frequencies of stack distances, plus the count of intinities.
-    pub fn stack_distance_histogram(&self) -> (Vec<usize>, usize) {
-        let distances = self.stack_distance();
-        let max = distances.iter().flatten().max();
+    /// Returns `None` if there are no more than `index` canonical traces of that length.
+    pub fn unrank(len: usize, index: &BigUint) -> Option<Self> {
+        let completions = Completions::new(len, u32::MAX);
+        if index >= &completions.total() {
+            return None;
+        }
 
-        let mut freqs = max.map_or_else(Vec::new, |max| vec![0; max + 1]);
+        // choose each access in turn, skipping past every trace which starts with a smaller one
+        let mut index = index.clone();
+        let mut trace = Vec::with_capacity(len);
+        let mut used = 0;
+        for i in 0..len {
+            let remaining = len - i - 1;
+            for symbol in 0..=used {
+                let count = completions.get(remaining, used.max(symbol + 1));
+                if index < count {
+                    #[allow(clippy::cast_possible_truncation)]
+                    trace.push(symbol as u32);
+                    used = used.max(symbol + 1);
+                    break;
+                }
+                index -= count;
+            }
+        }
+        Some(Self::from(trace))
+    }
 
-        let mut infinities = 0;
+    /// Sample a canonical trace of length `len` uniformly at random.
+    pub fn random_canonical(len: usize, rng: &mut Rng) -> Self {
+        let count = counting::bell(len);
+        // Draw just enough random bits to cover the count, and retry if they exceed it, which
+        // happens less than half the time.
+        let bits = count.bits();
+        let index = loop {
+            let mut index = BigUint::ZERO;
+            let mut drawn = 0;
+            while drawn < bits {
+                let chunk = (bits - drawn).min(32);
+                index = index * (1_u64 << chunk) + (rng.next_u64() >> (64 - chunk));
+                drawn += chunk;
+            }
+            if index < count {
+                break index;
+            }
+        };
+        Self::unrank(len, &index).expect("the index is less than the count")
+    }
 
-        for i in distances {
-            #[allow(clippy::option_if_let_else)]
-            if let Some(i) = i {
-                freqs[i] += 1;
-            } else {
-                infinities += 1;
+    /// The position of the trace in the order [`TraceIter`] enumerates canonical traces of its
+    /// length, the inverse of [`Trace::unrank`].
+    ///
+    /// Returns `None` if the trace isn't canonical, i.e. doesn't introduce symbols in order
+    /// starting from 0.
+    pub fn rank(&self) -> Option<BigUint> {
+        let len = self.trace.len();
+        let completions = Completions::new(len, u32::MAX);
+
+        // every trace which starts the same but has a smaller symbol here comes first
+        let mut rank = BigUint::ZERO;
+        let mut used = 0;
+        for (i, &symbol) in self.trace.iter().enumerate() {
+            let symbol = symbol as usize;
+            if symbol > used {
+                return None;
             }
+            rank += completions.get(len - i - 1, used) * symbol;
+            used = used.max(symbol + 1);
         }
+        Some(rank)
+    }
+
+    /// Rename the symbols in order of first access, giving the canonical trace with the same
+    /// stack distances.
+    pub fn canonical(&self) -> Self {
+        let mut names = HashMap::new();
+        self.trace
+            .iter()
+            .map(|&n| {
+                let next = names.len();
+                #[allow(clippy::cast_possible_truncation)]
+                let name = *names.entry(n).or_insert(next as u32);
+                name
+            })
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    /// The trace with its accesses in reverse order.
+    pub fn reversed(&self) -> Self {
+        Self::from(self.trace.iter().rev().copied().collect::<Vec<_>>())
+    }
 
-        (freqs, infinities)
+    /// Calculate the stack distances per-operation.
+    ///
+    /// Returns a vector where the ith entry represents the stack distance at that point.
+    pub fn stack_distance(&self) -> Vec<Option<usize>> {
+        let mut stack = LruStack::default();
+        self.trace.iter().map(|&curr| stack.access(curr)).collect()
+    }
+
+    /// Calculate the stack distance histogram.
+    ///
+    /// Returns a histogram of frequencies of stack distances, plus the count of intinities.
+    pub fn stack_distance_histogram(&self) -> Histogram {
+        Histogram::from(&self.stack_distance()[..])
     }
 
     /// Calculate the frequency historgram.
@@ -73,9 +270,57 @@ impl Trace {
     }
 }
 
+/// An LRU stack, for computing stack distances one access at a time.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct LruStack {
+    // the stack is right-to-left
+    stack: Vec<u32>,
+}
+
+impl LruStack {
+    /// Access a symbol, moving it to the top of the stack.
+    ///
+    /// Returns its stack distance, or `None` if this is its first access.
+    pub fn access(&mut self, symbol: u32) -> Option<usize> {
+        let position = self.stack.iter().position(|&n| n == symbol);
+        if let Some(position) = position {
+            self.stack.remove(position);
+        }
+        self.stack.push(symbol);
+        position.map(|n| self.stack.len() - n - 1)
+    }
+
+    /// The symbols on the stack, most recently accessed first.
+    pub fn symbols(&self) -> impl Iterator<Item = u32> + '_ {
+        self.stack.iter().rev().copied()
+    }
+
+    /// Forget every symbol but the `len` most recently accessed.
+    pub fn truncate(&mut self, len: usize) {
+        let excess = self.stack.len().saturating_sub(len);
+        self.stack.drain(..excess);
+    }
+
+    /// Forget every access.
+    pub fn clear(&mut self) {
+        self.stack.clear();
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Trace {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        // byte-sized symbols, so that fuzzed traces actually reuse them
+        let trace: Vec<u8> = u.arbitrary()?;
+        Ok(Self::from(
+            trace.into_iter().map(u32::from).collect::<Vec<_>>(),
+        ))
+    }
+}
+
 impl Display for Trace {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.trace.iter().max().map_or(true, |&n| n < 26) {
+        if self.trace.iter().max().is_none_or(|&n| n < 26) {
             for i in &self.trace {
                 write!(
                     f,
@@ -92,84 +337,410 @@ impl Display for Trace {
     }
 }
 
+/// A structural constraint on canonical traces, which [`TraceIter::filtered`] prunes with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Constraint {
+    /// There are at most this many infinities, i.e. distinct symbols.
+    MaxInfinities(usize),
+    /// There are at least this many infinities.
+    MinInfinities(usize),
+    /// The first access to `symbol` comes before `position`.
+    IntroducedBefore { symbol: u32, position: usize },
+}
+
+#[derive(Debug, Clone)]
 pub struct TraceIter {
     next: Option<Vec<u32>>,
+    // used[i] is the number of symbols in the first i accesses of next
+    used: Vec<usize>,
+    // the last trace left to yield, and the number of symbols in each of its prefixes
+    back: Vec<u32>,
+    back_used: Vec<usize>,
+    // the ways to finish each prefix, which is zero for prefixes breaking the constraints
+    completions: Completions,
+    // the number of traces left to yield, if it fits
+    remaining: Option<usize>,
+    // the length of the prefix which is held fixed
+    fixed: usize,
+}
+
+// The number of symbols in each prefix of a trace, from the empty one up.
+fn prefix_used(trace: &[u32]) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(trace.iter().scan(0, |used, &n| {
+            *used = (n as usize + 1).max(*used);
+            Some(*used)
+        }))
+        .collect()
+}
+
+// Overwrite the accesses of a trace from `from` on with the first way to finish its prefix.
+fn complete_first(completions: &Completions, trace: &mut [u32], used: &mut [usize], from: usize) {
+    let len = trace.len();
+    for i in from..len {
+        // reuse the smallest symbol if that leaves a way to finish, otherwise introduce one
+        if used[i] > 0 && completions.is_possible(len - i - 1, used[i]) {
+            trace[i] = 0;
+            used[i + 1] = used[i];
+        } else {
+            #[allow(clippy::cast_possible_truncation)]
+            let symbol = used[i] as u32;
+            trace[i] = symbol;
+            used[i + 1] = used[i] + 1;
+        }
+    }
+}
+
+// Overwrite the accesses of a trace from `from` on with the last way to finish its prefix.
+fn complete_last(completions: &Completions, trace: &mut [u32], used: &mut [usize], from: usize) {
+    let len = trace.len();
+    for i in from..len {
+        // introduce a symbol if that leaves a way to finish, otherwise reuse the largest, if
+        // there's any way to finish at all
+        let symbol = if completions.is_possible(len - i - 1, used[i] + 1) {
+            used[i]
+        } else {
+            used[i].saturating_sub(1)
+        };
+        #[allow(clippy::cast_possible_truncation)]
+        let access = symbol as u32;
+        trace[i] = access;
+        used[i + 1] = used[i].max(symbol + 1);
+    }
+}
+
+// The completions allowing exactly the traces of the given length which satisfy every constraint.
+fn constrained(len: usize, constraints: &[Constraint]) -> Completions {
+    let mut max_symbols = u32::MAX;
+    // the number of symbols the first i accesses must use
+    let mut min_used = vec![0; len + 1];
+    for &constraint in constraints {
+        match constraint {
+            Constraint::MaxInfinities(max) => {
+                max_symbols = max_symbols.min(u32::try_from(max).unwrap_or(u32::MAX));
+            }
+            Constraint::MinInfinities(min) => min_used[len] = min_used[len].max(min),
+            Constraint::IntroducedBefore { symbol, position } => {
+                let i = position.min(len);
+                min_used[i] = min_used[i].max(symbol as usize + 1);
+            }
+        }
+    }
+    Completions::with_bounds(len, max_symbols, &min_used)
+}
+
+// Finish a prefix in the first or last way, returning the trace and the symbols in its prefixes.
+fn finish(
+    completions: &Completions,
+    prefix: &[u32],
+    len: usize,
+    last: bool,
+) -> (Vec<u32>, Vec<usize>) {
+    let mut trace = prefix.to_vec();
+    trace.resize(len, 0);
+    let mut used = prefix_used(&trace);
+    if last {
+        complete_last(completions, &mut trace, &mut used, prefix.len());
+    } else {
+        complete_first(completions, &mut trace, &mut used, prefix.len());
+    }
+    (trace, used)
 }
 
 impl TraceIter {
     pub fn new(trace_size: usize) -> Self {
+        Self::with_max_symbols(trace_size, u32::MAX)
+    }
+
+    /// Enumerate every canonical trace of each length from 1 to `max_len`, shortest first.
+    pub fn all_up_to(max_len: usize) -> impl Iterator<Item = Trace> {
+        (1..=max_len).flat_map(Self::new)
+    }
+
+    /// Enumerate only the canonical traces of the given length with at most `max_symbols`
+    /// distinct symbols.
+    pub fn with_max_symbols(len: usize, max_symbols: u32) -> Self {
+        Self::bounded(Completions::new(len, max_symbols), &[], len)
+    }
+
+    /// Enumerate only the canonical traces of the given length with exactly `symbols` distinct
+    /// symbols, of which there are [`TraceIter::count_with_symbols`].
+    pub fn with_symbols(len: usize, symbols: usize) -> Self {
+        Self::filtered(
+            len,
+            &[
+                Constraint::MinInfinities(symbols),
+                Constraint::MaxInfinities(symbols),
+            ],
+        )
+    }
+
+    /// Enumerate only the canonical traces of the given length which satisfy every constraint.
+    ///
+    /// Unlike filtering the enumeration afterwards, this never visits a prefix which can't be
+    /// finished, so it stays fast however selective the constraints are, and there are exactly
+    /// [`TraceIter::count_filtered`] of them.
+    pub fn filtered(len: usize, constraints: &[Constraint]) -> Self {
+        Self::bounded(constrained(len, constraints), &[], len)
+    }
+
+    // Enumerate every trace the completions allow which starts with the prefix.
+    fn bounded(completions: Completions, prefix: &[u32], len: usize) -> Self {
+        let symbols = prefix_used(prefix).last().copied().unwrap_or(0);
+        let count = completions.get(len - prefix.len(), symbols);
+        let (next, used) = finish(&completions, prefix, len, false);
+        let (back, back_used) = finish(&completions, prefix, len, true);
         Self {
-            next: Some(vec![0; trace_size]),
+            next: (count > BigUint::ZERO).then_some(next),
+            used,
+            back,
+            back_used,
+            remaining: usize::try_from(count).ok(),
+            completions,
+            fixed: prefix.len(),
         }
     }
+
+    /// Resume enumerating canonical traces of the trace's length, starting with the trace itself.
+    ///
+    /// This lets a long run checkpoint the last trace it processed and pick up from there with
+    /// `.skip(1)`. Returns `None` if the trace isn't canonical.
+    pub fn starting_from(trace: &Trace) -> Option<Self> {
+        let len = trace.trace.len();
+        let rank = trace.rank()?;
+        let completions = Completions::new(len, u32::MAX);
+        let (back, back_used) = finish(&completions, &[], len, true);
+        Some(Self {
+            next: Some(trace.trace.clone()),
+            used: prefix_used(&trace.trace),
+            back,
+            back_used,
+            remaining: usize::try_from(completions.total() - rank).ok(),
+            completions,
+            fixed: 0,
+        })
+    }
+
+    /// Split the enumeration of traces of length `len` with at most `max_symbols` symbols into
+    /// independent parts, one for each canonical prefix of length `prefix_len`.
+    ///
+    /// The parts can be run on separate threads; in order, they enumerate exactly what
+    /// [`TraceIter::with_max_symbols`] does. Longer prefixes give more, smaller parts.
+    pub fn partition(len: usize, max_symbols: u32, prefix_len: usize) -> Vec<Self> {
+        let prefix_len = prefix_len.min(len);
+        let completions = Completions::new(len, max_symbols);
+
+        Self::with_max_symbols(prefix_len, max_symbols)
+            .map(|prefix| Self::bounded(completions.clone(), prefix.as_slice(), len))
+            .collect()
+    }
+
+    /// Split the enumeration of [`TraceIter::filtered`] into independent parts, like
+    /// [`TraceIter::partition`].
+    ///
+    /// Prefixes which can't be finished to satisfy the constraints get no part.
+    pub fn partition_filtered(
+        len: usize,
+        constraints: &[Constraint],
+        prefix_len: usize,
+    ) -> Vec<Self> {
+        let prefix_len = prefix_len.min(len);
+        let completions = constrained(len, constraints);
+        // a prefix need only meet the constraints which end within it
+        let prefix_constraints: Vec<_> = constraints
+            .iter()
+            .copied()
+            .filter(|constraint| match *constraint {
+                Constraint::MaxInfinities(_) => true,
+                Constraint::MinInfinities(_) => prefix_len == len,
+                Constraint::IntroducedBefore { position, .. } => position <= prefix_len,
+            })
+            .collect();
+
+        Self::filtered(prefix_len, &prefix_constraints)
+            .map(|prefix| Self::bounded(completions.clone(), prefix.as_slice(), len))
+            .filter(|part| part.remaining != Some(0))
+            .collect()
+    }
+
+    /// Count the canonical traces of the given length without enumerating them.
+    ///
+    /// This is the Bell number of the length.
+    pub fn count_for(len: usize) -> BigUint {
+        counting::bell(len)
+    }
+
+    /// Count the canonical traces of the given length with exactly `symbols` distinct symbols.
+    ///
+    /// This is a Stirling number of the second kind.
+    pub fn count_with_symbols(len: usize, symbols: usize) -> BigUint {
+        counting::stirling(len, symbols)
+    }
+
+    /// Count the canonical traces [`TraceIter::filtered`] enumerates without enumerating them.
+    pub fn count_filtered(len: usize, constraints: &[Constraint]) -> BigUint {
+        constrained(len, constraints).get(len, 0)
+    }
 }
 
 impl Iterator for TraceIter {
     type Item = Trace;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let ret = self.next.clone();
+        let next = self.next.as_mut()?;
+        let ret = Trace::from(next.clone());
 
-        if let Some(next) = &mut self.next {
-            let current_symbols: Vec<_> = next.iter().unique().collect();
+        if *next == self.back {
+            self.next = None;
+        } else {
+            let len = next.len();
+            let used = &mut self.used;
 
-            for (i, n) in next.clone().iter().enumerate().rev() {
-                // unique is stable, so we can just get the next element
-                let current_symbol_index = current_symbols
-                    .iter()
-                    .position(|&x| x == n)
-                    .expect("The current symbol is in the list of current symbols.");
-
-                if current_symbol_index == current_symbols.len() - 1 {
-                    // at the end of the list of symbols
-                    // two options: either we're already on a unique symbol for this value, or we
-                    // need to go to a unique symbol.
-
-                    if next
-                        .iter()
-                        .position(|x| x == n)
-                        .expect("The current symbol is in the trace.")
-                        != i
-                    {
-                        // first occurance is at another access, we can increment this one to get
-                        // a new symbol
-                        next[i] += 1;
-                        for j in next.iter_mut().skip(i + 1) {
-                            // reset everything else to 0, since we need to redo everything after
-                            // this
-                            *j = 0;
-                        }
-                        break;
+            // Canonical traces never use a symbol more than one past the largest so far, since
+            // otherwise they could be renamed to a smaller one. The successor in lexicographic
+            // order increments the last access which can be while still leaving a way to finish
+            // the trace, and then finishes it in the first way possible.
+            // Usually that's one of the last few accesses, so keeping the symbols used by each
+            // prefix up to date makes this constant amortized time.
+            // The first access is always 0.
+            let completions = &self.completions;
+            let (i, symbol) = (self.fixed.max(1)..len)
+                .rev()
+                .find_map(|i| {
+                    let remaining = len - i - 1;
+                    let symbol = next[i] as usize + 1;
+                    if symbol < used[i] && completions.is_possible(remaining, used[i]) {
+                        Some((i, symbol))
+                    } else if symbol <= used[i] && completions.is_possible(remaining, used[i] + 1) {
+                        Some((i, used[i]))
+                    } else {
+                        None
                     }
-                } else if i == 0 {
-                    // here we get to the beginning of the trace, which should always just be 0 (up
-                    // to renaming), so we're done with iteration
-                    self.next = None;
-                    break; // needed for borrow-checker, obviously if i=0 the loop ends here anyway
-                } else if n <= &next[i - 1] {
-                    // we have this extra constraint to prevent the following "runaway" case:
-                    //
-                    // AA
-                    // AB
-                    // BA
-                    // BB
-                    // BC
-                    // etc.
-                    //
-                    // There's no reason for the ith access to be bigger than one more than the
-                    // i-1st access, given we want renaming symmetry. This check guarantees that.
-                    next[i] = *current_symbols[current_symbol_index + 1];
-                    for j in next.iter_mut().skip(i + 1) {
-                        // reset everything else to 0, since we need to redo everything after
-                        // this
-                        *j = 0;
+                })
+                .expect("only the last trace has no successor");
+            #[allow(clippy::cast_possible_truncation)]
+            let access = symbol as u32;
+            next[i] = access;
+            used[i + 1] = used[i].max(symbol + 1);
+            complete_first(completions, next, used, i + 1);
+        }
+
+        self.remaining = self.remaining.map(|n| n - 1);
+        Some(ret)
+    }
+
+    /// The exact number of traces left, unless it overflows a `usize`, as it does past length 25.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.remaining.map_or((usize::MAX, None), |n| (n, Some(n)))
+    }
+}
+
+/// Enumerate in reverse lexicographic order, from the trace with every symbol distinct down to
+/// the one with a single symbol.
+///
+/// Taking from both ends lets two workers split the enumeration without knowing where the middle
+/// is.
+impl DoubleEndedIterator for TraceIter {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let next = self.next.as_ref()?;
+        let back = &mut self.back;
+        let ret = Trace::from(back.clone());
+
+        if next == back {
+            self.next = None;
+        } else {
+            // The predecessor decrements the last access which can be while still leaving a way
+            // to finish the trace, and then finishes it in the last way possible. Decrementing
+            // never introduces a symbol, so only reusing needs to be possible.
+            let len = back.len();
+            let used = &mut self.back_used;
+            let completions = &self.completions;
+            let i = (self.fixed.max(1)..len)
+                .rev()
+                .find(|&i| back[i] > 0 && completions.is_possible(len - i - 1, used[i]))
+                .expect("only the first trace has no predecessor");
+            back[i] -= 1;
+            used[i + 1] = used[i];
+            complete_last(completions, back, used, i + 1);
+        }
+
+        self.remaining = self.remaining.map(|n| n - 1);
+        Some(ret)
+    }
+}
+
+/// An iterator over canonical traces in which consecutive traces usually differ in only one
+/// access, unlike the lexicographic order of [`TraceIter`].
+///
+/// Each item comes with the index of the first access which differs from the previous trace, so
+/// analyses can be updated from there rather than redone.
+#[derive(Debug, Clone)]
+pub struct GrayIter {
+    next: Option<Vec<u32>>,
+    // used[i] is the number of symbols in the first i accesses of next
+    used: Vec<usize>,
+    // whether each access is counting up, rather than down
+    forward: Vec<bool>,
+    // the first access which differs between next and the previous trace
+    changed: usize,
+}
+
+impl GrayIter {
+    pub fn new(len: usize) -> Self {
+        let mut used = vec![1; len + 1];
+        used[0] = 0;
+        Self {
+            next: Some(vec![0; len]),
+            used,
+            forward: vec![true; len],
+            changed: 0,
+        }
+    }
+}
+
+impl Iterator for GrayIter {
+    type Item = (Trace, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = self.next.as_mut()?;
+        let ret = (Trace::from(next.clone()), self.changed);
+
+        // This is a reflected code: each access sweeps through its possible symbols, from 0 up to
+        // the next new one or back down, and reverses direction whenever an earlier access moves.
+        // Since the access then stays where it is, or at the new symbol, which usually still is,
+        // most steps change only one access.
+        let len = next.len();
+        let (used, forward) = (&mut self.used, &mut self.forward);
+        let step = (1..len).rev().find(|&i| {
+            if forward[i] {
+                (next[i] as usize) < used[i]
+            } else {
+                next[i] > 0
+            }
+        });
+        match step {
+            Some(i) => {
+                if forward[i] {
+                    next[i] += 1;
+                } else {
+                    next[i] -= 1;
+                }
+                used[i + 1] = used[i].max(next[i] as usize + 1);
+                for j in i + 1..len {
+                    forward[j] = !forward[j];
+                    if !forward[j] {
+                        #[allow(clippy::cast_possible_truncation)]
+                        let symbol = used[j] as u32;
+                        next[j] = symbol;
                     }
-                    break;
+                    used[j + 1] = used[j].max(next[j] as usize + 1);
                 }
+                self.changed = i;
             }
+            None => self.next = None,
         }
 
-        ret.map(Trace::from)
+        Some(ret)
     }
 }
 
@@ -177,6 +748,8 @@ impl Iterator for TraceIter {
 mod tests {
     use std::collections::HashSet;
 
+    use itertools::Itertools;
+
     use super::*;
 
     mod stack_distance {
@@ -205,9 +778,9 @@ mod tests {
             ($name:ident: $($in:expr),* => $($out:expr),*; $infinities:expr) => {
                 #[test]
                 fn $name() {
-                    let (freqs, infinities) = Trace::from(vec![$($in),*]).stack_distance_histogram();
-                    assert_eq!(infinities, $infinities);
-                    assert_eq!(freqs, vec![$($out),*]);
+                    let histogram = Trace::from(vec![$($in),*]).stack_distance_histogram();
+                    assert_eq!(histogram.infinities(), $infinities);
+                    assert_eq!(histogram.frequencies(), vec![$($out),*]);
                 }
             };
         }
@@ -238,6 +811,306 @@ mod tests {
         frequency_test!(empty: => );
     }
 
+    #[test]
+    fn parse_works() {
+        assert_eq!("1 2\n3 1".parse(), Ok(Trace::from(vec![1, 2, 3, 1])));
+        assert_eq!("".parse(), Ok(Trace::from(vec![])));
+        assert!("1 a".parse::<Trace>().is_err());
+
+        let wide = "140737488355328 140737488355392 7 140737488355328";
+        let (trace, addresses) = Trace::parse_addresses(wide, 6).unwrap();
+        assert_eq!(trace, Trace::from(vec![0, 1, 2, 0]));
+        assert_eq!(addresses.address(1), 140737488355392 >> 6);
+        assert_eq!(addresses.address(2), 0);
+        assert_eq!(
+            Trace::parse_addresses("64 130 0x40", 6),
+            Ok((Trace::from(vec![1, 2, 1]), Addresses::Identity))
+        );
+    }
+
+    #[test]
+    fn trace_iter_counts() {
+        for len in 0..=8 {
+            assert_eq!(
+                BigUint::from(TraceIter::new(len).count()),
+                TraceIter::count_for(len)
+            );
+            for symbols in 0..=len {
+                let count = TraceIter::new(len)
+                    .filter(|t| t.as_slice().iter().unique().count() == symbols)
+                    .count();
+                assert_eq!(
+                    BigUint::from(count),
+                    TraceIter::count_with_symbols(len, symbols)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn trace_iter_max_symbols() {
+        for len in 0..=7 {
+            for max in 0..=4 {
+                let bounded: Vec<_> = TraceIter::with_max_symbols(len, max).collect();
+                let filtered: Vec<_> = TraceIter::new(len)
+                    .filter(|t| t.as_slice().iter().unique().count() <= max as usize)
+                    .collect();
+                assert_eq!(bounded, filtered);
+            }
+        }
+    }
+
+    #[test]
+    fn trace_iter_size_hint() {
+        let mut iter = TraceIter::with_max_symbols(6, 3);
+        let mut expected = iter.clone().count();
+        assert_eq!(iter.size_hint(), (expected, Some(expected)));
+        while iter.next().is_some() {
+            expected -= 1;
+            assert_eq!(iter.size_hint(), (expected, Some(expected)));
+        }
+        assert_eq!(
+            TraceIter::new(25).size_hint().1,
+            Some(4_638_590_332_229_999_353)
+        );
+        assert_eq!(TraceIter::new(30).size_hint(), (usize::MAX, None));
+    }
+
+    #[test]
+    fn trace_iter_partition() {
+        for prefix_len in 0..=7 {
+            let parts = TraceIter::partition(6, 4, prefix_len);
+            assert_eq!(
+                parts.iter().map(|part| part.size_hint().0).sum::<usize>(),
+                TraceIter::with_max_symbols(6, 4).count()
+            );
+            assert_eq!(
+                parts.into_iter().flatten().collect::<Vec<_>>(),
+                TraceIter::with_max_symbols(6, 4).collect::<Vec<_>>()
+            );
+        }
+        assert_eq!(TraceIter::partition(6, u32::MAX, 3).len(), 5);
+    }
+
+    #[test]
+    fn trace_iter_partition_filtered() {
+        let constraints = [
+            Constraint::MinInfinities(3),
+            Constraint::MaxInfinities(4),
+            Constraint::IntroducedBefore {
+                symbol: 1,
+                position: 2,
+            },
+        ];
+        for prefix_len in 0..=6 {
+            let parts = TraceIter::partition_filtered(6, &constraints, prefix_len);
+            assert!(parts.iter().all(|part| part.size_hint().0 > 0));
+            assert_eq!(
+                parts.into_iter().flatten().collect::<Vec<_>>(),
+                TraceIter::filtered(6, &constraints).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn canonical() {
+        let trace = Trace::from(vec![7, 3, 7, 9, 3]);
+        assert_eq!(trace.canonical(), Trace::from(vec![0, 1, 0, 2, 1]));
+        assert_eq!(trace.reversed(), Trace::from(vec![3, 9, 7, 3, 7]));
+        assert_eq!(trace.canonical().stack_distance(), trace.stack_distance());
+    }
+
+    #[test]
+    fn unrank() {
+        for len in 0..=6 {
+            for (i, trace) in TraceIter::new(len).enumerate() {
+                assert_eq!(Trace::unrank(len, &BigUint::from(i)), Some(trace));
+            }
+            assert_eq!(Trace::unrank(len, &counting::bell(len)), None);
+        }
+    }
+
+    #[test]
+    fn random_canonical() {
+        let mut rng = Rng::new(3);
+        let mut counts = [0; 15];
+        for _ in 0..15_000 {
+            let trace = Trace::random_canonical(4, &mut rng);
+            let rank = usize::try_from(trace.rank().unwrap()).unwrap();
+            counts[rank] += 1;
+        }
+        assert!(counts.iter().all(|&n| (800..1200).contains(&n)));
+
+        assert_eq!(Trace::random_canonical(0, &mut rng), Trace::from(vec![]));
+        assert_eq!(Trace::random_canonical(40, &mut rng).len(), 40);
+    }
+
+    #[test]
+    fn rank() {
+        for len in 0..=6 {
+            for (i, trace) in TraceIter::new(len).enumerate() {
+                assert_eq!(trace.rank(), Some(BigUint::from(i)));
+            }
+        }
+        let index = BigUint::from(123_456_789_u32);
+        assert_eq!(Trace::unrank(30, &index).unwrap().rank(), Some(index));
+        assert_eq!(Trace::from(vec![0, 2, 1]).rank(), None);
+        assert_eq!(Trace::from(vec![1]).rank(), None);
+    }
+
+    #[test]
+    fn trace_iter_filtered() {
+        let infinities = |trace: &Trace| trace.as_slice().iter().max().map_or(0, |&n| n + 1);
+        let introduced =
+            |trace: &Trace, symbol, position| trace.as_slice()[..position].contains(&symbol);
+
+        for len in 0..=6 {
+            let constraints = [
+                Constraint::MinInfinities(2),
+                Constraint::MaxInfinities(4),
+                Constraint::IntroducedBefore {
+                    symbol: 2,
+                    position: 4,
+                },
+            ];
+            let expected: Vec<_> = TraceIter::new(len)
+                .filter(|trace| (2..=4).contains(&infinities(trace)))
+                .filter(|trace| introduced(trace, 2, len.min(4)))
+                .collect();
+            let iter = TraceIter::filtered(len, &constraints);
+            assert_eq!(iter.size_hint().1, Some(expected.len()));
+            assert_eq!(iter.collect::<Vec<_>>(), expected);
+        }
+
+        assert_eq!(
+            TraceIter::filtered(6, &[Constraint::MaxInfinities(2)]).count(),
+            32
+        );
+        assert_eq!(
+            TraceIter::count_filtered(6, &[Constraint::MaxInfinities(2)]),
+            BigUint::from(32_u32)
+        );
+        assert_eq!(TraceIter::count_filtered(30, &[]), TraceIter::count_for(30));
+        assert_eq!(
+            TraceIter::filtered(3, &[Constraint::MinInfinities(4)]).count(),
+            0
+        );
+        assert_eq!(TraceIter::filtered(3, &[]).count(), 5);
+    }
+
+    #[test]
+    fn trace_iter_with_symbols() {
+        for len in 0..=7 {
+            for symbols in 0..=len + 1 {
+                let iter = TraceIter::with_symbols(len, symbols);
+                assert_eq!(
+                    BigUint::from(iter.clone().count()),
+                    TraceIter::count_with_symbols(len, symbols)
+                );
+                assert!(iter
+                    .map(|trace| trace.as_slice().iter().unique().count())
+                    .all(|n| n == symbols));
+            }
+        }
+    }
+
+    #[test]
+    fn trace_iter_all_up_to() {
+        let traces: Vec<_> = TraceIter::all_up_to(4).collect();
+        assert_eq!(traces.len(), 1 + 2 + 5 + 15);
+        assert_eq!(traces[0], Trace::from(vec![0]));
+        assert_eq!(traces[3].len(), 3);
+        assert!(traces.windows(2).all(|pair| pair[0].len() <= pair[1].len()));
+        assert_eq!(TraceIter::all_up_to(0).count(), 0);
+    }
+
+    #[test]
+    fn trace_iter_rev() {
+        for len in 0..=6 {
+            let mut forward: Vec<_> = TraceIter::new(len).collect();
+            forward.reverse();
+            assert_eq!(TraceIter::new(len).rev().collect::<Vec<_>>(), forward);
+
+            let constraints = [Constraint::IntroducedBefore {
+                symbol: 1,
+                position: 3,
+            }];
+            let mut forward: Vec<_> = TraceIter::filtered(len, &constraints).collect();
+            forward.reverse();
+            let backward: Vec<_> = TraceIter::filtered(len, &constraints).rev().collect();
+            assert_eq!(backward, forward);
+        }
+        assert_eq!(
+            TraceIter::new(4).next_back(),
+            Some(Trace::from(vec![0, 1, 2, 3]))
+        );
+
+        // meeting in the middle yields everything once
+        let mut iter = TraceIter::new(5);
+        let mut seen = HashSet::new();
+        while let (Some(a), b) = (iter.next(), iter.next_back()) {
+            assert!(seen.insert(a));
+            if let Some(b) = b {
+                assert!(seen.insert(b));
+            }
+            assert_eq!(iter.size_hint().1, Some(52 - seen.len()));
+        }
+        assert_eq!(seen.len(), 52);
+
+        for part in TraceIter::partition(5, 3, 2) {
+            let mut forward: Vec<_> = part.clone().collect();
+            forward.reverse();
+            assert_eq!(part.rev().collect::<Vec<_>>(), forward);
+        }
+    }
+
+    #[test]
+    fn trace_iter_starting_from() {
+        let all: Vec<_> = TraceIter::new(5).collect();
+        for (i, trace) in all.iter().enumerate() {
+            let resumed: Vec<_> = TraceIter::starting_from(trace).unwrap().collect();
+            assert_eq!(resumed, all[i..]);
+            assert_eq!(
+                TraceIter::starting_from(trace).unwrap().size_hint().1,
+                Some(all.len() - i)
+            );
+        }
+        assert!(TraceIter::starting_from(&Trace::from(vec![0, 2])).is_none());
+    }
+
+    #[test]
+    fn gray_iter() {
+        for len in 0..=7 {
+            let gray: Vec<_> = GrayIter::new(len).collect();
+            let traces: HashSet<_> = gray.iter().map(|(trace, _)| trace.clone()).collect();
+            assert_eq!(traces, TraceIter::new(len).collect());
+            assert_eq!(gray.len(), traces.len());
+
+            for pair in gray.windows(2) {
+                let ((previous, _), (trace, changed)) = (&pair[0], &pair[1]);
+                assert_eq!(
+                    previous.as_slice()[..*changed],
+                    trace.as_slice()[..*changed]
+                );
+                assert_ne!(previous.as_slice()[*changed], trace.as_slice()[*changed]);
+            }
+        }
+
+        let gray: Vec<_> = GrayIter::new(8).map(|(trace, _)| trace).collect();
+        let changes: usize = gray
+            .windows(2)
+            .map(|pair| {
+                pair[0]
+                    .as_slice()
+                    .iter()
+                    .zip(pair[1].as_slice())
+                    .filter(|(a, b)| a != b)
+                    .count()
+            })
+            .sum();
+        assert!(changes < gray.len() * 21 / 20);
+    }
+
     #[test]
     fn trace_iter_works_three() {
         assert_eq!(
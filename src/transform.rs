@@ -0,0 +1,562 @@
+//! Contains seeded transformations of traces.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use tracing::debug;
+
+use crate::rng::{self, Rng};
+use crate::sampling::{self, Sampled};
+use crate::trace::Trace;
+
+/// Shuffle accesses locally, swapping each access with a random one among the next `window`
+/// accesses, including itself.
+///
+/// A window of one leaves the trace unchanged, and no access moves more than `window - 1` places
+/// earlier.
+pub fn swap_within(trace: &Trace, window: usize, seed: u64) -> Trace {
+    assert!(window > 0, "the window must be positive");
+
+    let mut accesses = trace.as_slice().to_vec();
+    let mut rng = Rng::new(seed);
+    for i in 0..accesses.len() {
+        let reach = window.min(accesses.len() - i);
+        #[allow(clippy::cast_possible_truncation)]
+        let j = i + rng.below(reach as u64) as usize;
+        accesses.swap(i, j);
+    }
+    Trace::from(accesses)
+}
+
+/// Replace each access, with probability `fraction`, by a symbol drawn uniformly from
+/// `alphabet_size` symbols.
+pub fn replace_random(trace: &Trace, fraction: f64, alphabet_size: u32, seed: u64) -> Trace {
+    assert!(alphabet_size > 0, "the alphabet must be non-empty");
+
+    let mut rng = Rng::new(seed);
+    #[allow(clippy::cast_possible_truncation)]
+    let accesses: Vec<_> = trace
+        .as_slice()
+        .iter()
+        .map(|&n| {
+            if rng.next_f64() < fraction {
+                rng.below(u64::from(alphabet_size)) as u32
+            } else {
+                n
+            }
+        })
+        .collect();
+    Trace::from(accesses)
+}
+
+/// Drop the low `shift` bits of every symbol, so addresses within the same aligned block of
+/// `2^shift` become one symbol, e.g. a shift of 6 analyzes byte addresses at the granularity of
+/// 64-byte cache lines.
+pub fn coarsen(trace: &Trace, shift: u32) -> Trace {
+    let accesses: Vec<_> = trace
+        .as_slice()
+        .iter()
+        .map(|&n| n.checked_shr(shift).unwrap_or(0))
+        .collect();
+    Trace::from(accesses)
+}
+
+/// Drop each access with probability `fraction`.
+pub fn drop_accesses(trace: &Trace, fraction: f64, seed: u64) -> Trace {
+    let mut rng = Rng::new(seed);
+    let accesses: Vec<_> = trace
+        .as_slice()
+        .iter()
+        .copied()
+        .filter(|_| rng.next_f64() >= fraction)
+        .collect();
+    Trace::from(accesses)
+}
+
+/// Scale a trace up by `factor`, in both length and alphabet, preserving the shape of its stack
+/// distance distribution.
+///
+/// Each symbol `s` becomes the `factor` symbols `s * factor..(s + 1) * factor`, and each access
+/// to it becomes an access to every one of them, in a random order. A reuse at distance `d` then
+/// becomes reuses at distances between `d * factor` and `(d + 1) * factor - 1`, so distances
+/// normalized by the number of distinct symbols are roughly unchanged.
+pub fn scale(trace: &Trace, factor: u32, seed: u64) -> Trace {
+    assert!(factor > 0, "the factor must be positive");
+
+    let mut rng = Rng::new(seed);
+    let mut group: Vec<u32> = (0..factor).collect();
+    let mut accesses = Vec::with_capacity(trace.as_slice().len() * factor as usize);
+    for &n in trace.as_slice() {
+        // a Fisher-Yates shuffle of the group
+        for i in (1..group.len()).rev() {
+            #[allow(clippy::cast_possible_truncation)]
+            let j = rng.below(i as u64 + 1) as usize;
+            group.swap(i, j);
+        }
+        accesses.extend(group.iter().map(|&j| n * factor + j));
+    }
+    Trace::from(accesses)
+}
+
+/// Thin a trace to roughly `rate` of its accesses, keeping the same fraction of every symbol's
+/// accesses.
+///
+/// Each symbol keeps `ceil(count * rate)` of its `count` accesses, spread evenly through them, so
+/// every symbol survives and a more popular symbol never ends up less popular than a less popular
+/// one, though the two may tie.
+///
+/// # Panics
+///
+/// Panics unless `rate` is in `(0, 1]`.
+pub fn thin(trace: &Trace, rate: f64) -> Trace {
+    assert!(
+        rate > 0.0 && rate <= 1.0,
+        "the rate must be a positive fraction"
+    );
+
+    let mut counts: HashMap<u32, usize> = HashMap::new();
+    for &n in trace.as_slice() {
+        *counts.entry(n).or_insert(0) += 1;
+    }
+
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    let keep = |count: usize| (count as f64 * rate).ceil() as usize;
+
+    // keep the jth of a symbol's accesses when j * kept / count steps past an integer
+    let mut seen: HashMap<u32, usize> = HashMap::new();
+    let accesses: Vec<_> = trace
+        .as_slice()
+        .iter()
+        .copied()
+        .filter(|n| {
+            let count = counts[n];
+            let j = seen.entry(*n).or_insert(0);
+            let kept = keep(count);
+            let step = (*j + 1) * kept / count > *j * kept / count;
+            *j += 1;
+            step
+        })
+        .collect();
+    Trace::from(accesses)
+}
+
+/// How tenants' accesses are interleaved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Interleaving {
+    /// Weighted round robin: each access goes to the tenant furthest behind its rate.
+    Deterministic,
+    /// Each access goes to a random tenant with probability proportional to its rate, drawn with
+    /// the given seed.
+    Random(u64),
+}
+
+/// Several tenants' traces interleaved into one.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Composite {
+    /// The interleaved trace, where each tenant's symbols are offset to be disjoint.
+    pub trace: Trace,
+    /// The tenant of each access of the interleaved trace.
+    pub tenants: Vec<usize>,
+    /// The amount added to each tenant's symbols.
+    pub offsets: Vec<u32>,
+}
+
+impl Composite {
+    /// Recover the original trace of a tenant.
+    pub fn tenant_trace(&self, tenant: usize) -> Trace {
+        let accesses: Vec<_> = self
+            .trace
+            .as_slice()
+            .iter()
+            .zip(&self.tenants)
+            .filter(|&(_, &t)| t == tenant)
+            .map(|(&n, _)| n - self.offsets[tenant])
+            .collect();
+        Trace::from(accesses)
+    }
+}
+
+/// Interleave tenants' traces into a composite trace, where tenant `i` issues accesses at relative
+/// rate `rates[i]`.
+///
+/// Each tenant's symbols are shifted past the previous tenants', so tenants never share data. Once
+/// a tenant's trace runs out, the others continue without it.
+pub fn interleave(traces: &[Trace], rates: &[f64], interleaving: Interleaving) -> Composite {
+    assert_eq!(traces.len(), rates.len(), "there must be a rate per tenant");
+    assert!(
+        rates.iter().all(|&r| r > 0.0),
+        "every rate must be positive"
+    );
+
+    let mut offsets = Vec::with_capacity(traces.len());
+    let mut next_offset = 0;
+    for trace in traces {
+        offsets.push(next_offset);
+        next_offset += trace.as_slice().iter().max().map_or(0, |&n| n + 1);
+    }
+
+    let len = traces.iter().map(|t| t.as_slice().len()).sum();
+    let mut positions = vec![0; traces.len()];
+    let mut rng = match interleaving {
+        Interleaving::Deterministic => None,
+        Interleaving::Random(seed) => Some(Rng::new(seed)),
+    };
+
+    let mut accesses = Vec::with_capacity(len);
+    let mut tenants = Vec::with_capacity(len);
+    for _ in 0..len {
+        let remaining = (0..traces.len()).filter(|&i| positions[i] < traces[i].as_slice().len());
+        #[allow(clippy::cast_precision_loss)]
+        let tenant = if let Some(rng) = &mut rng {
+            let candidates: Vec<_> = remaining.collect();
+            let total: f64 = candidates.iter().map(|&i| rates[i]).sum();
+            let mut target = rng.next_f64() * total;
+            *candidates
+                .iter()
+                .find(|&&i| {
+                    target -= rates[i];
+                    target < 0.0
+                })
+                .unwrap_or(&candidates[candidates.len() - 1])
+        } else {
+            // the tenant whose next access is due soonest, ties going to the first
+            remaining
+                .min_by(|&a, &b| {
+                    ((positions[a] + 1) as f64 / rates[a])
+                        .total_cmp(&((positions[b] + 1) as f64 / rates[b]))
+                })
+                .expect("some tenant has accesses left")
+        };
+
+        accesses.push(traces[tenant].as_slice()[positions[tenant]] + offsets[tenant]);
+        tenants.push(tenant);
+        positions[tenant] += 1;
+    }
+
+    Composite {
+        trace: Trace::from(accesses),
+        tenants,
+        offsets,
+    }
+}
+
+/// How symbols are disguised by [`anonymize`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Anonymization {
+    /// Map every symbol through a bijection keyed by a secret, so the same key anonymizes several
+    /// traces consistently.
+    KeyedHash(u64),
+    /// Relabel the symbols which occur as `0..n` in a random order drawn with the given seed.
+    Permutation(u64),
+}
+
+// A keyed bijection on u32: a four-round Feistel network over the two 16-bit halves.
+fn feistel(key: u64, symbol: u32) -> u32 {
+    let (mut left, mut right) = (symbol >> 16, symbol & 0xffff);
+    for round in 0..4 {
+        #[allow(clippy::cast_possible_truncation)]
+        let f = rng::mix(key ^ (round << 32) ^ u64::from(right)) as u32 & 0xffff;
+        (left, right) = (right, left ^ f);
+    }
+    (left << 16) | right
+}
+
+/// Disguise the symbols of a trace, so it can be shared without revealing them.
+///
+/// Distinct symbols stay distinct and repeats stay repeats, so the stack distance histogram, and
+/// every analysis built on it, is unchanged.
+pub fn anonymize(trace: &Trace, anonymization: Anonymization) -> Trace {
+    let accesses: Vec<_> = match anonymization {
+        Anonymization::KeyedHash(key) => {
+            trace.as_slice().iter().map(|&n| feistel(key, n)).collect()
+        }
+        Anonymization::Permutation(seed) => {
+            // label in order of first appearance, then shuffle the labels
+            let mut labels = HashMap::new();
+            for &n in trace.as_slice() {
+                let next = labels.len();
+                labels.entry(n).or_insert(next);
+            }
+
+            let mut rng = Rng::new(seed);
+            #[allow(clippy::cast_possible_truncation)]
+            let mut permutation: Vec<u32> = (0..labels.len() as u32).collect();
+            for i in (1..permutation.len()).rev() {
+                #[allow(clippy::cast_possible_truncation)]
+                let j = rng.below(i as u64 + 1) as usize;
+                permutation.swap(i, j);
+            }
+
+            trace
+                .as_slice()
+                .iter()
+                .map(|n| permutation[labels[n]])
+                .collect()
+        }
+    };
+    Trace::from(accesses)
+}
+
+/// Round timestamps down to a multiple of `quantum`, so fine-grained timing can't identify
+/// accesses.
+pub fn quantize_timestamps(timestamps: &[u64], quantum: u64) -> Vec<u64> {
+    assert!(quantum > 0, "the quantum must be positive");
+    timestamps.iter().map(|&t| t - t % quantum).collect()
+}
+
+/// A spatially sampled trace, with the rate needed to scale its analysis back up.
+///
+/// This displays as the rate on its own line followed by the sampled trace, and parses back from
+/// the same format, so it can be archived or shared in place of the full trace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Downsampled {
+    /// The sampled accesses.
+    pub trace: Trace,
+    /// The fraction of symbols which were kept.
+    pub rate: f64,
+}
+
+impl Downsampled {
+    /// Estimate the stack distances of the original trace.
+    pub fn analyze(&self) -> Sampled {
+        Sampled::from_sampled(&self.trace, self.rate)
+    }
+}
+
+impl Display for Downsampled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.rate)?;
+        for n in self.trace.as_slice() {
+            write!(f, "{} ", n)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Downsampled {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (rate, trace) = s.split_once('\n').unwrap_or((s, ""));
+        Ok(Self {
+            trace: trace.parse()?,
+            rate: rate.trim().parse()?,
+        })
+    }
+}
+
+/// Downsample a trace by SHARDS spatial sampling, keeping every access to a `rate` fraction of
+/// its symbols.
+///
+/// The result is roughly `rate` times the size, but its analysis still approximates the original
+/// miss-ratio curve; see [`Sampled`].
+pub fn downsample(trace: &Trace, rate: f64) -> Downsampled {
+    let accesses: Vec<_> = trace
+        .as_slice()
+        .iter()
+        .copied()
+        .filter(|&n| sampling::is_sampled(n, rate))
+        .collect();
+    debug!(
+        rate,
+        sampled = accesses.len(),
+        accesses = trace.len(),
+        "downsampled trace"
+    );
+    Downsampled {
+        trace: Trace::from(accesses),
+        rate,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use itertools::Itertools;
+
+    use super::*;
+    use crate::generate;
+    use crate::metrics;
+    use crate::mrc::MissRatioCurve;
+
+    #[test]
+    fn coarsen_works() {
+        let trace = Trace::from(vec![0, 63, 64, 130, 4095, 4096]);
+        assert_eq!(coarsen(&trace, 0), trace);
+        assert_eq!(coarsen(&trace, 6), Trace::from(vec![0, 0, 1, 2, 63, 64]));
+        assert_eq!(coarsen(&trace, 12), Trace::from(vec![0, 0, 0, 0, 0, 1]));
+        assert_eq!(coarsen(&trace, 40), Trace::from(vec![0; 6]));
+    }
+
+    #[test]
+    fn swap() {
+        let trace = generate::uniform(1000, 50, 1);
+        assert_eq!(swap_within(&trace, 1, 2), trace);
+
+        let swapped = swap_within(&trace, 4, 2);
+        assert_ne!(swapped, trace);
+        assert_eq!(swapped.frequency_histogram(), trace.frequency_histogram());
+        assert_eq!(swapped, swap_within(&trace, 4, 2));
+    }
+
+    #[test]
+    fn replace() {
+        let trace = generate::uniform(10_000, 50, 1);
+        assert_eq!(replace_random(&trace, 0.0, 50, 2), trace);
+
+        let replaced = replace_random(&trace, 0.1, 1000, 2);
+        let changed = trace
+            .as_slice()
+            .iter()
+            .zip(replaced.as_slice())
+            .filter(|(a, b)| a != b)
+            .count();
+        assert!((900..1100).contains(&changed));
+    }
+
+    #[test]
+    fn drop() {
+        let trace = generate::uniform(10_000, 50, 1);
+        assert_eq!(drop_accesses(&trace, 0.0, 2), trace);
+        assert!(drop_accesses(&trace, 1.0, 2).as_slice().is_empty());
+
+        let dropped = drop_accesses(&trace, 0.1, 2);
+        assert!((8900..9100).contains(&dropped.as_slice().len()));
+    }
+
+    #[test]
+    fn scaling() {
+        let trace = Trace::from(vec![0, 1, 0]);
+        assert_eq!(scale(&trace, 1, 1), trace);
+
+        let scaled = scale(&trace, 3, 1);
+        assert_eq!(scaled.as_slice().len(), 9);
+        let histogram = scaled.stack_distance_histogram();
+        assert_eq!(histogram.infinities(), 6);
+        // the reuse of 0 at distance 1 becomes reuses at distances 3 to 5
+        assert_eq!(histogram.frequencies().iter().sum::<usize>(), 3);
+        assert!(histogram.frequencies()[..3].iter().all(|&n| n == 0));
+
+        let trace = generate::zipf(5000, 100, 1.0, 2);
+        let original = MissRatioCurve::from(&trace);
+        let scaled = MissRatioCurve::from(&scale(&trace, 4, 3));
+        for size in [10, 25, 50] {
+            assert!((original.miss_ratio(size) - scaled.miss_ratio(size * 4)).abs() < 0.05);
+        }
+    }
+
+    #[test]
+    fn thinning() {
+        let trace = Trace::from(vec![0, 0, 1, 0, 2, 0, 1, 0, 1, 0]);
+        assert_eq!(thin(&trace, 1.0), trace);
+        assert_eq!(thin(&trace, 0.5), Trace::from(vec![0, 2, 0, 1, 1, 0]));
+
+        let trace = generate::zipf(20_000, 100, 1.0, 1);
+        let freqs = trace.frequency_histogram();
+        let thinned = thin(&trace, 0.1).frequency_histogram();
+        assert!((1900..2200).contains(&thinned.iter().sum::<usize>()));
+        for a in 0..freqs.len() {
+            for b in 0..freqs.len() {
+                if freqs[a] > freqs[b] {
+                    assert!(thinned[a] >= thinned[b]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn interleaving() {
+        let traces = [Trace::from(vec![0, 1, 0, 1]), Trace::from(vec![5, 5])];
+        let composite = interleave(&traces, &[2.0, 1.0], Interleaving::Deterministic);
+        assert_eq!(composite.offsets, vec![0, 2]);
+        assert_eq!(composite.tenants, vec![0, 0, 1, 0, 0, 1]);
+        assert_eq!(composite.trace, Trace::from(vec![0, 1, 7, 0, 1, 7]));
+        assert_eq!(composite.tenant_trace(1), traces[1]);
+
+        let traces = [
+            generate::uniform(3000, 10, 1),
+            generate::uniform(1000, 10, 2),
+        ];
+        let composite = interleave(&traces, &[3.0, 1.0], Interleaving::Random(3));
+        assert_eq!(composite.tenant_trace(0), traces[0]);
+        assert_eq!(composite.tenant_trace(1), traces[1]);
+        let early = composite.tenants[..2000]
+            .iter()
+            .filter(|&&t| t == 1)
+            .count();
+        assert!((400..600).contains(&early));
+    }
+
+    #[test]
+    fn anonymization() {
+        let trace = generate::zipf(2000, 300, 1.0, 1);
+        for anonymization in [Anonymization::KeyedHash(7), Anonymization::Permutation(7)] {
+            let anonymized = anonymize(&trace, anonymization);
+            assert_ne!(anonymized, trace);
+            assert_eq!(
+                anonymized.stack_distance_histogram(),
+                trace.stack_distance_histogram()
+            );
+            assert_eq!(anonymized, anonymize(&trace, anonymization));
+        }
+
+        // the keyed hash is consistent across traces
+        let prefix = Trace::from(trace.as_slice()[..100].to_vec());
+        assert_eq!(
+            anonymize(&prefix, Anonymization::KeyedHash(7)).as_slice(),
+            &anonymize(&trace, Anonymization::KeyedHash(7)).as_slice()[..100]
+        );
+        assert_ne!(
+            anonymize(&trace, Anonymization::KeyedHash(7)),
+            anonymize(&trace, Anonymization::KeyedHash(8))
+        );
+
+        let permuted = anonymize(&trace, Anonymization::Permutation(7));
+        let symbols = trace.as_slice().iter().unique().count();
+        assert!(permuted.as_slice().iter().all(|&n| (n as usize) < symbols));
+    }
+
+    #[test]
+    fn feistel_is_bijective() {
+        let images: HashSet<_> = (0..100_000).map(|n| feistel(3, n)).collect();
+        assert_eq!(images.len(), 100_000);
+    }
+
+    #[test]
+    fn quantize() {
+        assert_eq!(quantize_timestamps(&[0, 9, 10, 25], 10), vec![0, 0, 10, 20]);
+    }
+
+    #[test]
+    fn downsampling() {
+        let trace = generate::uniform(20_000, 1000, 1);
+        let downsampled = downsample(&trace, 0.1);
+        assert!(downsampled.trace.as_slice().len() < 3000);
+        assert_eq!(
+            downsampled.analyze().miss_ratio_curve(),
+            Sampled::new(&trace, 0.1).miss_ratio_curve()
+        );
+
+        let archived: Downsampled = downsampled.to_string().parse().unwrap();
+        assert_eq!(archived, downsampled);
+        assert!("x\n1 2".parse::<Downsampled>().is_err());
+    }
+
+    #[test]
+    fn small_noise_is_small() {
+        let trace = generate::zipf(10_000, 200, 1.0, 1);
+        let histogram = trace.stack_distance_histogram();
+        let little = swap_within(&trace, 2, 3).stack_distance_histogram();
+        let lots = replace_random(&trace, 0.5, 10_000, 3).stack_distance_histogram();
+        assert!(
+            metrics::earth_movers_distance(&histogram, &little)
+                < metrics::earth_movers_distance(&histogram, &lots)
+        );
+    }
+}
@@ -0,0 +1,166 @@
+//! Contains dry-run checks of trace files, which find problems before an analysis runs.
+
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::io::{self, BufRead};
+use std::mem::size_of;
+
+use crate::trace::parse_address;
+
+/// The number of anomalies a [`Validation`] keeps as examples.
+pub const MAX_EXAMPLES: usize = 10;
+
+/// A token of a trace file which isn't a symbol.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Anomaly {
+    /// The line of the token, counting from one.
+    pub line: usize,
+    pub token: String,
+}
+
+impl Display for Anomaly {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: unparseable symbol `{}`", self.line, self.token)
+    }
+}
+
+/// What a trace file holds, found without analyzing it.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Validation {
+    /// The number of accesses which parse.
+    pub records: usize,
+    /// The number of distinct symbols, after coarsening.
+    pub symbols: usize,
+    /// The smallest and largest addresses, before coarsening, or `None` if there are none.
+    pub range: Option<(u64, u64)>,
+    /// The number of tokens which don't parse.
+    pub anomalies: usize,
+    /// The first few anomalies, up to [`MAX_EXAMPLES`].
+    pub examples: Vec<Anomaly>,
+}
+
+impl Validation {
+    /// Scan a trace of whitespace-separated addresses, coarsened by `shift` bits as an analysis
+    /// would, without stopping at anomalies.
+    pub fn new<R: BufRead>(input: R, shift: u32) -> io::Result<Self> {
+        let mut validation = Self::default();
+        let mut seen = HashSet::new();
+
+        for (i, line) in input.lines().enumerate() {
+            for token in line?.split_whitespace() {
+                let Ok(address) = parse_address(token, 0) else {
+                    validation.anomalies += 1;
+                    if validation.examples.len() < MAX_EXAMPLES {
+                        validation.examples.push(Anomaly {
+                            line: i + 1,
+                            token: token.to_string(),
+                        });
+                    }
+                    continue;
+                };
+                validation.records += 1;
+                seen.insert(address.checked_shr(shift).unwrap_or(0));
+                validation.range = Some(validation.range.map_or((address, address), |(lo, hi)| {
+                    (lo.min(address), hi.max(address))
+                }));
+            }
+        }
+
+        validation.symbols = seen.len();
+        Ok(validation)
+    }
+
+    /// Whether every token parses.
+    pub const fn is_ok(&self) -> bool {
+        self.anomalies == 0
+    }
+
+    /// Estimate the bytes an exact stack distance analysis needs.
+    ///
+    /// This counts the trace and its distances, which grow with the records, and the LRU stack
+    /// and histogram, which grow with the symbols.
+    pub const fn estimated_bytes(&self) -> usize {
+        let per_record = size_of::<u32>() + size_of::<Option<usize>>();
+        let per_symbol = size_of::<u32>() + size_of::<usize>();
+        self.records * per_record + self.symbols * per_symbol
+    }
+}
+
+// Render a count of bytes with a binary unit.
+#[allow(clippy::cast_precision_loss)]
+fn human_bytes(bytes: usize) -> String {
+    let mut size = bytes as f64;
+    for unit in ["B", "KiB", "MiB", "GiB"] {
+        if size < 1024.0 {
+            return format!("{:.1} {}", size, unit);
+        }
+        size /= 1024.0;
+    }
+    format!("{:.1} TiB", size)
+}
+
+impl Display for Validation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rows = [
+            ("records", self.records.to_string()),
+            ("symbols", self.symbols.to_string()),
+            (
+                "address range",
+                self.range
+                    .map_or_else(|| "-".to_string(), |(lo, hi)| format!("{}..={}", lo, hi)),
+            ),
+            ("anomalies", self.anomalies.to_string()),
+            ("estimated memory", human_bytes(self.estimated_bytes())),
+        ];
+        for (name, value) in rows {
+            writeln!(f, "{:<18}{}", name, value)?;
+        }
+        for anomaly in &self.examples {
+            writeln!(f, "  {}", anomaly)?;
+        }
+        if self.anomalies > self.examples.len() {
+            writeln!(f, "  and {} more", self.anomalies - self.examples.len())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validation_works() {
+        let validation = Validation::new("3 7 3\nx 9\n\n12 -1\n".as_bytes(), 0).unwrap();
+        assert_eq!(validation.records, 5);
+        assert_eq!(validation.symbols, 4);
+        assert_eq!(validation.range, Some((3, 12)));
+        assert!(!validation.is_ok());
+        assert_eq!(
+            validation.examples,
+            [
+                Anomaly {
+                    line: 2,
+                    token: "x".to_string()
+                },
+                Anomaly {
+                    line: 4,
+                    token: "-1".to_string()
+                },
+            ]
+        );
+        assert_eq!(
+            validation.estimated_bytes(),
+            5 * (4 + size_of::<Option<usize>>()) + 4 * (4 + size_of::<usize>())
+        );
+
+        let wide = Validation::new("140737488355328 140737488355336 128".as_bytes(), 6).unwrap();
+        assert!(wide.is_ok());
+        assert_eq!(wide.symbols, 2);
+        assert_eq!(wide.range, Some((128, 140_737_488_355_336)));
+
+        let empty = Validation::new("".as_bytes(), 0).unwrap();
+        assert!(empty.is_ok());
+        assert_eq!(empty.range, None);
+    }
+}
@@ -0,0 +1,107 @@
+//! Contains self-checks of the consistency of the analyses.
+
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use crate::footprint::Footprint;
+use crate::trace::Trace;
+
+/// Which consistency properties hold for a trace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Report {
+    /// Whether the number of infinite distances is the number of distinct symbols, since an
+    /// infinity means a new symbol.
+    pub infinities_match_symbols: bool,
+    /// Whether the stack distance histogram counts every access exactly once.
+    pub histogram_sums_to_length: bool,
+    /// Whether every distance is less than the number of symbols accessed before it, i.e. the
+    /// depth of the stack.
+    pub distances_bounded: bool,
+    /// Whether the footprint of the whole trace is the number of distinct symbols.
+    pub footprint_matches_symbols: bool,
+}
+
+impl Report {
+    /// Whether every property holds.
+    pub const fn is_ok(&self) -> bool {
+        self.infinities_match_symbols
+            && self.histogram_sums_to_length
+            && self.distances_bounded
+            && self.footprint_matches_symbols
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rows = [
+            ("infinities match symbols", self.infinities_match_symbols),
+            ("histogram sums to length", self.histogram_sums_to_length),
+            ("distances bounded", self.distances_bounded),
+            ("footprint matches symbols", self.footprint_matches_symbols),
+        ];
+        for (name, ok) in rows {
+            writeln!(f, "{}: {}", name, if ok { "ok" } else { "FAILED" })?;
+        }
+        Ok(())
+    }
+}
+
+/// Check that the analyses of a trace are consistent with each other.
+pub fn check_invariants(trace: &Trace) -> Report {
+    let symbols = trace.as_slice().iter().collect::<HashSet<_>>().len();
+    let distances = trace.stack_distance();
+    let histogram = trace.stack_distance_histogram();
+
+    let mut seen = 0;
+    let distances_bounded = distances.iter().all(|distance| match distance {
+        Some(d) => *d < seen,
+        None => {
+            seen += 1;
+            true
+        }
+    });
+
+    #[allow(clippy::cast_precision_loss)]
+    let footprint_matches_symbols =
+        (Footprint::new(trace).get(trace.len()) - symbols as f64).abs() < 1e-9;
+
+    Report {
+        infinities_match_symbols: histogram.infinities() == symbols
+            && trace
+                .frequency_histogram()
+                .iter()
+                .filter(|&&n| n != 0)
+                .count()
+                == symbols,
+        histogram_sums_to_length: histogram.total() == trace.len(),
+        distances_bounded,
+        footprint_matches_symbols,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::TraceIter;
+
+    #[test]
+    fn enumerated_traces_are_consistent() {
+        for trace in TraceIter::all_up_to(6) {
+            assert!(check_invariants(&trace).is_ok(), "{}", trace);
+        }
+        assert!(check_invariants(&Trace::from(vec![])).is_ok());
+        assert!(check_invariants(&Trace::from(vec![9, 4, 9, 7, 4])).is_ok());
+    }
+
+    #[test]
+    fn display() {
+        let report = Report {
+            infinities_match_symbols: true,
+            histogram_sums_to_length: true,
+            distances_bounded: false,
+            footprint_matches_symbols: true,
+        };
+        assert!(!report.is_ok());
+        assert!(report.to_string().contains("distances bounded: FAILED\n"));
+    }
+}
@@ -0,0 +1,105 @@
+//! Contains a JavaScript API, for analyzing traces in the browser.
+//!
+//! Build it with `wasm-pack build --target web -- --features wasm`.
+
+use wasm_bindgen::prelude::*;
+
+use crate::histogram::Histogram;
+use crate::mrc::MissRatioCurve;
+use crate::trace::LruStack;
+
+// Counts and distances go to JavaScript as u32, saturating past its maximum.
+fn to_u32(n: usize) -> u32 {
+    u32::try_from(n).unwrap_or(u32::MAX)
+}
+
+/// Computes stack distances one access at a time, keeping the LRU stack and histogram so far.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Default)]
+pub struct Analyzer {
+    stack: LruStack,
+    histogram: Histogram,
+}
+
+#[wasm_bindgen]
+impl Analyzer {
+    /// Create an analyzer which hasn't seen any accesses.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Access a symbol, returning its stack distance, or `undefined` if it's the first access.
+    pub fn access(&mut self, symbol: u32) -> Option<u32> {
+        let distance = self.stack.access(symbol);
+        self.histogram.record(distance);
+        distance.map(to_u32)
+    }
+
+    /// Access every symbol of an array in order.
+    pub fn feed(&mut self, symbols: &[u32]) {
+        for &symbol in symbols {
+            self.access(symbol);
+        }
+    }
+
+    /// The symbols on the LRU stack, most recently accessed first.
+    pub fn stack(&self) -> Vec<u32> {
+        self.stack.symbols().collect()
+    }
+
+    /// The number of accesses at each finite distance.
+    pub fn frequencies(&self) -> Vec<u32> {
+        self.histogram
+            .frequencies()
+            .iter()
+            .map(|&count| to_u32(count))
+            .collect()
+    }
+
+    /// The number of first accesses, whose distance is infinite.
+    pub fn infinities(&self) -> u32 {
+        to_u32(self.histogram.infinities())
+    }
+
+    /// The miss ratio of an LRU cache at each size, from zero up.
+    #[wasm_bindgen(js_name = missRatios)]
+    pub fn miss_ratios(&self) -> Vec<f64> {
+        MissRatioCurve::from_histogram(&self.histogram)
+            .ratios()
+            .to_vec()
+    }
+
+    /// Forget every access.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Analyze a whole trace at once.
+#[wasm_bindgen]
+pub fn analyze(symbols: &[u32]) -> Analyzer {
+    let mut analyzer = Analyzer::new();
+    analyzer.feed(symbols);
+    analyzer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn analyzer() {
+        let mut analyzer = analyze(&[1, 2, 1]);
+        assert_eq!(analyzer.stack(), [1, 2]);
+        assert_eq!(analyzer.access(2), Some(1));
+        assert_eq!(analyzer.access(3), None);
+        assert_eq!(analyzer.stack(), [3, 2, 1]);
+        assert_eq!(analyzer.frequencies(), [0, 2]);
+        assert_eq!(analyzer.infinities(), 3);
+
+        analyzer.reset();
+        assert!(analyzer.stack().is_empty());
+        assert_eq!(analyzer.miss_ratios(), [0.0]);
+    }
+}
@@ -0,0 +1,173 @@
+//! Contains online analysis of a sliding window of the most recent accesses, for live monitoring
+//! where old history shouldn't dominate.
+//!
+//! The analysis is always that of the trace of just the last `size` accesses. When an access
+//! leaves the window, the next access of its symbol, if it's still in the window, becomes a first
+//! access; otherwise the symbol leaves the window too. Either way it takes constant time on top
+//! of computing distances.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::histogram::Histogram;
+use crate::mrc::MissRatioCurve;
+use crate::trace::LruStack;
+
+// An access in the window.
+#[derive(Debug, Clone, Copy)]
+struct Access {
+    address: u64,
+    // the distance counted in the histogram
+    distance: Option<usize>,
+    // the time of the next access of the symbol, if it's been accessed again
+    next: Option<u64>,
+}
+
+/// Computes the stack distances of the last `size` accesses as they arrive.
+#[derive(Debug, Clone)]
+pub struct SlidingWindow {
+    size: usize,
+    // the accesses in the window, oldest first
+    accesses: VecDeque<Access>,
+    // the time of the oldest access in the window
+    start: u64,
+    // the time of the last access of each address in the window
+    last: HashMap<u64, u64>,
+    // the addresses are arbitrary, so number those in the window, reusing the numbers of those
+    // which leave it
+    ids: HashMap<u64, u32>,
+    free: Vec<u32>,
+    // the numbers in the window, and no others
+    stack: LruStack,
+    histogram: Histogram,
+}
+
+impl SlidingWindow {
+    /// Create a window of the last `size` accesses, which hasn't seen any.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero.
+    pub fn new(size: usize) -> Self {
+        assert!(size > 0, "a window must hold an access");
+        Self {
+            size,
+            accesses: VecDeque::with_capacity(size),
+            start: 0,
+            last: HashMap::new(),
+            ids: HashMap::new(),
+            free: Vec::new(),
+            stack: LruStack::default(),
+            histogram: Histogram::default(),
+        }
+    }
+
+    /// Access an address, expiring the oldest access if the window is full, and return its stack
+    /// distance within the window, or `None` if it's the first there.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the window holds 2^32 distinct addresses.
+    pub fn access(&mut self, address: u64) -> Option<usize> {
+        if self.accesses.len() == self.size {
+            self.expire();
+        }
+
+        let id = match self.ids.get(&address) {
+            Some(&id) => id,
+            None => {
+                // with no free numbers, every number is in use
+                let id = self.free.pop().unwrap_or_else(|| {
+                    u32::try_from(self.ids.len()).expect("fewer than 2^32 distinct addresses")
+                });
+                self.ids.insert(address, id);
+                id
+            }
+        };
+
+        let now = self.start + self.accesses.len() as u64;
+        let distance = self.stack.access(id);
+        if let Some(previous) = self.last.insert(address, now) {
+            self.get_mut(previous).next = Some(now);
+        }
+        self.histogram.record(distance);
+        self.accesses.push_back(Access {
+            address,
+            distance,
+            next: None,
+        });
+        distance
+    }
+
+    // Drop the oldest access from the window.
+    fn expire(&mut self) {
+        let oldest = self.accesses.pop_front().expect("the window is full");
+        self.start += 1;
+        self.histogram.forget(oldest.distance);
+        match oldest.next {
+            // the next access's reuse now reaches outside the window
+            Some(next) => {
+                let next = self.get_mut(next);
+                let distance = next.distance.take();
+                self.histogram.forget(distance);
+                self.histogram.record(None);
+            }
+            // this was the address's last access, so it's at the bottom of the stack
+            None => {
+                self.last.remove(&oldest.address);
+                let id = self
+                    .ids
+                    .remove(&oldest.address)
+                    .expect("the address is numbered");
+                self.free.push(id);
+                self.stack.truncate(self.last.len());
+            }
+        }
+    }
+
+    // The access in the window at a time.
+    fn get_mut(&mut self, time: u64) -> &mut Access {
+        #[allow(clippy::cast_possible_truncation)]
+        let index = (time - self.start) as usize;
+        &mut self.accesses[index]
+    }
+
+    /// The histogram of the accesses in the window.
+    pub fn histogram(&self) -> &Histogram {
+        &self.histogram
+    }
+
+    /// The miss ratio curve of the accesses in the window.
+    pub fn mrc(&self) -> MissRatioCurve {
+        MissRatioCurve::from_histogram(&self.histogram)
+    }
+
+    /// The number of accesses in the window, which is `size` once it's full.
+    pub fn len(&self) -> usize {
+        self.accesses.len()
+    }
+
+    /// Whether the window has no accesses yet.
+    pub fn is_empty(&self) -> bool {
+        self.accesses.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::Trace;
+
+    #[test]
+    fn matches_last_accesses() {
+        let trace = crate::generate::uniform(400, 30, 3);
+        let symbols = trace.as_slice();
+        let mut window = SlidingWindow::new(50);
+        for (i, &symbol) in symbols.iter().enumerate() {
+            // wide addresses, which the window numbers itself
+            window.access(u64::from(symbol) << 32);
+            let recent = Trace::from(symbols[(i + 1).saturating_sub(50)..=i].to_vec());
+            assert_eq!(window.histogram(), &recent.stack_distance_histogram());
+        }
+        assert_eq!(window.len(), 50);
+    }
+}
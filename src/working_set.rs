@@ -0,0 +1,155 @@
+//! Contains Denning's working set model.
+
+use std::collections::HashMap;
+
+use crate::mrc::MissRatioCurve;
+use crate::timeseries;
+use crate::trace::Trace;
+
+/// Calculate the working set size over time.
+///
+/// Returns a vector where the ith entry is `W(i, window)`, the number of distinct symbols among
+/// the last `window` accesses up to and including the ith.
+pub fn working_set_sizes(trace: &Trace, window: usize) -> Vec<usize> {
+    let accesses = trace.as_slice();
+    let mut counts: HashMap<u32, usize> = HashMap::new();
+
+    accesses
+        .iter()
+        .enumerate()
+        .map(|(i, &curr)| {
+            *counts.entry(curr).or_insert(0) += 1;
+
+            // slide the oldest access out of the window
+            if i >= window {
+                let old = accesses[i - window];
+                let count = counts
+                    .get_mut(&old)
+                    .expect("the old access is in the window");
+                *count -= 1;
+                if *count == 0 {
+                    counts.remove(&old);
+                }
+            }
+
+            counts.len()
+        })
+        .collect()
+}
+
+/// The memory usage and miss ratio of a replacement policy at one setting of its parameter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tradeoff {
+    /// The window length or cache size.
+    pub parameter: usize,
+    /// The mean number of resident symbols over the trace.
+    pub mean_size: f64,
+    /// The fraction of accesses which miss.
+    pub miss_ratio: f64,
+}
+
+/// Calculate the tradeoff of the working set policy at each window length.
+///
+/// The working set policy keeps exactly the symbols among the last `window` accesses resident,
+/// so an access misses if its symbol wasn't accessed in the preceding `window` accesses.
+pub fn working_set_tradeoffs(trace: &Trace, windows: &[usize]) -> Vec<Tradeoff> {
+    let accesses = trace.as_slice();
+    if accesses.is_empty() {
+        return Vec::new();
+    }
+
+    // the time since the previous access to the same symbol, or None on a first access
+    let mut last = HashMap::new();
+    let gaps: Vec<_> = accesses
+        .iter()
+        .enumerate()
+        .map(|(i, &curr)| last.insert(curr, i).map(|prev| i - prev))
+        .collect();
+
+    #[allow(clippy::cast_precision_loss)]
+    windows
+        .iter()
+        .map(|&window| {
+            let misses = gaps
+                .iter()
+                .filter(|gap| gap.is_none_or(|gap| gap > window))
+                .count();
+            // the resident set at each access is the working set just before it
+            let resident: usize = std::iter::once(0)
+                .chain(working_set_sizes(trace, window))
+                .take(accesses.len())
+                .sum();
+
+            Tradeoff {
+                parameter: window,
+                mean_size: resident as f64 / accesses.len() as f64,
+                miss_ratio: misses as f64 / accesses.len() as f64,
+            }
+        })
+        .collect()
+}
+
+/// Calculate the tradeoff of an LRU cache at each size, for comparison with the working set
+/// policy.
+///
+/// A cache holds every symbol accessed so far until it fills up, so its mean size is smaller than
+/// its capacity.
+pub fn lru_tradeoffs(trace: &Trace, sizes: &[usize]) -> Vec<Tradeoff> {
+    let accesses = trace.as_slice();
+    if accesses.is_empty() {
+        return Vec::new();
+    }
+
+    let mrc = MissRatioCurve::from(trace);
+    // the number of distinct symbols before each access
+    let footprints: Vec<_> = std::iter::once(0)
+        .chain(timeseries::per_access(trace).iter().map(|s| s.footprint))
+        .take(accesses.len())
+        .collect();
+
+    #[allow(clippy::cast_precision_loss)]
+    sizes
+        .iter()
+        .map(|&size| Tradeoff {
+            parameter: size,
+            mean_size: footprints.iter().map(|&fp| fp.min(size)).sum::<usize>() as f64
+                / accesses.len() as f64,
+            miss_ratio: mrc.miss_ratio(size),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn working_set() {
+        let trace = Trace::from(vec![0, 1, 0, 2, 2, 2, 3]);
+        assert_eq!(working_set_sizes(&trace, 3), vec![1, 2, 2, 3, 2, 1, 2]);
+        assert_eq!(working_set_sizes(&trace, 100), vec![1, 2, 2, 3, 3, 3, 4]);
+        assert_eq!(working_set_sizes(&trace, 0), vec![0; 7]);
+    }
+
+    #[test]
+    fn tradeoffs() {
+        let trace = Trace::from(vec![0, 1, 0, 1, 2, 2, 0]);
+
+        let ws = working_set_tradeoffs(&trace, &[0, 2, 4]);
+        assert_eq!(ws[0].miss_ratio, 1.0);
+        assert_eq!(ws[0].mean_size, 0.0);
+        // the last access to 0 misses with a window of 2, but hits with 4
+        assert_eq!(ws[1].miss_ratio, 4.0 / 7.0);
+        assert_eq!(ws[1].mean_size, 10.0 / 7.0);
+        assert_eq!(ws[2].miss_ratio, 3.0 / 7.0);
+
+        let lru = lru_tradeoffs(&trace, &[0, 2, 3]);
+        assert_eq!(lru[0].miss_ratio, 1.0);
+        assert_eq!(lru[1].miss_ratio, 4.0 / 7.0);
+        assert_eq!(lru[1].mean_size, 11.0 / 7.0);
+        assert_eq!(lru[2].miss_ratio, 3.0 / 7.0);
+        assert_eq!(lru[2].parameter, 3);
+
+        assert!(working_set_tradeoffs(&Trace::from(vec![]), &[1]).is_empty());
+    }
+}